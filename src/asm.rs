@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+use crate::cpu::{AddressingMode, CpuVariant, RESET_VECTOR};
+use crate::disasm::{describe_branch, instruction_length, lookup};
+use crate::memory::Memory;
+
+/// An operand value: a number, or a label resolved in the second pass
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number { value: u16, wide: bool }, // wide: written with more than two hex digits or over 255
+    Label(String),
+    Here, // the address of the current line, `*` or `$`
+    Offset(Box<Expr>, i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+    Instruction { opcode: u8, mode: AddressingMode, operand: Option<Expr> },
+}
+
+struct Line {
+    number: usize,
+    label: Option<String>,
+    item: Option<Item>,
+}
+
+/// Assemble `source` for loading at `origin`.
+///
+/// Each line holds an optional `label:`, then an instruction, a `.byte`
+/// directive, or a `.word` directive emitting 16-bit values low byte first;
+/// `;` starts a comment. Operands use the syntax the disassembler prints
+/// (`#$42`, `$10`, `$0200,X`, `($10),Y`, `($FFFC)`, `A`), and numbers may
+/// also be decimal. A label can stand in for any address, as can `*` (or a
+/// lone `$`) for the address of the current line, and either may be followed
+/// by `+n` or `-n`. Values of up to two hex digits use zero page addressing
+/// where the instruction has it.
+pub fn assemble(source: &str, origin: u16, variant: CpuVariant) -> Result<Vec<u8>, String> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| parse_line(i + 1, text, variant))
+        .collect::<Result<Vec<Line>, String>>()?;
+
+    // First pass: addresses of labels
+    let mut labels = HashMap::new();
+    let mut address = origin as u32;
+    for line in &lines {
+        if let Some(label) = &line.label {
+            if labels.insert(label.clone(), address as u16).is_some() {
+                return Err(format!("line {}: label {} defined twice", line.number, label));
+            }
+        }
+        address += line.item.as_ref().map_or(0, item_length) as u32;
+        if address > 0x10000 {
+            return Err(format!("line {}: program runs past $FFFF", line.number));
+        }
+    }
+
+    // Second pass: emit bytes
+    let mut bytes = Vec::new();
+    for line in &lines {
+        let address = origin.wrapping_add(bytes.len() as u16);
+        let error = |message: String| format!("line {}: {}", line.number, message);
+        match &line.item {
+            None => {}
+            Some(Item::Bytes(values)) => {
+                for value in values {
+                    bytes.push(byte_value(evaluate(value, &labels, address).map_err(error)?).map_err(error)?);
+                }
+            }
+            Some(Item::Words(values)) => {
+                for value in values {
+                    bytes.extend_from_slice(&evaluate(value, &labels, address).map_err(error)?.to_le_bytes());
+                }
+            }
+            Some(Item::Instruction { opcode, mode, operand }) => {
+                bytes.push(*opcode);
+                if let Some(operand) = operand {
+                    let value = evaluate(operand, &labels, address).map_err(error)?;
+                    if *mode == AddressingMode::Relative {
+                        let offset = value as i32 - (address as i32 + 2);
+                        if !(-128..=127).contains(&offset) {
+                            return Err(error(format!("branch offset {} is out of range (-128 to +127)", describe_branch(address, offset))));
+                        }
+                        bytes.push(offset as i8 as u8);
+                    } else if mode.operand_bytes() == 1 {
+                        bytes.push(byte_value(value).map_err(error)?);
+                    } else {
+                        bytes.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Assemble `source`, load it at `origin` and point the reset vector there,
+/// ready for `CPU::reset`. Returns the assembled bytes.
+///
+/// ```
+/// use mos6502_emulator::asm::load_source;
+/// use mos6502_emulator::cpu::{CpuVariant, CPU};
+/// use mos6502_emulator::memory::Memory;
+///
+/// let mut memory = Memory::new();
+/// load_source(&mut memory, "LDA #$42\nBRK", 0x8000, CpuVariant::Nmos6502).unwrap();
+///
+/// let mut cpu = CPU::new();
+/// cpu.reset(&mut memory);
+/// cpu.step(&mut memory);
+/// assert_eq!(cpu.get_register_a(), 0x42);
+/// ```
+pub fn load_source(memory: &mut Memory, source: &str, origin: u16, variant: CpuVariant) -> Result<Vec<u8>, String> {
+    let bytes = assemble(source, origin, variant)?;
+    memory.load_rom(&bytes, origin);
+    memory.write_u16(RESET_VECTOR, origin);
+    Ok(bytes)
+}
+
+fn item_length(item: &Item) -> usize {
+    match item {
+        Item::Bytes(values) => values.len(),
+        Item::Words(values) => values.len() * 2,
+        Item::Instruction { mode, .. } => instruction_length(*mode) as usize,
+    }
+}
+
+fn parse_line(number: usize, text: &str, variant: CpuVariant) -> Result<Line, String> {
+    let error = |message: String| format!("line {}: {}", number, message);
+    let mut text = text.split(';').next().unwrap().trim();
+
+    let mut label = None;
+    if let Some((name, rest)) = text.split_once(':') {
+        let name = name.trim();
+        if !is_identifier(name) {
+            return Err(error(format!("invalid label {:?}", name)));
+        }
+        label = Some(name.to_string());
+        text = rest.trim();
+    }
+
+    if text.is_empty() {
+        return Ok(Line { number, label, item: None });
+    }
+
+    let (word, operand) = match text.split_once(char::is_whitespace) {
+        Some((word, operand)) => (word, operand.trim()),
+        None => (text, ""),
+    };
+
+    let item = if word.eq_ignore_ascii_case(".byte") || word.eq_ignore_ascii_case(".word") {
+        let values = operand
+            .split(',')
+            .map(|value| parse_expr(value.trim()))
+            .collect::<Result<Vec<Expr>, String>>()
+            .map_err(error)?;
+        if word.eq_ignore_ascii_case(".byte") {
+            Item::Bytes(values)
+        } else {
+            Item::Words(values)
+        }
+    } else {
+        parse_instruction(&word.to_ascii_uppercase(), operand, variant).map_err(error)?
+    };
+
+    Ok(Line { number, label, item: Some(item) })
+}
+
+fn parse_instruction(mnemonic: &str, operand: &str, variant: CpuVariant) -> Result<Item, String> {
+    use AddressingMode::*;
+
+    let upper = operand.to_ascii_uppercase();
+    let (candidates, value): (Vec<AddressingMode>, Option<Expr>) = if operand.is_empty() {
+        (vec![Implicit, Accumulator], None)
+    } else if upper == "A" {
+        (vec![Accumulator], None)
+    } else if let Some(value) = operand.strip_prefix('#') {
+        (vec![Immediate], Some(parse_expr(value.trim())?))
+    } else if let Some(inner) = upper.strip_prefix('(') {
+        if let Some(value) = inner.strip_suffix(",X)") {
+            (vec![IndexedIndirect], Some(parse_expr(&operand[1..1 + value.len()])?))
+        } else if let Some(value) = inner.strip_suffix("),Y") {
+            (vec![IndirectIndexed], Some(parse_expr(&operand[1..1 + value.len()])?))
+        } else if let Some(value) = inner.strip_suffix(')') {
+            (vec![Indirect], Some(parse_expr(&operand[1..1 + value.len()])?))
+        } else {
+            return Err(format!("invalid operand {:?}", operand));
+        }
+    } else {
+        let (value, zero_page, absolute) = if let Some(value) = upper.strip_suffix(",X") {
+            (value, ZeroPageX, AbsoluteX)
+        } else if let Some(value) = upper.strip_suffix(",Y") {
+            (value, ZeroPageY, AbsoluteY)
+        } else {
+            (upper.as_str(), ZeroPage, Absolute)
+        };
+        let value = parse_expr(operand[..value.len()].trim())?;
+        let candidates = match value {
+            Expr::Number { wide: false, .. } => vec![Relative, zero_page, absolute],
+            _ => vec![Relative, absolute],
+        };
+        (candidates, Some(value))
+    };
+
+    for mode in candidates {
+        let opcode = (0..=255u8).find(|&opcode| {
+            lookup(opcode, variant).is_some_and(|info| info.mnemonic == mnemonic && info.mode == mode)
+        });
+        if let Some(opcode) = opcode {
+            return Ok(Item::Instruction { opcode, mode, operand: value });
+        }
+    }
+
+    if (0..=255u8).any(|opcode| lookup(opcode, variant).is_some_and(|info| info.mnemonic == mnemonic)) {
+        Err(format!("{} does not take operand {:?}", mnemonic, operand))
+    } else {
+        Err(format!("unknown instruction {}", mnemonic))
+    }
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    // A term plus or minus a number; the first character may be `*` itself
+    if let Some(i) = text.rfind(['+', '-']).filter(|&i| i > 0) {
+        let base = parse_term(text[..i].trim())?;
+        let offset = match parse_term(text[i + 1..].trim())? {
+            Expr::Number { value, .. } => value as i32,
+            _ => return Err(format!("invalid offset in {:?}", text)),
+        };
+        let offset = if text[i..].starts_with('-') { -offset } else { offset };
+        return Ok(Expr::Offset(Box::new(base), offset));
+    }
+    parse_term(text)
+}
+
+fn parse_term(text: &str) -> Result<Expr, String> {
+    let number = |digits: &str, radix: u32, wide: bool| {
+        u16::from_str_radix(digits, radix)
+            .map(|value| Expr::Number { value, wide })
+            .map_err(|_| format!("invalid number {:?}", text))
+    };
+
+    if text == "*" || text == "$" {
+        Ok(Expr::Here)
+    } else if let Some(hex) = text.strip_prefix('$') {
+        number(hex, 16, hex.len() > 2)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        let wide = text.parse::<u32>().is_ok_and(|value| value > 0xFF);
+        number(text, 10, wide)
+    } else if is_identifier(text) {
+        Ok(Expr::Label(text.to_string()))
+    } else {
+        Err(format!("invalid operand {:?}", text))
+    }
+}
+
+fn evaluate(expr: &Expr, labels: &HashMap<String, u16>, here: u16) -> Result<u16, String> {
+    match expr {
+        Expr::Number { value, .. } => Ok(*value),
+        Expr::Label(name) => labels.get(name).copied().ok_or_else(|| format!("undefined label {}", name)),
+        Expr::Here => Ok(here),
+        Expr::Offset(base, offset) => Ok(evaluate(base, labels, here)?.wrapping_add(*offset as u16)),
+    }
+}
+
+fn byte_value(value: u16) -> Result<u8, String> {
+    u8::try_from(value).map_err(|_| format!("value ${:04X} does not fit in a byte", value))
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_round_trips_through_disassembler() {
+        let source = "
+            LDA #$42
+            STA $0200,X
+            LDA ($10),Y
+            JMP ($FFFC)
+            BNE $8000
+            ASL A
+            BRK
+        ";
+        let bytes = assemble(source, 0x8000, CpuVariant::Nmos6502).unwrap();
+        assert_eq!(bytes, vec![
+            0xA9, 0x42, 0x9D, 0x00, 0x02, 0xB1, 0x10, 0x6C, 0xFC, 0xFF, 0xD0, 0xF4, 0x0A, 0x00,
+        ]);
+
+        let text: Vec<String> = disassemble(&bytes, 0x8000, CpuVariant::Nmos6502)
+            .into_iter()
+            .map(|i| i.text)
+            .collect();
+        let expected: Vec<&str> = source.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_labels_and_zero_page() {
+        let source = "
+            start:  LDX #10      ; count down
+            loop:   DEX
+                    STX $10      ; zero page
+                    STA $0010    ; absolute, as written
+                    BNE loop
+                    JMP start
+            data:   .byte 1, $02, 255
+        ";
+        let bytes = assemble(source, 0xC000, CpuVariant::Nmos6502).unwrap();
+        assert_eq!(bytes, vec![
+            0xA2, 0x0A,       // LDX #10
+            0xCA,             // DEX
+            0x86, 0x10,       // STX $10
+            0x8D, 0x10, 0x00, // STA $0010
+            0xD0, 0xF8,       // BNE loop
+            0x4C, 0x00, 0xC0, // JMP start
+            0x01, 0x02, 0xFF,
+        ]);
+    }
+
+    #[test]
+    fn test_word_and_current_address() {
+        let source = "
+                    JMP (table)
+                    BNE *+4      ; skip the JMP below
+                    JMP *
+            table:  .word handler, $1234
+            here:   .word *, *-2
+            handler: BRK
+        ";
+        let bytes = assemble(source, 0x8000, CpuVariant::Nmos6502).unwrap();
+        assert_eq!(bytes, vec![
+            0x6C, 0x08, 0x80, // JMP (table)
+            0xD0, 0x02,       // BNE $8007
+            0x4C, 0x05, 0x80, // JMP $8005
+            0x10, 0x80,       // handler
+            0x34, 0x12,
+            0x0C, 0x80,       // here
+            0x0A, 0x80,       // * is where the line starts
+            0x00,
+        ]);
+    }
+
+    #[test]
+    fn test_assembled_program_runs() {
+        // The program `cargo run` demonstrates
+        let source = "
+                    LDX #5
+                    LDA #0
+            loop:   CLC
+                    ADC #7
+                    DEX
+                    BNE loop
+                    BRK
+        ";
+        let mut memory = Memory::new();
+        load_source(&mut memory, source, 0x8000, CpuVariant::Nmos6502).unwrap();
+
+        let mut cpu = crate::cpu::CPU::new();
+        cpu.reset(&mut memory);
+        while !cpu.is_halted() {
+            cpu.step(&mut memory);
+        }
+        assert_eq!(cpu.get_register_a(), 35);
+        assert_eq!(cpu.get_register_x(), 0);
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let nmos = CpuVariant::Nmos6502;
+        assert_eq!(assemble("NOP\nFOO", 0x8000, nmos).unwrap_err(), "line 2: unknown instruction FOO");
+        assert!(assemble("LDA #$100", 0x8000, nmos).unwrap_err().contains("does not fit"));
+        assert!(assemble("JMP nowhere", 0x8000, nmos).unwrap_err().contains("undefined label nowhere"));
+        assert!(assemble("TSB $10", 0x8000, nmos).unwrap_err().contains("unknown instruction"));
+        assert!(assemble("TSB $10", 0x8000, CpuVariant::Cmos65C02).is_ok());
+
+        assert!(assemble(".word 1+start", 0x8000, nmos).unwrap_err().contains("invalid offset"));
+
+        let far = format!("BEQ far\n.byte {}\nfar: BRK", vec!["0"; 200].join(","));
+        assert!(assemble(&far, 0x8000, nmos).unwrap_err().contains("out of range"));
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
+use std::sync::{Arc, LockResult, Mutex, MutexGuard};
 use chrono::{DateTime, Utc, Duration};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use bcrypt::{hash, verify, DEFAULT_COST};
@@ -10,6 +11,9 @@ use base64::prelude::*;
 
 const JWT_SECRET: &[u8] = b"your-secret-key-change-this-in-production";
 
+// How long a login token stays valid unless EC65_JWT_TTL_HOURS says otherwise
+pub const DEFAULT_JWT_TTL_HOURS: i64 = 24;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
@@ -95,6 +99,7 @@ pub struct CreateApiKeyRequest {
 pub struct AuthResponse {
     pub token: String,
     pub user: UserInfo,
+    pub expires_in_hours: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,9 +118,76 @@ pub struct ApiKeyResponse {
     pub key: String, // Only returned on creation
     pub permissions: Vec<Permission>,
     pub expires_at: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+// Most often API key last_used times are written to a file-backed store
+const LAST_USED_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Accounts by user id. In memory by default; a store opened on a file is
+// loaded from it and rewritten by `save` whenever accounts are added or
+// removed, and when API keys are used, at most once per
+// LAST_USED_SAVE_INTERVAL so a busy key doesn't cost a write per request.
+#[derive(Clone, Default)]
+pub struct UserStore {
+    users: Arc<Mutex<HashMap<String, User>>>,
+    path: Option<Arc<PathBuf>>,
+    last_used_saved: Arc<Mutex<Option<std::time::Instant>>>,
 }
 
-pub type UserStore = Arc<Mutex<HashMap<String, User>>>;
+impl UserStore {
+    // Back the store with a JSON file, loading any accounts already in it
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let users = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Invalid user store {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(format!("Can't read user store {}: {}", path.display(), e)),
+        };
+        Ok(Self {
+            users: Arc::new(Mutex::new(users)),
+            path: Some(Arc::new(path)),
+            last_used_saved: Arc::default(),
+        })
+    }
+    
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, HashMap<String, User>>> {
+        self.users.lock()
+    }
+    
+    // Write `users` (normally the contents of the locked store) to the
+    // backing file, if there is one. The file is replaced atomically, so a
+    // crash mid-write leaves the previous version intact.
+    pub fn save(&self, users: &HashMap<String, User>) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(users).map_err(|e| e.to_string())?;
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, json)
+            .and_then(|_| std::fs::rename(&temp, path.as_path()))
+            .map_err(|e| format!("Can't write user store {}: {}", path.display(), e))
+    }
+    
+    // Look up a raw API key and record that it was used, writing the new
+    // last_used time out if the last such write was long enough ago
+    pub fn use_api_key(&self, key: &str) -> Option<(User, ApiKey)> {
+        let mut users = self.lock().unwrap();
+        let used = use_api_key(&mut users, key)?;
+        if self.path.is_some() {
+            let mut saved = self.last_used_saved.lock().unwrap();
+            if saved.is_none_or(|at| at.elapsed() >= LAST_USED_SAVE_INTERVAL) {
+                *saved = Some(std::time::Instant::now());
+                if let Err(e) = self.save(&users) {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+        Some(used)
+    }
+}
 pub type SessionStore = Arc<Mutex<HashMap<String, DateTime<Utc>>>>; // user_id -> last_activity
 
 impl Default for ResourceQuota {
@@ -189,13 +261,25 @@ impl User {
             key: raw_key,
             permissions,
             expires_at,
+            last_used: None,
+        }
+    }
+    
+    // Public view of the account, without password hash or keys
+    pub fn info(&self) -> UserInfo {
+        UserInfo {
+            id: self.id.clone(),
+            username: self.username.clone(),
+            email: self.email.clone(),
+            quota: self.quota.clone(),
+            created_at: self.created_at,
         }
     }
     
     pub fn has_permission(&self, permission: &Permission) -> bool {
         self.api_keys.iter().any(|key| {
             key.is_active && 
-            key.expires_at.map_or(true, |exp| exp > Utc::now()) &&
+            key.expires_at.is_none_or(|exp| exp > Utc::now()) &&
             (key.permissions.contains(permission) || key.permissions.contains(&Permission::Admin))
         })
     }
@@ -215,8 +299,8 @@ pub fn hash_api_key(key: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-pub fn create_jwt_token(user: &User) -> Result<String, jsonwebtoken::errors::Error> {
-    let expiration = Utc::now() + Duration::hours(24);
+pub fn create_jwt_token(user: &User, ttl_hours: i64) -> Result<String, jsonwebtoken::errors::Error> {
+    let expiration = Utc::now() + Duration::hours(ttl_hours);
     
     let claims = Claims {
         sub: user.id.clone(),
@@ -242,10 +326,27 @@ pub fn verify_jwt_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Err
 }
 
 pub fn authenticate_api_key(users: UserStore, key: &str) -> Result<User, AuthError> {
+    users.use_api_key(key)
+        .map(|(user, _)| user)
+        .ok_or(AuthError::InvalidApiKey)
+}
+
+// Look up a raw API key and record that it was used
+fn use_api_key(users: &mut HashMap<String, User>, key: &str) -> Option<(User, ApiKey)> {
+    let (user_id, key_id) = find_api_key(users, key)
+        .map(|(user, api_key)| (user.id.clone(), api_key.id.clone()))?;
+    let user = users.get_mut(&user_id)?;
+    let api_key = user.api_keys.iter_mut().find(|k| k.id == key_id)?;
+    api_key.last_used = Some(Utc::now());
+    let api_key = api_key.clone();
+    Some((user.clone(), api_key))
+}
+
+// Find the active user and valid key matching a raw API key
+fn find_api_key<'a>(users: &'a HashMap<String, User>, key: &str) -> Option<(&'a User, &'a ApiKey)> {
     let key_hash = hash_api_key(key);
-    let users_lock = users.lock().unwrap();
     
-    for user in users_lock.values() {
+    for user in users.values() {
         if !user.is_active {
             continue;
         }
@@ -253,13 +354,43 @@ pub fn authenticate_api_key(users: UserStore, key: &str) -> Result<User, AuthErr
         for api_key in &user.api_keys {
             if api_key.key_hash == key_hash && 
                api_key.is_active &&
-               api_key.expires_at.map_or(true, |exp| exp > Utc::now()) {
-                return Ok(user.clone());
+               api_key.expires_at.is_none_or(|exp| exp > Utc::now()) {
+                return Some((user, api_key));
             }
         }
     }
     
-    Err(AuthError::InvalidApiKey)
+    None
+}
+
+// The API key a request was made with, if it used one. Requests without a
+// valid key pass through with None, so this can be combined with with_auth
+// or used on unauthenticated routes.
+pub fn with_api_key(users: UserStore) -> impl Filter<Extract = (Option<ApiKey>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .map(move |auth_header: Option<String>| {
+            let key = auth_header.as_deref()?.strip_prefix("ApiKey ")?;
+            users.use_api_key(key).map(|(_, api_key)| api_key)
+        })
+}
+
+// Whole days left before the request's API key expires, if it expires
+// within `warning_days`. Expired and unknown keys give None; they are
+// rejected by authentication anyway.
+pub fn with_key_expiry(
+    users: UserStore,
+    warning_days: i64,
+) -> impl Filter<Extract = (Option<i64>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .map(move |auth_header: Option<String>| {
+            let key = auth_header.as_deref()?.strip_prefix("ApiKey ")?.to_string();
+            let users_lock = users.lock().unwrap();
+            let (_, api_key) = find_api_key(&users_lock, &key)?;
+            let remaining = (api_key.expires_at? - Utc::now()).num_seconds();
+            // Round up, so a key with hours left reports 1 rather than 0
+            let days = (remaining + 86_399) / 86_400;
+            (days <= warning_days).then_some(days)
+        })
 }
 
 #[derive(Debug)]
@@ -282,22 +413,18 @@ pub fn with_auth(users: UserStore) -> impl Filter<Extract = (User,), Error = Rej
             async move {
                 if let Some(token) = auth_header.strip_prefix("Bearer ") {
                     // JWT token authentication
-                    match verify_jwt_token(token) {
-                        Ok(claims) => {
-                            let users_lock = users.lock().unwrap();
-                            if let Some(user) = users_lock.get(&claims.sub) {
-                                if user.is_active {
-                                    return Ok(user.clone());
-                                }
+                    if let Ok(claims) = verify_jwt_token(token) {
+                        let users_lock = users.lock().unwrap();
+                        if let Some(user) = users_lock.get(&claims.sub) {
+                            if user.is_active {
+                                return Ok(user.clone());
                             }
                         }
-                        Err(_) => {}
                     }
                 } else if let Some(api_key) = auth_header.strip_prefix("ApiKey ") {
                     // API key authentication
-                    match authenticate_api_key(users, api_key) {
-                        Ok(user) => return Ok(user),
-                        Err(_) => {}
+                    if let Ok(user) = authenticate_api_key(users, api_key) {
+                        return Ok(user);
                     }
                 }
                 
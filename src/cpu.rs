@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 use crate::memory::Memory;
-use crate::metrics::{record_instruction, get_instruction_name, Timer};
+use crate::metrics::{record_instruction, record_unimplemented_opcode, get_instruction_name, Timer};
 
 #[derive(Debug)]
 pub struct CPU {
@@ -14,6 +16,49 @@ pub struct CPU {
     // Internal state
     pub cycles: u64,
     pub halted: bool,
+    pub variant: CpuVariant,
+    // Interrupt lines raised with raise_irq/raise_nmi, not yet dispatched
+    pub pending_irq: bool,
+    pub pending_nmi: bool,
+}
+
+// Which chip is being emulated. CMOS-only instructions are only decoded on
+// the 65C02; on the NMOS part those opcodes are undocumented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuVariant {
+    #[default]
+    Nmos6502,
+    Cmos65C02,
+}
+
+// Reasons the CPU can't execute the next instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpuError {
+    UnknownOpcode { opcode: u8, pc: u16 },
+}
+
+impl CpuError {
+    // Stable name for clients to match on, unlike the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            CpuError::UnknownOpcode { .. } => "UnknownOpcode",
+        }
+    }
+    
+    pub fn pc(&self) -> u16 {
+        match self {
+            CpuError::UnknownOpcode { pc, .. } => *pc,
+        }
+    }
+}
+
+impl std::fmt::Display for CpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { opcode, pc } => write!(f, "Unknown opcode: ${:02X} at PC: ${:04X}", opcode, pc),
+        }
+    }
 }
 
 // Status register flags
@@ -26,7 +71,77 @@ pub const UNUSED_FLAG: u8 = 0x20;
 pub const OVERFLOW_FLAG: u8 = 0x40;
 pub const NEGATIVE_FLAG: u8 = 0x80;
 
-#[derive(Debug, Clone, Copy)]
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+// Cycles the hardware spends entering an interrupt handler
+pub const INTERRUPT_CYCLES: u64 = 7;
+
+// Extra cycles on top of base_cycles: indexed reads (abs,X, abs,Y and
+// (zp),Y) add 1 when the index carries into the next page, and a taken
+// branch adds 1, or 2 if it lands on a different page.
+
+// Cycles an implemented opcode takes before those penalties, per the
+// published 6502 timing chart. None for opcodes the CPU doesn't implement
+// on `variant`.
+pub fn base_cycles(opcode: u8, variant: CpuVariant) -> Option<u8> {
+    let cycles = match opcode {
+        // Loads, and the ALU instructions that share their addressing modes
+        0xA9 | 0x69 | 0x29 | 0x09 | 0x49 | 0xC9 | 0xE9 => 2, // #imm
+        0xA5 | 0x65 | 0x25 | 0x05 | 0x45 | 0xC5 | 0xE5 => 3, // zp
+        0xB5 | 0x75 | 0x35 | 0x15 | 0x55 | 0xD5 | 0xF5 => 4, // zp,X
+        0xAD | 0x6D | 0x2D | 0x0D | 0x4D | 0xCD | 0xED => 4, // abs
+        0xBD | 0x7D | 0x3D | 0x1D | 0x5D | 0xDD | 0xFD => 4, // abs,X
+        0xB9 | 0x79 | 0x39 | 0x19 | 0x59 | 0xD9 | 0xF9 => 4, // abs,Y
+        0xA1 | 0x61 | 0x21 | 0x01 | 0x41 | 0xC1 | 0xE1 => 6, // (zp,X)
+        0xB1 | 0x71 | 0x31 | 0x11 | 0x51 | 0xD1 | 0xF1 => 5, // (zp),Y
+        
+        0xA2 | 0xA0 | 0xE0 | 0xC0 => 2,               // LDX, LDY, CPX, CPY #imm
+        0xA6 | 0xA4 | 0xE4 | 0xC4 | 0x24 => 3,        // LDX, LDY, CPX, CPY, BIT zp
+        0xB6 | 0xB4 => 4,                             // LDX zp,Y, LDY zp,X
+        0xAE | 0xAC | 0xEC | 0xCC | 0x2C => 4,        // LDX, LDY, CPX, CPY, BIT abs
+        0xBE | 0xBC => 4,                             // LDX abs,Y, LDY abs,X
+        
+        // Stores never take the page-crossing shortcut
+        0x85 => 3, 0x95 => 4, 0x8D => 4, 0x9D => 5, 0x99 => 5, 0x81 => 6, 0x91 => 6,
+        0x86 | 0x84 => 3, 0x96 | 0x94 => 4, 0x8E | 0x8C => 4, // STX, STY
+        
+        // Read-modify-write
+        0xE6 | 0xC6 => 5, 0xF6 | 0xD6 => 6, 0xEE | 0xCE => 6, 0xFE | 0xDE => 7,
+        0x04 | 0x14 if variant == CpuVariant::Cmos65C02 => 5, // TSB, TRB zp
+        0x0C | 0x1C if variant == CpuVariant::Cmos65C02 => 6, // TSB, TRB abs
+        
+        // Register, transfer and flag instructions
+        0xE8 | 0xC8 | 0xCA | 0x88 | 0xAA | 0xA8 | 0x8A | 0x98 | 0xBA | 0x9A => 2,
+        0x18 | 0x38 | 0x58 | 0x78 | 0xD8 | 0xF8 | 0xB8 | 0xEA => 2,
+        
+        // Branches, not taken
+        0x90 | 0xB0 | 0xF0 | 0xD0 | 0x30 | 0x10 | 0x50 | 0x70 => 2,
+        
+        // Control flow. The 65C02 spends an extra cycle on JMP (ind) fixing
+        // the page-wrap bug.
+        0x4C => 3,
+        0x6C => if variant == CpuVariant::Cmos65C02 { 6 } else { 5 },
+        0x20 | 0x60 | 0x40 => 6, // JSR, RTS, RTI
+        0x00 => 7,
+        
+        _ => return None,
+    };
+    Some(cycles)
+}
+
+// Whether indexing moved `base` onto a different page
+fn page_crossed(base: u16, addr: u16) -> bool {
+    base & 0xFF00 != addr & 0xFF00
+}
+
+// Destination of a taken relative branch. Offsets are relative to the
+// address after the operand byte.
+pub fn relative_target(pc_after_operand: u16, offset: i8) -> u16 {
+    pc_after_operand.wrapping_add(offset as u16)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum AddressingMode {
     Implicit,
     Accumulator,
@@ -43,6 +158,12 @@ pub enum AddressingMode {
     IndirectIndexed,
 }
 
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CPU {
     pub fn new() -> Self {
         CPU {
@@ -54,32 +175,86 @@ impl CPU {
             status: UNUSED_FLAG | INTERRUPT_DISABLE,
             cycles: 0,
             halted: false,
+            variant: CpuVariant::default(),
+            pending_irq: false,
+            pending_nmi: false,
+        }
+    }
+    
+    pub fn with_variant(variant: CpuVariant) -> Self {
+        CPU {
+            variant,
+            ..Self::new()
         }
     }
     
+    // Switch behavior between NMOS and CMOS from the next instruction on.
+    // Registers and flags are left as they are.
+    pub fn set_variant(&mut self, variant: CpuVariant) {
+        self.variant = variant;
+    }
+    
+    // The NMOS 6502 leaves the decimal flag as it was on reset; the 65C02
+    // clears it
     pub fn reset(&mut self, memory: &mut Memory) {
-        let low = memory.read(0xFFFC) as u16;
-        let high = memory.read(0xFFFD) as u16;
-        self.pc = (high << 8) | low;
+        self.pc = memory.read_u16(RESET_VECTOR);
         
+        let decimal = match self.variant {
+            CpuVariant::Nmos6502 => self.status & DECIMAL_MODE,
+            CpuVariant::Cmos65C02 => 0,
+        };
         self.a = 0;
         self.x = 0;
         self.y = 0;
         self.sp = 0xFD;
-        self.status = UNUSED_FLAG | INTERRUPT_DISABLE;
+        self.status = UNUSED_FLAG | INTERRUPT_DISABLE | decimal;
         self.cycles = 0;
         self.halted = false;
+        self.pending_irq = false;
+        self.pending_nmi = false;
     }
     
+    // Whether `step` can execute `opcode` on this CPU's variant. Opcodes
+    // outside this set panic in `step`.
+    pub fn is_implemented(&self, opcode: u8) -> bool {
+        match opcode {
+            0x04 | 0x0C | 0x14 | 0x1C => self.variant == CpuVariant::Cmos65C02,
+            _ => matches!(opcode,
+                0x00 | 0x01 | 0x05 | 0x09 | 0x0D | 0x10 | 0x11 | 0x15 | 0x18 | 0x19 | 0x1D | 0x20 |
+                0x21 | 0x24 | 0x25 | 0x29 | 0x2C | 0x2D | 0x30 | 0x31 | 0x35 | 0x38 | 0x39 | 0x3D |
+                0x40 | 0x41 | 0x45 | 0x49 | 0x4C | 0x4D | 0x50 | 0x51 | 0x55 | 0x58 | 0x59 | 0x5D |
+                0x60 | 0x61 | 0x65 | 0x69 | 0x6C | 0x6D | 0x70 | 0x71 | 0x75 | 0x78 | 0x79 | 0x7D |
+                0x81 | 0x84 | 0x85 | 0x86 | 0x88 | 0x8A | 0x8C | 0x8D | 0x8E | 0x90 | 0x91 | 0x94 |
+                0x95 | 0x96 | 0x98 | 0x99 | 0x9A | 0x9D | 0xA0 | 0xA1 | 0xA2 | 0xA4 | 0xA5 | 0xA6 |
+                0xA8 | 0xA9 | 0xAA | 0xAC | 0xAD | 0xAE | 0xB0 | 0xB1 | 0xB4 | 0xB5 | 0xB6 | 0xB8 |
+                0xB9 | 0xBA | 0xBC | 0xBD | 0xBE | 0xC0 | 0xC1 | 0xC4 | 0xC5 | 0xC6 | 0xC8 | 0xC9 |
+                0xCA | 0xCC | 0xCD | 0xCE | 0xD0 | 0xD1 | 0xD5 | 0xD6 | 0xD8 | 0xD9 | 0xDD | 0xDE |
+                0xE0 | 0xE1 | 0xE4 | 0xE5 | 0xE6 | 0xE8 | 0xE9 | 0xEA | 0xEC | 0xED | 0xEE | 0xF0 |
+                0xF1 | 0xF5 | 0xF6 | 0xF8 | 0xF9 | 0xFD | 0xFE),
+        }
+    }
+    
+    // Execute one instruction, panicking on an opcode that isn't implemented
     pub fn step(&mut self, memory: &mut Memory) {
+        if let Err(e) = self.try_step(memory) {
+            panic!("{}", e);
+        }
+    }
+    
+    // Execute one instruction. An unknown opcode is reported without
+    // executing anything, leaving PC on the opcode.
+    pub fn try_step(&mut self, memory: &mut Memory) -> Result<(), CpuError> {
         if self.halted {
-            return;
+            // Nothing is executing, so a raised interrupt is taken at once
+            self.poll_interrupts(memory);
+            return Ok(());
         }
         
         let opcode = memory.read(self.pc);
         self.pc = self.pc.wrapping_add(1);
         
         let timer = Timer::new();
+        let start_cycles = self.cycles;
         let instruction_name = get_instruction_name(opcode);
         
         match opcode {
@@ -116,6 +291,16 @@ impl CPU {
             0x81 => self.sta_indexed_indirect(memory),
             0x91 => self.sta_indirect_indexed(memory),
             
+            // STX - Store X Register
+            0x86 => self.stx_zero_page(memory),
+            0x96 => self.stx_zero_page_y(memory),
+            0x8E => self.stx_absolute(memory),
+            
+            // STY - Store Y Register
+            0x84 => self.sty_zero_page(memory),
+            0x94 => self.sty_zero_page_x(memory),
+            0x8C => self.sty_absolute(memory),
+            
             // ADC - Add with Carry
             0x69 => self.adc_immediate(memory),
             0x65 => self.adc_zero_page(memory),
@@ -186,6 +371,16 @@ impl CPU {
             0x41 => self.eor_indexed_indirect(memory),
             0x51 => self.eor_indirect_indexed(memory),
             
+            // BIT - Bit Test
+            0x24 => self.bit_zero_page(memory),
+            0x2C => self.bit_absolute(memory),
+            
+            // TSB/TRB - Test and Set/Reset Bits (65C02)
+            0x04 if self.variant == CpuVariant::Cmos65C02 => self.tsb_zero_page(memory),
+            0x0C if self.variant == CpuVariant::Cmos65C02 => self.tsb_absolute(memory),
+            0x14 if self.variant == CpuVariant::Cmos65C02 => self.trb_zero_page(memory),
+            0x1C if self.variant == CpuVariant::Cmos65C02 => self.trb_absolute(memory),
+            
             // INC - Increment Memory
             0xE6 => self.inc_zero_page(memory),
             0xF6 => self.inc_zero_page_x(memory),
@@ -238,6 +433,9 @@ impl CPU {
             // RTS - Return from Subroutine
             0x60 => self.rts(memory),
             
+            // RTI - Return from Interrupt
+            0x40 => self.rti(memory),
+            
             // BRK - Break
             0x00 => self.brk(memory),
             
@@ -292,14 +490,81 @@ impl CPU {
             0xEA => self.nop(),
             
             _ => {
-                panic!("Unknown opcode: ${:02X} at PC: ${:04X}", opcode, self.pc - 1);
+                record_unimplemented_opcode(opcode);
+                self.pc = self.pc.wrapping_sub(1);
+                return Err(CpuError::UnknownOpcode { opcode, pc: self.pc });
             }
         }
         
-        self.cycles += 1;
+        self.cycles += base_cycles(opcode, self.variant).expect("implemented opcodes are all timed") as u64;
         
         // Record metrics for this instruction
-        record_instruction(opcode, instruction_name, timer.elapsed());
+        record_instruction(opcode, instruction_name, timer.elapsed(), self.cycles - start_cycles);
+        // Devices run on the same clock, so an IRQ a timer raises during this
+        // instruction is taken straight after it
+        self.tick_devices(memory, self.cycles - start_cycles);
+        let dispatch_cycles = self.cycles;
+        self.poll_interrupts(memory);
+        self.tick_devices(memory, self.cycles - dispatch_cycles);
+        Ok(())
+    }
+    
+    // Request a maskable interrupt. Ignored while the I flag is set; returns
+    // whether the interrupt was taken.
+    pub fn irq(&mut self, memory: &mut Memory) -> bool {
+        if self.get_flag(INTERRUPT_DISABLE) {
+            return false;
+        }
+        self.interrupt(memory, IRQ_VECTOR);
+        true
+    }
+    
+    pub fn nmi(&mut self, memory: &mut Memory) {
+        self.interrupt(memory, NMI_VECTOR);
+    }
+    
+    // Assert an interrupt line without dispatching it. As on the hardware,
+    // interrupts are only recognized at instruction boundaries: the
+    // instruction in progress (the next one `step` runs) finishes first, then
+    // the 7-cycle dispatch follows in the same step. An IRQ stays pending
+    // while the I flag is set.
+    pub fn raise_irq(&mut self) {
+        self.pending_irq = true;
+    }
+    
+    pub fn raise_nmi(&mut self) {
+        self.pending_nmi = true;
+    }
+    
+    fn tick_devices(&mut self, memory: &mut Memory, cycles: u64) {
+        if cycles > 0 && memory.tick_devices(cycles) {
+            self.raise_irq();
+        }
+    }
+    
+    // Dispatch a raised interrupt, if one can be taken. NMI wins over IRQ.
+    fn poll_interrupts(&mut self, memory: &mut Memory) {
+        if std::mem::take(&mut self.pending_nmi) {
+            self.interrupt(memory, NMI_VECTOR);
+        } else if self.pending_irq && !self.get_flag(INTERRUPT_DISABLE) {
+            self.pending_irq = false;
+            self.interrupt(memory, IRQ_VECTOR);
+        }
+    }
+    
+    // Hardware interrupt sequence: push PCH, PCL, then P with B clear, set I
+    // (and on the 65C02 clear D) and jump through the vector. Wakes a halted
+    // CPU.
+    fn interrupt(&mut self, memory: &mut Memory, vector: u16) {
+        self.push_u16(memory, self.pc);
+        self.push(memory, (self.status | UNUSED_FLAG) & !BREAK_COMMAND);
+        self.set_flag(INTERRUPT_DISABLE, true);
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.set_flag(DECIMAL_MODE, false);
+        }
+        self.pc = memory.read_u16(vector);
+        self.cycles += INTERRUPT_CYCLES;
+        self.halted = false;
     }
     
     // Getters
@@ -356,7 +621,7 @@ impl CPU {
     
     fn read_absolute(&mut self, memory: &Memory) -> u8 {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = (high << 8) | low;
         self.pc = self.pc.wrapping_add(2);
         memory.read(addr)
@@ -364,17 +629,38 @@ impl CPU {
     
     fn read_absolute_x(&mut self, memory: &Memory) -> u8 {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.x as u16);
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
+        let base = (high << 8) | low;
+        let addr = base.wrapping_add(self.x as u16);
         self.pc = self.pc.wrapping_add(2);
+        if page_crossed(base, addr) {
+            self.cycles += 1;
+        }
         memory.read(addr)
     }
     
     fn read_absolute_y(&mut self, memory: &Memory) -> u8 {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
+        let base = (high << 8) | low;
+        let addr = base.wrapping_add(self.y as u16);
         self.pc = self.pc.wrapping_add(2);
+        if page_crossed(base, addr) {
+            self.cycles += 1;
+        }
+        memory.read(addr)
+    }
+    
+    fn read_indirect_indexed(&mut self, memory: &Memory) -> u8 {
+        let ptr = memory.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        let low = memory.read(ptr) as u16;
+        let high = memory.read(ptr.wrapping_add(1)) as u16;
+        let base = (high << 8) | low;
+        let addr = base.wrapping_add(self.y as u16);
+        if page_crossed(base, addr) {
+            self.cycles += 1;
+        }
         memory.read(addr)
     }
     
@@ -420,12 +706,7 @@ impl CPU {
     }
     
     fn lda_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        self.a = memory.read(addr);
+        self.a = self.read_indirect_indexed(memory);
         self.update_zero_and_negative_flags(self.a);
     }
     
@@ -493,7 +774,7 @@ impl CPU {
     
     fn sta_absolute(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = (high << 8) | low;
         self.pc = self.pc.wrapping_add(2);
         memory.write(addr, self.a);
@@ -501,7 +782,7 @@ impl CPU {
     
     fn sta_absolute_x(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = ((high << 8) | low).wrapping_add(self.x as u16);
         self.pc = self.pc.wrapping_add(2);
         memory.write(addr, self.a);
@@ -509,7 +790,7 @@ impl CPU {
     
     fn sta_absolute_y(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = ((high << 8) | low).wrapping_add(self.y as u16);
         self.pc = self.pc.wrapping_add(2);
         memory.write(addr, self.a);
@@ -533,6 +814,46 @@ impl CPU {
         memory.write(addr, self.a);
     }
     
+    fn stx_zero_page(&mut self, memory: &mut Memory) {
+        let addr = memory.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        memory.write(addr, self.x);
+    }
+    
+    fn stx_zero_page_y(&mut self, memory: &mut Memory) {
+        let addr = (memory.read(self.pc).wrapping_add(self.y)) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        memory.write(addr, self.x);
+    }
+    
+    fn stx_absolute(&mut self, memory: &mut Memory) {
+        let low = memory.read(self.pc) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
+        let addr = (high << 8) | low;
+        self.pc = self.pc.wrapping_add(2);
+        memory.write(addr, self.x);
+    }
+    
+    fn sty_zero_page(&mut self, memory: &mut Memory) {
+        let addr = memory.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        memory.write(addr, self.y);
+    }
+    
+    fn sty_zero_page_x(&mut self, memory: &mut Memory) {
+        let addr = (memory.read(self.pc).wrapping_add(self.x)) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        memory.write(addr, self.y);
+    }
+    
+    fn sty_absolute(&mut self, memory: &mut Memory) {
+        let low = memory.read(self.pc) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
+        let addr = (high << 8) | low;
+        self.pc = self.pc.wrapping_add(2);
+        memory.write(addr, self.y);
+    }
+    
     fn brk(&mut self, _memory: &mut Memory) {
         self.halted = true;
     }
@@ -583,12 +904,7 @@ impl CPU {
     }
     
     fn adc_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        let value = memory.read(addr);
+        let value = self.read_indirect_indexed(memory);
         self.adc(value);
     }
     
@@ -646,12 +962,7 @@ impl CPU {
     }
     
     fn sbc_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        let value = memory.read(addr);
+        let value = self.read_indirect_indexed(memory);
         self.sbc(value);
     }
     
@@ -710,12 +1021,7 @@ impl CPU {
     }
     
     fn cmp_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        let value = memory.read(addr);
+        let value = self.read_indirect_indexed(memory);
         self.compare(self.a, value);
     }
     
@@ -804,12 +1110,7 @@ impl CPU {
     }
     
     fn and_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        let value = memory.read(addr);
+        let value = self.read_indirect_indexed(memory);
         self.a &= value;
         self.update_zero_and_negative_flags(self.a);
     }
@@ -862,12 +1163,7 @@ impl CPU {
     }
     
     fn ora_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        let value = memory.read(addr);
+        let value = self.read_indirect_indexed(memory);
         self.a |= value;
         self.update_zero_and_negative_flags(self.a);
     }
@@ -920,16 +1216,76 @@ impl CPU {
     }
     
     fn eor_indirect_indexed(&mut self, memory: &Memory) {
-        let ptr = memory.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = memory.read(ptr) as u16;
-        let high = memory.read(ptr.wrapping_add(1)) as u16;
-        let addr = ((high << 8) | low).wrapping_add(self.y as u16);
-        let value = memory.read(addr);
+        let value = self.read_indirect_indexed(memory);
         self.a ^= value;
         self.update_zero_and_negative_flags(self.a);
     }
     
+    // BIT copies bits 7 and 6 of the operand straight into N and V; only Z
+    // depends on the accumulator
+    fn bit_zero_page(&mut self, memory: &Memory) {
+        let value = self.read_zero_page(memory);
+        self.bit(value);
+    }
+    
+    fn bit_absolute(&mut self, memory: &Memory) {
+        let value = self.read_absolute(memory);
+        self.bit(value);
+    }
+    
+    fn bit(&mut self, value: u8) {
+        self.test_bits(value);
+        self.set_flag(OVERFLOW_FLAG, value & 0x40 != 0);
+        self.set_flag(NEGATIVE_FLAG, value & 0x80 != 0);
+    }
+    
+    // Z flag from A & M, shared by BIT, TSB and TRB
+    fn test_bits(&mut self, value: u8) {
+        self.set_flag(ZERO_FLAG, self.a & value == 0);
+    }
+    
+    fn absolute_address(&mut self, memory: &Memory) -> u16 {
+        let low = memory.read(self.pc) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
+        self.pc = self.pc.wrapping_add(2);
+        (high << 8) | low
+    }
+    
+    fn tsb_zero_page(&mut self, memory: &mut Memory) {
+        let addr = memory.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        self.tsb(memory, addr);
+    }
+    
+    fn tsb_absolute(&mut self, memory: &mut Memory) {
+        let addr = self.absolute_address(memory);
+        self.tsb(memory, addr);
+    }
+    
+    fn trb_zero_page(&mut self, memory: &mut Memory) {
+        let addr = memory.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        self.trb(memory, addr);
+    }
+    
+    fn trb_absolute(&mut self, memory: &mut Memory) {
+        let addr = self.absolute_address(memory);
+        self.trb(memory, addr);
+    }
+    
+    // Z reflects the bits as they were before the write
+    fn tsb(&mut self, memory: &mut Memory, addr: u16) {
+        let value = memory.read(addr);
+        self.test_bits(value);
+        memory.write(addr, value | self.a);
+    }
+    
+    fn trb(&mut self, memory: &mut Memory, addr: u16) {
+        let value = memory.read(addr);
+        self.test_bits(value);
+        memory.write(addr, value & !self.a);
+    }
+    
     // Increment/Decrement operations
     fn inc_zero_page(&mut self, memory: &mut Memory) {
         let addr = memory.read(self.pc) as u16;
@@ -949,7 +1305,7 @@ impl CPU {
     
     fn inc_absolute(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = (high << 8) | low;
         self.pc = self.pc.wrapping_add(2);
         let value = memory.read(addr).wrapping_add(1);
@@ -957,9 +1313,11 @@ impl CPU {
         self.update_zero_and_negative_flags(value);
     }
     
+    // Read-modify-write instructions always spend the extra indexing cycle
+    // (7 cycles total), whether or not base + X crosses a page.
     fn inc_absolute_x(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = ((high << 8) | low).wrapping_add(self.x as u16);
         self.pc = self.pc.wrapping_add(2);
         let value = memory.read(addr).wrapping_add(1);
@@ -985,7 +1343,7 @@ impl CPU {
     
     fn dec_absolute(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = (high << 8) | low;
         self.pc = self.pc.wrapping_add(2);
         let value = memory.read(addr).wrapping_sub(1);
@@ -993,9 +1351,10 @@ impl CPU {
         self.update_zero_and_negative_flags(value);
     }
     
+    // Same fixed 7-cycle timing as INC absolute,X.
     fn dec_absolute_x(&mut self, memory: &mut Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         let addr = ((high << 8) | low).wrapping_add(self.x as u16);
         self.pc = self.pc.wrapping_add(2);
         let value = memory.read(addr).wrapping_sub(1);
@@ -1056,32 +1415,35 @@ impl CPU {
     // Jump operations
     fn jmp_absolute(&mut self, memory: &Memory) {
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         self.pc = (high << 8) | low;
     }
     
     fn jmp_indirect(&mut self, memory: &Memory) {
         let ptr_low = memory.read(self.pc) as u16;
-        let ptr_high = memory.read(self.pc + 1) as u16;
+        let ptr_high = memory.read(self.pc.wrapping_add(1)) as u16;
         let ptr = (ptr_high << 8) | ptr_low;
         
-        // 6502 bug: if ptr is at page boundary, high byte wraps around within the page
+        // 6502 bug: if ptr is at page boundary, high byte wraps around within the page.
+        // The 65C02 fixed this.
         let low = memory.read(ptr) as u16;
-        let high = if ptr & 0xFF == 0xFF {
+        let high = if ptr & 0xFF == 0xFF && self.variant == CpuVariant::Nmos6502 {
             memory.read(ptr & 0xFF00) as u16
         } else {
-            memory.read(ptr + 1) as u16
+            memory.read(ptr.wrapping_add(1)) as u16
         };
         
         self.pc = (high << 8) | low;
     }
     
     fn jsr(&mut self, memory: &mut Memory) {
+        // The 6502 pushes the address of the last byte of the JSR
+        // instruction, not of the next instruction; RTS adds the 1 back
         let return_addr = self.pc.wrapping_add(1);
         self.push_u16(memory, return_addr);
         
         let low = memory.read(self.pc) as u16;
-        let high = memory.read(self.pc + 1) as u16;
+        let high = memory.read(self.pc.wrapping_add(1)) as u16;
         self.pc = (high << 8) | low;
     }
     
@@ -1089,6 +1451,13 @@ impl CPU {
         self.pc = self.pop_u16(memory).wrapping_add(1);
     }
     
+    // Unlike RTS, the pushed address is the return address itself
+    fn rti(&mut self, memory: &Memory) {
+        let status = self.pop(memory);
+        self.status = (status | UNUSED_FLAG) & !BREAK_COMMAND;
+        self.pc = self.pop_u16(memory);
+    }
+    
     // Stack operations
     pub fn push(&mut self, memory: &mut Memory, value: u8) {
         memory.write(0x100 + self.sp as u16, value);
@@ -1146,11 +1515,8 @@ impl CPU {
         self.pc = self.pc.wrapping_add(1);
         
         if condition {
-            let target = if offset >= 0 {
-                self.pc.wrapping_add(offset as u16)
-            } else {
-                self.pc.wrapping_sub((-offset) as u16)
-            };
+            let target = relative_target(self.pc, offset);
+            self.cycles += if page_crossed(self.pc, target) { 2 } else { 1 };
             self.pc = target;
         }
     }
@@ -1258,6 +1624,47 @@ mod tests {
         assert_eq!(cpu.get_register_y(), 0x42);
     }
     
+    #[test]
+    fn test_txs_leaves_flags_unchanged() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        // Unlike every other transfer, TXS doesn't touch Z or N
+        memory.write(0x8000, 0xA2); // LDX #$00
+        memory.write(0x8001, 0x00);
+        memory.write(0x8002, 0xA9); // LDA #$80: N set, Z clear
+        memory.write(0x8003, 0x80);
+        memory.write(0x8004, 0x9A); // TXS: zero into SP
+        memory.write(0x8005, 0xA2); // LDX #$FF
+        memory.write(0x8006, 0xFF);
+        memory.write(0x8007, 0xA9); // LDA #$00: Z set, N clear
+        memory.write(0x8008, 0x00);
+        memory.write(0x8009, 0x9A); // TXS: negative into SP
+        memory.write(0x800A, 0xBA); // TSX, which does set flags
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!(cpu.get_sp(), 0x00);
+        assert!(!cpu.get_flag(ZERO_FLAG));
+        assert!(cpu.get_flag(NEGATIVE_FLAG));
+        
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!(cpu.get_sp(), 0xFF);
+        assert!(cpu.get_flag(ZERO_FLAG));
+        assert!(!cpu.get_flag(NEGATIVE_FLAG));
+        
+        cpu.step(&mut memory); // TSX
+        assert_eq!(cpu.get_register_x(), 0xFF);
+        assert!(!cpu.get_flag(ZERO_FLAG));
+        assert!(cpu.get_flag(NEGATIVE_FLAG));
+    }
+    
     #[test]
     fn test_adc_carry_flag() {
         let mut cpu = CPU::new();
@@ -1487,6 +1894,493 @@ mod tests {
         assert!(cpu.get_flag(NEGATIVE_FLAG)); // Result negative
     }
     
+    #[test]
+    fn test_compare_negative_flag_is_bit_7_of_difference() {
+        let pairs = [
+            (0x30, 0x50), // 0xE0, borrow
+            (0x50, 0x30), // 0x20
+            (0x80, 0x00), // 0x80 without borrow
+            (0x00, 0x01), // 0xFF
+            (0xFF, 0x7F), // 0x80
+            (0x7F, 0xFF), // 0x80 with borrow
+            (0x01, 0x81), // 0x80
+            (0x42, 0x42), // 0x00
+        ];
+        
+        // CMP #, CPX #, CPY # against A, X, Y loaded with the same value
+        for (register, operand) in pairs {
+            let mut cpu = CPU::new();
+            let mut memory = Memory::new();
+            let program = [
+                0xA9, register, 0xA2, register, 0xA0, register,
+                0xC9, operand, 0xE0, operand, 0xC0, operand,
+            ];
+            for (i, &byte) in program.iter().enumerate() {
+                memory.write(0x8000 + i as u16, byte);
+            }
+            memory.write(0xFFFC, 0x00);
+            memory.write(0xFFFD, 0x80);
+            cpu.reset(&mut memory);
+            for _ in 0..3 {
+                cpu.step(&mut memory);
+            }
+            
+            let difference = u8::wrapping_sub(register, operand);
+            for name in ["CMP", "CPX", "CPY"] {
+                cpu.step(&mut memory);
+                assert_eq!(
+                    cpu.get_flag(NEGATIVE_FLAG),
+                    difference & 0x80 != 0,
+                    "{} ${:02X} - ${:02X}", name, register, operand
+                );
+                assert_eq!(cpu.get_flag(CARRY_FLAG), register >= operand);
+                assert_eq!(cpu.get_flag(ZERO_FLAG), register == operand);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_clv_clears_overflow_from_adc() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        // LDA #$7F, ADC #$01 (sets V), CLV
+        memory.write(0x8000, 0xA9); // LDA #$7F
+        memory.write(0x8001, 0x7F);
+        memory.write(0x8002, 0x69); // ADC #$01
+        memory.write(0x8003, 0x01);
+        memory.write(0x8004, 0xB8); // CLV
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // LDA
+        cpu.step(&mut memory); // ADC
+        assert!(cpu.get_flag(OVERFLOW_FLAG));
+        
+        cpu.step(&mut memory); // CLV
+        assert!(!cpu.get_flag(OVERFLOW_FLAG));
+        assert_eq!(cpu.get_register_a(), 0x80); // Result untouched
+        assert!(cpu.get_flag(NEGATIVE_FLAG)); // Other flags untouched
+    }
+    
+    #[test]
+    fn test_sbc_overflow_flag() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        // SEC, LDA #$80, SBC #$01 (-128 - 1 overflows to +127)
+        memory.write(0x8000, 0x38); // SEC
+        memory.write(0x8001, 0xA9); // LDA #$80
+        memory.write(0x8002, 0x80);
+        memory.write(0x8003, 0xE9); // SBC #$01
+        memory.write(0x8004, 0x01);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // SEC
+        cpu.step(&mut memory); // LDA
+        cpu.step(&mut memory); // SBC
+        
+        assert_eq!(cpu.get_register_a(), 0x7F);
+        assert!(cpu.get_flag(OVERFLOW_FLAG));
+        assert!(cpu.get_flag(CARRY_FLAG));
+        assert!(!cpu.get_flag(NEGATIVE_FLAG));
+    }
+    
+    #[test]
+    fn test_bit_sets_overflow_from_operand() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        memory.write(0x10, 0x40); // Bit 6 set, bit 7 clear
+        memory.write(0x3000, 0x80); // Bit 7 set, bit 6 clear
+        
+        // LDA #$00, BIT $10, BIT $3000
+        memory.write(0x8000, 0xA9); // LDA #$00
+        memory.write(0x8001, 0x00);
+        memory.write(0x8002, 0x24); // BIT $10
+        memory.write(0x8003, 0x10);
+        memory.write(0x8004, 0x2C); // BIT $3000
+        memory.write(0x8005, 0x00);
+        memory.write(0x8006, 0x30);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // LDA
+        cpu.step(&mut memory); // BIT $10
+        
+        // V comes from the operand even though A & M is zero
+        assert!(cpu.get_flag(OVERFLOW_FLAG));
+        assert!(!cpu.get_flag(NEGATIVE_FLAG));
+        assert!(cpu.get_flag(ZERO_FLAG));
+        assert_eq!(cpu.get_register_a(), 0x00);
+        
+        cpu.step(&mut memory); // BIT $3000
+        assert!(!cpu.get_flag(OVERFLOW_FLAG));
+        assert!(cpu.get_flag(NEGATIVE_FLAG));
+        assert!(cpu.get_flag(ZERO_FLAG));
+    }
+    
+    // Run the program at $8000 on a 65C02 for the given number of steps
+    fn run_cmos(memory: &mut Memory, steps: usize) -> CPU {
+        let mut cpu = CPU::with_variant(CpuVariant::Cmos65C02);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        cpu.reset(memory);
+        for _ in 0..steps {
+            cpu.step(memory);
+        }
+        cpu
+    }
+    
+    #[test]
+    fn test_tsb_sets_bits_and_tests_before_write() {
+        let mut memory = Memory::new();
+        memory.write(0x10, 0xF0);
+        memory.write(0x3000, 0x01);
+        
+        memory.write(0x8000, 0xA9); // LDA #$0F
+        memory.write(0x8001, 0x0F);
+        memory.write(0x8002, 0x04); // TSB $10
+        memory.write(0x8003, 0x10);
+        memory.write(0x8004, 0x0C); // TSB $3000
+        memory.write(0x8005, 0x00);
+        memory.write(0x8006, 0x30);
+        
+        let mut cpu = run_cmos(&mut memory, 2);
+        
+        // $0F & $F0 is zero, even though the result written back is $FF
+        assert_eq!(memory.read(0x10), 0xFF);
+        assert!(cpu.get_flag(ZERO_FLAG));
+        assert_eq!(cpu.get_register_a(), 0x0F);
+        
+        cpu.step(&mut memory);
+        assert_eq!(memory.read(0x3000), 0x0F);
+        assert!(!cpu.get_flag(ZERO_FLAG));
+        assert_eq!(cpu.get_pc(), 0x8007);
+    }
+    
+    #[test]
+    fn test_trb_resets_bits_and_tests_before_write() {
+        let mut memory = Memory::new();
+        memory.write(0x10, 0xFF);
+        memory.write(0x3000, 0xF0);
+        
+        memory.write(0x8000, 0xA9); // LDA #$0F
+        memory.write(0x8001, 0x0F);
+        memory.write(0x8002, 0x14); // TRB $10
+        memory.write(0x8003, 0x10);
+        memory.write(0x8004, 0x1C); // TRB $3000
+        memory.write(0x8005, 0x00);
+        memory.write(0x8006, 0x30);
+        
+        let mut cpu = run_cmos(&mut memory, 2);
+        
+        assert_eq!(memory.read(0x10), 0xF0);
+        assert!(!cpu.get_flag(ZERO_FLAG));
+        
+        cpu.step(&mut memory);
+        assert_eq!(memory.read(0x3000), 0xF0);
+        assert!(cpu.get_flag(ZERO_FLAG));
+    }
+    
+    #[test]
+    fn test_is_implemented_matches_dispatch() {
+        for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            for opcode in 0..=255u8 {
+                let ran = std::panic::catch_unwind(|| {
+                    let mut cpu = CPU::with_variant(variant);
+                    let mut memory = Memory::new();
+                    memory.write(0x8000, opcode);
+                    memory.write(0xFFFC, 0x00);
+                    memory.write(0xFFFD, 0x80);
+                    cpu.reset(&mut memory);
+                    cpu.step(&mut memory);
+                })
+                .is_ok();
+                assert_eq!(CPU::with_variant(variant).is_implemented(opcode), ran, "opcode ${:02X}", opcode);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_implemented_opcodes_agree_across_tables() {
+        // Dispatch, disassembler, metrics names and cycle table are kept
+        // separately; an instruction added to one must be added to all
+        for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            let cpu = CPU::with_variant(variant);
+            for opcode in 0..=255u8 {
+                if !cpu.is_implemented(opcode) {
+                    continue;
+                }
+                let fetch = |address: u16| Some(if address == 0 { opcode } else { 0 });
+                let decoded = crate::disasm::decode(fetch, 0, variant);
+                let decoded = decoded.unwrap_or_else(|| panic!("${:02X} runs but doesn't decode on {:?}", opcode, variant));
+                assert_eq!(get_instruction_name(opcode), decoded.mnemonic, "${:02X}", opcode);
+                assert!(base_cycles(opcode, variant).is_some_and(|cycles| cycles > 0), "${:02X} has no timing", opcode);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_unimplemented_opcode_is_counted() {
+        let counter = || crate::metrics::UNIMPLEMENTED_OPCODES_TOTAL.with_label_values(&["0x02"]).get();
+        let before = counter();
+        
+        let ran = std::panic::catch_unwind(|| {
+            let mut cpu = CPU::new();
+            let mut memory = Memory::new();
+            memory.write(0x8000, 0x02); // No such instruction
+            memory.write(0xFFFC, 0x00);
+            memory.write(0xFFFD, 0x80);
+            cpu.reset(&mut memory);
+            cpu.step(&mut memory);
+        });
+        
+        assert!(ran.is_err());
+        // Other tests may hit $02 concurrently, so only a lower bound holds
+        assert!(counter() >= before + 1.0);
+    }
+    
+    #[test]
+    fn test_stx_sty_store_and_are_counted_by_name() {
+        let counter = || crate::metrics::CPU_INSTRUCTIONS_TOTAL.with_label_values(&["0x8E", "STX"]).get();
+        let before = counter();
+        
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.load_rom(&[
+            0xA2, 0x11,       // LDX #$11
+            0xA0, 0x22,       // LDY #$22
+            0x8E, 0x00, 0x02, // STX $0200
+            0x86, 0x10,       // STX $10
+            0x96, 0x10,       // STX $10,Y
+            0x8C, 0x01, 0x02, // STY $0201
+            0x84, 0x11,       // STY $11
+            0x94, 0x10,       // STY $10,X
+        ], 0x8000);
+        memory.write_u16(RESET_VECTOR, 0x8000);
+        cpu.reset(&mut memory);
+        for _ in 0..8 {
+            cpu.step(&mut memory);
+        }
+        
+        assert_eq!(memory.read(0x0200), 0x11);
+        assert_eq!(memory.read(0x0010), 0x11);
+        assert_eq!(memory.read(0x0032), 0x11);
+        assert_eq!(memory.read(0x0201), 0x22);
+        assert_eq!(memory.read(0x0011), 0x22);
+        assert_eq!(memory.read(0x0021), 0x22);
+        // Other tests may run STX concurrently, so only a lower bound holds
+        assert!(counter() >= before + 1.0);
+    }
+    
+    #[test]
+    #[should_panic(expected = "Unknown opcode: $04")]
+    fn test_tsb_not_decoded_on_nmos() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        memory.write(0x8000, 0x04); // TSB $10 (65C02 only)
+        memory.write(0x8001, 0x10);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory);
+    }
+    
+    #[test]
+    fn test_reset_reads_vector_little_endian() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(RESET_VECTOR, 0x34);
+        memory.write(RESET_VECTOR + 1, 0x12);
+        
+        cpu.reset(&mut memory);
+        
+        assert_eq!(cpu.get_pc(), memory.read_u16(RESET_VECTOR));
+        assert_eq!(cpu.get_pc(), 0x1234);
+    }
+    
+    #[test]
+    fn test_irq_push_order_and_rti() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(0x8000, 0x58); // CLI
+        memory.write(0x8001, 0x38); // SEC
+        memory.write(0x8002, 0xEA); // NOP
+        memory.write(0x9000, 0x18); // handler: CLC
+        memory.write(0x9001, 0x40); // RTI
+        memory.write_u16(0xFFFC, 0x8000);
+        memory.write_u16(IRQ_VECTOR, 0x9000);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // CLI
+        cpu.step(&mut memory); // SEC
+        let (pc, status, sp, cycles) = (cpu.get_pc(), cpu.get_status(), cpu.get_sp(), cpu.cycles);
+        
+        assert!(cpu.irq(&mut memory));
+        assert_eq!(cpu.get_pc(), 0x9000);
+        assert_eq!(cpu.get_sp(), sp.wrapping_sub(3));
+        assert_eq!(cpu.cycles, cycles + INTERRUPT_CYCLES);
+        assert!(cpu.get_flag(INTERRUPT_DISABLE));
+        
+        // PCH, PCL, P in push order, i.e. descending stack addresses
+        let stack = |offset: u8| memory.read(0x100 + sp.wrapping_sub(offset) as u16);
+        assert_eq!(stack(0), (pc >> 8) as u8);
+        assert_eq!(stack(1), (pc & 0xFF) as u8);
+        assert_eq!(stack(2), status & !BREAK_COMMAND);
+        
+        cpu.step(&mut memory); // CLC
+        cpu.step(&mut memory); // RTI
+        assert_eq!(cpu.get_pc(), pc);
+        assert_eq!(cpu.get_status(), status);
+        assert_eq!(cpu.get_sp(), sp);
+        assert!(cpu.get_flag(CARRY_FLAG));
+    }
+    
+    #[test]
+    fn test_reset_decimal_flag_depends_on_variant() {
+        for (variant, decimal_after_reset) in [(CpuVariant::Nmos6502, true), (CpuVariant::Cmos65C02, false)] {
+            let mut cpu = CPU::with_variant(variant);
+            let mut memory = Memory::new();
+            memory.write(0x8000, 0xF8); // SED
+            memory.write(0xFFFC, 0x00);
+            memory.write(0xFFFD, 0x80);
+            
+            cpu.reset(&mut memory);
+            cpu.step(&mut memory);
+            assert!(cpu.get_flag(DECIMAL_MODE));
+            
+            cpu.reset(&mut memory);
+            assert_eq!(cpu.get_flag(DECIMAL_MODE), decimal_after_reset, "{:?}", variant);
+        }
+    }
+    
+    #[test]
+    fn test_interrupt_decimal_flag_depends_on_variant() {
+        for (variant, decimal_in_handler) in [(CpuVariant::Nmos6502, true), (CpuVariant::Cmos65C02, false)] {
+            let mut cpu = CPU::with_variant(variant);
+            let mut memory = Memory::new();
+            memory.write(0x8000, 0xF8); // SED
+            memory.write(0xFFFA, 0x00); // NMI vector
+            memory.write(0xFFFB, 0x90);
+            memory.write(0xFFFC, 0x00);
+            memory.write(0xFFFD, 0x80);
+            
+            cpu.reset(&mut memory);
+            cpu.step(&mut memory);
+            cpu.nmi(&mut memory);
+            assert_eq!(cpu.get_pc(), 0x9000);
+            assert_eq!(cpu.get_flag(DECIMAL_MODE), decimal_in_handler, "{:?}", variant);
+            // The pushed status keeps D either way, so RTI restores it
+            assert_ne!(memory.read(0x01FB) & DECIMAL_MODE, 0);
+        }
+    }
+    
+    #[test]
+    fn test_irq_masked_by_interrupt_disable() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write_u16(0xFFFC, 0x8000);
+        cpu.reset(&mut memory); // Reset sets I
+        
+        assert!(!cpu.irq(&mut memory));
+        assert_eq!(cpu.get_pc(), 0x8000);
+        
+        cpu.nmi(&mut memory);
+        assert_eq!(cpu.get_pc(), memory.read_u16(NMI_VECTOR));
+    }
+    
+    #[test]
+    fn test_raised_irq_waits_for_instruction_to_finish() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.load_rom(&[
+            0x58,             // CLI
+            0xFE, 0x00, 0x02, // INC $0200,X    7 cycles
+            0xEA,             // NOP
+        ], 0x8000);
+        memory.write_u16(RESET_VECTOR, 0x8000);
+        memory.write_u16(IRQ_VECTOR, 0x9000);
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // CLI
+        let (sp, cycles) = (cpu.get_sp(), cpu.cycles);
+        
+        // Raised as INC starts: INC completes, then the IRQ is dispatched
+        cpu.raise_irq();
+        cpu.step(&mut memory);
+        assert_eq!(memory.read(0x0200), 1);
+        assert_eq!(cpu.get_pc(), 0x9000);
+        assert_eq!(cpu.cycles, cycles + 7 + INTERRUPT_CYCLES);
+        // The return address is the NOP after INC, not INC itself
+        assert_eq!(memory.read(0x100 + sp as u16), 0x80);
+        assert_eq!(memory.read(0x100 + sp.wrapping_sub(1) as u16), 0x04);
+        assert!(!cpu.pending_irq);
+        
+        // Masked, it stays pending until I is cleared
+        cpu.raise_irq();
+        cpu.step(&mut memory);
+        assert!(cpu.pending_irq);
+    }
+    
+    #[test]
+    fn test_relative_target() {
+        assert_eq!(relative_target(0x8002, 0x10), 0x8012);
+        assert_eq!(relative_target(0x8002, -2), 0x8000);
+        assert_eq!(relative_target(0x8002, -128), 0x7F82);
+        assert_eq!(relative_target(0xFFFE, 4), 0x0002);
+        
+        // BNE -4, taken, lands where relative_target says
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(0x8010, 0xD0);
+        memory.write(0x8011, 0xFC);
+        memory.write(0xFFFC, 0x10);
+        memory.write(0xFFFD, 0x80);
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory);
+        assert_eq!(cpu.get_pc(), relative_target(0x8012, -4));
+        assert_eq!(cpu.get_pc(), 0x800E);
+    }
+    
+    #[test]
+    fn test_bvc_bvs_follow_overflow() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        memory.write(0x10, 0x40);
+        
+        // BIT $10 (sets V), BVC +2 (not taken), BVS +2 (taken), CLV, BVC +2 (taken)
+        memory.write(0x8000, 0x24); // BIT $10
+        memory.write(0x8001, 0x10);
+        memory.write(0x8002, 0x50); // BVC +2
+        memory.write(0x8003, 0x02);
+        memory.write(0x8004, 0x70); // BVS +2
+        memory.write(0x8005, 0x02);
+        memory.write(0x8008, 0xB8); // CLV
+        memory.write(0x8009, 0x50); // BVC +2
+        memory.write(0x800A, 0x02);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // BIT
+        cpu.step(&mut memory); // BVC
+        assert_eq!(cpu.get_pc(), 0x8004); // Not taken
+        
+        cpu.step(&mut memory); // BVS
+        assert_eq!(cpu.get_pc(), 0x8008); // Taken
+        
+        cpu.step(&mut memory); // CLV
+        cpu.step(&mut memory); // BVC
+        assert_eq!(cpu.get_pc(), 0x800D); // Taken
+    }
+    
     #[test]
     fn test_inx_dex() {
         let mut cpu = CPU::new();
@@ -1543,6 +2437,59 @@ mod tests {
         assert!(cpu.get_flag(ZERO_FLAG));
     }
     
+    #[test]
+    fn test_inc_dec_absolute_x_page_cross() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        memory.write(0x30FF, 0x41);
+        memory.write(0x3100, 0x10);
+        
+        // LDX #$FF, INC $3000,X, DEC $3001,X
+        memory.write(0x8000, 0xA2); // LDX #$FF
+        memory.write(0x8001, 0xFF);
+        memory.write(0x8002, 0xFE); // INC $3000,X (targets $30FF)
+        memory.write(0x8003, 0x00);
+        memory.write(0x8004, 0x30);
+        memory.write(0x8005, 0xDE); // DEC $3001,X (crosses into $3100)
+        memory.write(0x8006, 0x01);
+        memory.write(0x8007, 0x30);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // LDX
+        cpu.step(&mut memory); // INC
+        assert_eq!(memory.read(0x30FF), 0x42);
+        assert_eq!(memory.read(0x3000), 0x00); // Base address untouched
+        
+        cpu.step(&mut memory); // DEC
+        assert_eq!(memory.read(0x3100), 0x0F);
+        assert_eq!(memory.read(0x30FF), 0x42); // No wrap within the page
+        assert_eq!(cpu.get_pc(), 0x8008);
+    }
+    
+    #[test]
+    fn test_inc_absolute_x_operand_fetch_wraps_pc() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        // INC $3000,X with the opcode at $FFFE: the high operand byte is
+        // fetched from $0000 after the PC wraps
+        memory.write(0xFFFE, 0xFE); // INC abs,X
+        memory.write(0xFFFF, 0x00); // Low byte
+        memory.write(0x0000, 0x30); // High byte
+        memory.write(0x3005, 0x7F);
+        
+        cpu.pc = 0xFFFE;
+        cpu.x = 0x05;
+        cpu.step(&mut memory);
+        
+        assert_eq!(memory.read(0x3005), 0x80);
+        assert!(cpu.get_flag(NEGATIVE_FLAG));
+        assert_eq!(cpu.get_pc(), 0x0001);
+    }
+    
     #[test]
     fn test_jmp_absolute() {
         let mut cpu = CPU::new();
@@ -1589,6 +2536,29 @@ mod tests {
         assert_eq!(cpu.get_sp(), initial_sp); // Stack pointer restored
     }
     
+    #[test]
+    fn test_jsr_pushes_address_of_last_operand_byte() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        
+        // JSR $9000 at $80FE, so the operand straddles a page boundary
+        memory.write(0x80FE, 0x20);
+        memory.write(0x80FF, 0x00);
+        memory.write(0x8100, 0x90);
+        memory.write(0xFFFC, 0xFE);
+        memory.write(0xFFFD, 0x80);
+        
+        cpu.reset(&mut memory);
+        let sp = cpu.get_sp();
+        cpu.step(&mut memory);
+        
+        // Operand starts at $80FF; the pushed address is $80FF + 1 = $8100,
+        // high byte first
+        assert_eq!(memory.read(0x0100 + sp as u16), 0x81);
+        assert_eq!(memory.read(0x0100 + sp.wrapping_sub(1) as u16), 0x00);
+        assert_eq!(cpu.get_sp(), sp.wrapping_sub(2));
+    }
+    
     #[test]
     fn test_zero_page_x_addressing() {
         let mut cpu = CPU::new();
@@ -1934,4 +2904,161 @@ mod tests {
         cpu.step(&mut memory);
         assert_eq!(cpu.get_pc(), 0x8001); // PC unchanged when halted
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_base_cycles_match_published_chart() {
+        use CpuVariant::*;
+        // Cycle counts from the standard 6502 timing chart, before page
+        // crossing and branch penalties
+        let chart = [
+            (0xA9, "LDA #imm", 2), (0xA5, "LDA zp", 3), (0xB5, "LDA zp,X", 4),
+            (0xAD, "LDA abs", 4), (0xBD, "LDA abs,X", 4), (0xB9, "LDA abs,Y", 4),
+            (0xA1, "LDA (zp,X)", 6), (0xB1, "LDA (zp),Y", 5),
+            (0xB6, "LDX zp,Y", 4), (0xBC, "LDY abs,X", 4),
+            (0x85, "STA zp", 3), (0x9D, "STA abs,X", 5), (0x91, "STA (zp),Y", 6),
+            (0x69, "ADC #imm", 2), (0xF1, "SBC (zp),Y", 5), (0x2C, "BIT abs", 4),
+            (0xE6, "INC zp", 5), (0xFE, "INC abs,X", 7), (0xD6, "DEC zp,X", 6),
+            (0xE8, "INX", 2), (0x9A, "TXS", 2), (0x78, "SEI", 2),
+            (0xD0, "BNE", 2), (0x4C, "JMP abs", 3), (0x6C, "JMP (ind)", 5),
+            (0x20, "JSR", 6), (0x60, "RTS", 6), (0x40, "RTI", 6), (0x00, "BRK", 7),
+        ];
+        for (opcode, name, cycles) in chart {
+            assert_eq!(base_cycles(opcode, Nmos6502), Some(cycles), "{}", name);
+        }
+        assert_eq!(base_cycles(0x6C, Cmos65C02), Some(6));
+        assert_eq!(base_cycles(0x0C, Cmos65C02), Some(6)); // TSB abs
+        
+        // Every opcode the CPU runs has a timing, and nothing else does
+        for variant in [Nmos6502, Cmos65C02] {
+            let cpu = CPU::with_variant(variant);
+            for opcode in 0..=255u8 {
+                assert_eq!(cpu.is_implemented(opcode), base_cycles(opcode, variant).is_some(), "${:02X}", opcode);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_control_flow_and_interrupt_cycles_are_counted() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.load_rom(&[
+            0x20, 0x10, 0x80, // JSR $8010
+            0x00,             // BRK
+        ], 0x8000);
+        memory.write(0x8010, 0x60); // RTS
+        memory.write(0x9000, 0x40); // NMI handler: RTI
+        memory.write_u16(RESET_VECTOR, 0x8000);
+        memory.write_u16(NMI_VECTOR, 0x9000);
+        cpu.reset(&mut memory);
+        
+        let counted = |cpu: &mut CPU, memory: &mut Memory, f: fn(&mut CPU, &mut Memory)| {
+            let before = cpu.cycles;
+            f(cpu, memory);
+            cpu.cycles - before
+        };
+        assert_eq!(counted(&mut cpu, &mut memory, |cpu, memory| cpu.step(memory)), 6); // JSR
+        assert_eq!(counted(&mut cpu, &mut memory, |cpu, memory| cpu.step(memory)), 6); // RTS
+        assert_eq!(counted(&mut cpu, &mut memory, |cpu, memory| cpu.nmi(memory)), INTERRUPT_CYCLES);
+        assert_eq!(INTERRUPT_CYCLES, 7);
+        assert_eq!(counted(&mut cpu, &mut memory, |cpu, memory| cpu.step(memory)), 6); // RTI
+        assert_eq!(cpu.get_pc(), 0x8003);
+        assert_eq!(counted(&mut cpu, &mut memory, |cpu, memory| cpu.step(memory)), 7); // BRK
+        assert!(cpu.is_halted());
+    }
+    
+    #[test]
+    fn test_page_crossing_and_branch_cycles() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.load_rom(&[
+            0xA2, 0x01,       // LDX #$01
+            0xBD, 0x00, 0x20, // LDA $2000,X    4 cycles
+            0xBD, 0xFF, 0x20, // LDA $20FF,X    5, crosses into $2100
+            0xD0, 0x00,       // BNE +0         3, taken
+            0xF0, 0x00,       // BEQ +0         2, not taken
+        ], 0x8000);
+        memory.load_rom(&[0xD0, 0x7F], 0x80F0); // BNE +$7F    4, taken onto the next page
+        memory.write(0x2001, 0x01);
+        memory.write(0x2100, 0x01); // nonzero, so BNE is taken
+        memory.write_u16(RESET_VECTOR, 0x8000);
+        cpu.reset(&mut memory);
+        
+        let mut expect = |cpu: &mut CPU, cycles: u64| {
+            let before = cpu.cycles;
+            cpu.step(&mut memory);
+            assert_eq!(cpu.cycles - before, cycles, "at ${:04X}", cpu.get_pc());
+        };
+        expect(&mut cpu, 2);
+        expect(&mut cpu, 4);
+        expect(&mut cpu, 5);
+        expect(&mut cpu, 3);
+        expect(&mut cpu, 2);
+        cpu.pc = 0x80F0;
+        expect(&mut cpu, 4);
+        assert_eq!(cpu.get_pc(), 0x8171);
+    }
+    
+    #[test]
+    fn test_branch_target_wraps_at_address_space_ends() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(0x0005, 0xD0); // BNE -$10
+        memory.write(0x0006, 0xF0);
+        memory.write(0xFFF0, 0xD0); // BNE +$20
+        memory.write(0xFFF1, 0x20);
+        memory.write_u16(RESET_VECTOR, 0x0005);
+        cpu.reset(&mut memory);
+        cpu.set_flag(ZERO_FLAG, false);
+        
+        // Backward from low memory wraps to the top of the address space
+        cpu.step(&mut memory);
+        assert_eq!(cpu.get_pc(), 0xFFF7);
+        
+        // Forward from high memory wraps into the zero page
+        cpu.pc = 0xFFF0;
+        cpu.step(&mut memory);
+        assert_eq!(cpu.get_pc(), 0x0012);
+        
+        assert_eq!(relative_target(0x0007, -0x10), 0xFFF7);
+        assert_eq!(relative_target(0xFFF2, 0x20), 0x0012);
+    }
+    
+    #[test]
+    fn test_indexed_and_indirect_stores_cannot_write_rom() {
+        let program = [
+            0x9D, 0x00, 0xF0, // STA $F000,X
+            0x99, 0x00, 0xF0, // STA $F000,Y
+            0x81, 0x20,       // STA ($20,X)
+            0x91, 0x30,       // STA ($30),Y
+            0x8E, 0x10, 0xF0, // STX $F010
+            0x8C, 0x11, 0xF0, // STY $F011
+            0xFE, 0x12, 0xF0, // INC $F012,X
+            0xDE, 0x13, 0xF0, // DEC $F013,X
+        ];
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.load_rom(&program, 0x0200);
+        memory.write_u16(0x0024, 0xF020); // ($20,X) with X = 4
+        memory.write_u16(0x0030, 0xF030); // ($30),Y with Y = 6
+        memory.load_rom(&[0xAA; 0x100], 0xF000);
+        memory.add_read_only_region("rom", 0xF000, 0xF0FF);
+        cpu.pc = 0x0200;
+        cpu.a = 0x55;
+        cpu.x = 4;
+        cpu.y = 6;
+        
+        for _ in 0..8 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!(cpu.get_pc(), 0x0200 + program.len() as u16);
+        for address in 0xF000..=0xF0FF {
+            assert_eq!(memory.read(address), 0xAA, "${:04X} changed", address);
+        }
+        
+        // The same stores still reach RAM
+        memory.write_u16(0x0024, 0x0300);
+        cpu.pc = 0x0206;
+        cpu.step(&mut memory);
+        assert_eq!(memory.read(0x0300), 0x55);
+    }
+}
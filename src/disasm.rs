@@ -0,0 +1,613 @@
+use serde::Serialize;
+
+use crate::cpu::{base_cycles, relative_target, AddressingMode, CpuVariant, CPU};
+
+/// Mnemonic and addressing mode of a single opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+}
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DisassembledInstruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub operand: String,
+    pub text: String,
+    /// Absolute destination of a relative branch
+    pub branch_target: Option<u16>,
+}
+
+/// An instruction's bytes interpreted once. The disassembler, effective
+/// address calculation, program validation and opcode usage counts all
+/// start from this, so they can't disagree about what the bytes mean.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedInstruction {
+    pub address: u16,
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    /// The bytes after the opcode, low byte first
+    pub operand_bytes: Vec<u8>,
+    /// Opcode included
+    pub length: u8,
+}
+
+/// Everything known about one opcode, for learning and tooling
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpcodeDescription {
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    /// Mnemonic and mode in words, e.g. "ADC immediate"
+    pub name: String,
+    pub length: u8,
+    /// Before page-crossing and branch penalties; None if not implemented
+    pub base_cycles: Option<u8>,
+    pub implemented: bool,
+    /// Status flags the instruction can change, most significant first
+    pub flags_affected: Vec<&'static str>,
+    pub description: &'static str,
+}
+
+impl AddressingMode {
+    /// The mode in words, as used in opcode descriptions
+    pub fn name(self) -> &'static str {
+        use AddressingMode::*;
+        match self {
+            Implicit => "implied",
+            Accumulator => "accumulator",
+            Immediate => "immediate",
+            ZeroPage => "zero page",
+            ZeroPageX => "zero page,X",
+            ZeroPageY => "zero page,Y",
+            Relative => "relative",
+            Absolute => "absolute",
+            AbsoluteX => "absolute,X",
+            AbsoluteY => "absolute,Y",
+            Indirect => "indirect",
+            IndexedIndirect => "(indirect,X)",
+            IndirectIndexed => "(indirect),Y",
+        }
+    }
+
+    /// Number of operand bytes following the opcode
+    pub fn operand_bytes(self) -> u16 {
+        use AddressingMode::*;
+        match self {
+            Implicit | Accumulator => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | Relative | IndexedIndirect | IndirectIndexed => 1,
+            Absolute | AbsoluteX | AbsoluteY | Indirect => 2,
+        }
+    }
+}
+
+impl DecodedInstruction {
+    /// Absolute destination of a relative branch
+    pub fn branch_target(&self) -> Option<u16> {
+        match self.mode {
+            AddressingMode::Relative => Some(relative_target(self.address.wrapping_add(2), self.operand_bytes[0] as i8)),
+            _ => None,
+        }
+    }
+
+    /// Memory address the instruction will read or write, given the current
+    /// index registers; `fetch` reads the pointers of indirect modes.
+    /// Follows the CPU's own address calculation, including where it wraps.
+    /// None for instructions without a memory operand, and for JMP/JSR
+    /// absolute, which only transfer control; for JMP indirect it is the
+    /// pointer that gets read.
+    pub fn effective_address<F: Fn(u16) -> u8>(&self, fetch: F, x: u8, y: u8) -> Option<u16> {
+        use AddressingMode::*;
+        let byte = || self.operand_bytes[0];
+        let word = || u16::from_le_bytes([self.operand_bytes[0], self.operand_bytes[1]]);
+        let pointer = |address: u16| u16::from_le_bytes([fetch(address), fetch(address.wrapping_add(1))]);
+        match self.mode {
+            Implicit | Accumulator | Immediate | Relative => None,
+            ZeroPage => Some(byte() as u16),
+            ZeroPageX => Some(byte().wrapping_add(x) as u16),
+            ZeroPageY => Some(byte().wrapping_add(y) as u16),
+            Absolute if matches!(self.mnemonic, "JMP" | "JSR") => None,
+            Absolute | Indirect => Some(word()),
+            AbsoluteX => Some(word().wrapping_add(x as u16)),
+            AbsoluteY => Some(word().wrapping_add(y as u16)),
+            IndexedIndirect => Some(pointer(byte().wrapping_add(x) as u16)),
+            IndirectIndexed => Some(pointer(byte() as u16).wrapping_add(y as u16)),
+        }
+    }
+
+    /// Assembler text, e.g. "LDA ($10),Y"
+    pub fn text(&self) -> String {
+        let operand = format_operand(self);
+        if operand.is_empty() {
+            self.mnemonic.to_string()
+        } else {
+            format!("{} {}", self.mnemonic, operand)
+        }
+    }
+}
+
+impl From<DecodedInstruction> for DisassembledInstruction {
+    fn from(instruction: DecodedInstruction) -> Self {
+        let mut bytes = vec![instruction.opcode];
+        bytes.extend(&instruction.operand_bytes);
+        DisassembledInstruction {
+            address: instruction.address,
+            bytes,
+            mnemonic: instruction.mnemonic.to_string(),
+            operand: format_operand(&instruction),
+            text: instruction.text(),
+            branch_target: instruction.branch_target(),
+        }
+    }
+}
+
+impl DisassembledInstruction {
+    /// The instruction as a line of a monitor listing: address, raw bytes
+    /// padded to the longest instruction, then the text, e.g.
+    /// `8000: A9 42    LDA #$42`
+    pub fn listing_line(&self) -> String {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        format!("{:04X}: {:<8} {}", self.address, bytes.join(" "), self.text)
+    }
+}
+
+/// Disassemble a byte buffer loaded at `origin` as listing lines
+pub fn listing(data: &[u8], origin: u16, variant: CpuVariant) -> Vec<String> {
+    disassemble(data, origin, variant).iter().map(DisassembledInstruction::listing_line).collect()
+}
+
+/// Number of operand bytes for `mode`, for tools sharing these tables
+pub fn operand_length(mode: AddressingMode) -> u8 {
+    mode.operand_bytes() as u8
+}
+
+/// Total length of an instruction using `mode`, opcode included
+pub fn instruction_length(mode: AddressingMode) -> u8 {
+    1 + operand_length(mode)
+}
+
+/// Addressing mode of a documented NMOS 6502 opcode. Use `lookup` for
+/// other variants.
+pub fn mode_for_opcode(opcode: u8) -> Option<AddressingMode> {
+    lookup(opcode, CpuVariant::Nmos6502).map(|info| info.mode)
+}
+
+/// Look up an opcode. Returns None for opcodes that aren't documented
+/// instructions on the given variant.
+pub fn lookup(opcode: u8, variant: CpuVariant) -> Option<OpcodeInfo> {
+    use AddressingMode::*;
+    let (mnemonic, mode) = match opcode {
+        0x69 => ("ADC", Immediate), 0x65 => ("ADC", ZeroPage), 0x75 => ("ADC", ZeroPageX),
+        0x6D => ("ADC", Absolute), 0x7D => ("ADC", AbsoluteX), 0x79 => ("ADC", AbsoluteY),
+        0x61 => ("ADC", IndexedIndirect), 0x71 => ("ADC", IndirectIndexed),
+
+        0x29 => ("AND", Immediate), 0x25 => ("AND", ZeroPage), 0x35 => ("AND", ZeroPageX),
+        0x2D => ("AND", Absolute), 0x3D => ("AND", AbsoluteX), 0x39 => ("AND", AbsoluteY),
+        0x21 => ("AND", IndexedIndirect), 0x31 => ("AND", IndirectIndexed),
+
+        0x0A => ("ASL", Accumulator), 0x06 => ("ASL", ZeroPage), 0x16 => ("ASL", ZeroPageX),
+        0x0E => ("ASL", Absolute), 0x1E => ("ASL", AbsoluteX),
+
+        0x90 => ("BCC", Relative), 0xB0 => ("BCS", Relative), 0xF0 => ("BEQ", Relative),
+        0x30 => ("BMI", Relative), 0xD0 => ("BNE", Relative), 0x10 => ("BPL", Relative),
+        0x50 => ("BVC", Relative), 0x70 => ("BVS", Relative),
+
+        0x24 => ("BIT", ZeroPage), 0x2C => ("BIT", Absolute),
+
+        0x00 => ("BRK", Implicit),
+
+        0x18 => ("CLC", Implicit), 0xD8 => ("CLD", Implicit), 0x58 => ("CLI", Implicit),
+        0xB8 => ("CLV", Implicit),
+
+        0xC9 => ("CMP", Immediate), 0xC5 => ("CMP", ZeroPage), 0xD5 => ("CMP", ZeroPageX),
+        0xCD => ("CMP", Absolute), 0xDD => ("CMP", AbsoluteX), 0xD9 => ("CMP", AbsoluteY),
+        0xC1 => ("CMP", IndexedIndirect), 0xD1 => ("CMP", IndirectIndexed),
+
+        0xE0 => ("CPX", Immediate), 0xE4 => ("CPX", ZeroPage), 0xEC => ("CPX", Absolute),
+        0xC0 => ("CPY", Immediate), 0xC4 => ("CPY", ZeroPage), 0xCC => ("CPY", Absolute),
+
+        0xC6 => ("DEC", ZeroPage), 0xD6 => ("DEC", ZeroPageX), 0xCE => ("DEC", Absolute),
+        0xDE => ("DEC", AbsoluteX),
+        0xCA => ("DEX", Implicit), 0x88 => ("DEY", Implicit),
+
+        0x49 => ("EOR", Immediate), 0x45 => ("EOR", ZeroPage), 0x55 => ("EOR", ZeroPageX),
+        0x4D => ("EOR", Absolute), 0x5D => ("EOR", AbsoluteX), 0x59 => ("EOR", AbsoluteY),
+        0x41 => ("EOR", IndexedIndirect), 0x51 => ("EOR", IndirectIndexed),
+
+        0xE6 => ("INC", ZeroPage), 0xF6 => ("INC", ZeroPageX), 0xEE => ("INC", Absolute),
+        0xFE => ("INC", AbsoluteX),
+        0xE8 => ("INX", Implicit), 0xC8 => ("INY", Implicit),
+
+        0x4C => ("JMP", Absolute), 0x6C => ("JMP", Indirect),
+        0x20 => ("JSR", Absolute),
+
+        0xA9 => ("LDA", Immediate), 0xA5 => ("LDA", ZeroPage), 0xB5 => ("LDA", ZeroPageX),
+        0xAD => ("LDA", Absolute), 0xBD => ("LDA", AbsoluteX), 0xB9 => ("LDA", AbsoluteY),
+        0xA1 => ("LDA", IndexedIndirect), 0xB1 => ("LDA", IndirectIndexed),
+
+        0xA2 => ("LDX", Immediate), 0xA6 => ("LDX", ZeroPage), 0xB6 => ("LDX", ZeroPageY),
+        0xAE => ("LDX", Absolute), 0xBE => ("LDX", AbsoluteY),
+
+        0xA0 => ("LDY", Immediate), 0xA4 => ("LDY", ZeroPage), 0xB4 => ("LDY", ZeroPageX),
+        0xAC => ("LDY", Absolute), 0xBC => ("LDY", AbsoluteX),
+
+        0x4A => ("LSR", Accumulator), 0x46 => ("LSR", ZeroPage), 0x56 => ("LSR", ZeroPageX),
+        0x4E => ("LSR", Absolute), 0x5E => ("LSR", AbsoluteX),
+
+        0xEA => ("NOP", Implicit),
+
+        0x09 => ("ORA", Immediate), 0x05 => ("ORA", ZeroPage), 0x15 => ("ORA", ZeroPageX),
+        0x0D => ("ORA", Absolute), 0x1D => ("ORA", AbsoluteX), 0x19 => ("ORA", AbsoluteY),
+        0x01 => ("ORA", IndexedIndirect), 0x11 => ("ORA", IndirectIndexed),
+
+        0x48 => ("PHA", Implicit), 0x08 => ("PHP", Implicit),
+        0x68 => ("PLA", Implicit), 0x28 => ("PLP", Implicit),
+
+        0x2A => ("ROL", Accumulator), 0x26 => ("ROL", ZeroPage), 0x36 => ("ROL", ZeroPageX),
+        0x2E => ("ROL", Absolute), 0x3E => ("ROL", AbsoluteX),
+
+        0x6A => ("ROR", Accumulator), 0x66 => ("ROR", ZeroPage), 0x76 => ("ROR", ZeroPageX),
+        0x6E => ("ROR", Absolute), 0x7E => ("ROR", AbsoluteX),
+
+        0x40 => ("RTI", Implicit), 0x60 => ("RTS", Implicit),
+
+        0xE9 => ("SBC", Immediate), 0xE5 => ("SBC", ZeroPage), 0xF5 => ("SBC", ZeroPageX),
+        0xED => ("SBC", Absolute), 0xFD => ("SBC", AbsoluteX), 0xF9 => ("SBC", AbsoluteY),
+        0xE1 => ("SBC", IndexedIndirect), 0xF1 => ("SBC", IndirectIndexed),
+
+        0x38 => ("SEC", Implicit), 0xF8 => ("SED", Implicit), 0x78 => ("SEI", Implicit),
+
+        0x85 => ("STA", ZeroPage), 0x95 => ("STA", ZeroPageX), 0x8D => ("STA", Absolute),
+        0x9D => ("STA", AbsoluteX), 0x99 => ("STA", AbsoluteY),
+        0x81 => ("STA", IndexedIndirect), 0x91 => ("STA", IndirectIndexed),
+
+        0x86 => ("STX", ZeroPage), 0x96 => ("STX", ZeroPageY), 0x8E => ("STX", Absolute),
+        0x84 => ("STY", ZeroPage), 0x94 => ("STY", ZeroPageX), 0x8C => ("STY", Absolute),
+
+        0xAA => ("TAX", Implicit), 0xA8 => ("TAY", Implicit), 0xBA => ("TSX", Implicit),
+        0x8A => ("TXA", Implicit), 0x9A => ("TXS", Implicit), 0x98 => ("TYA", Implicit),
+
+        // 65C02 additions
+        0x04 if variant == CpuVariant::Cmos65C02 => ("TSB", ZeroPage),
+        0x0C if variant == CpuVariant::Cmos65C02 => ("TSB", Absolute),
+        0x14 if variant == CpuVariant::Cmos65C02 => ("TRB", ZeroPage),
+        0x1C if variant == CpuVariant::Cmos65C02 => ("TRB", Absolute),
+
+        _ => return None,
+    };
+    Some(OpcodeInfo { mnemonic, mode })
+}
+
+/// Describe a documented opcode on the given variant. None for bytes that
+/// aren't instructions there.
+pub fn describe_opcode(opcode: u8, variant: CpuVariant) -> Option<OpcodeDescription> {
+    let info = lookup(opcode, variant)?;
+    let (flags_affected, description) = mnemonic_details(info.mnemonic);
+    Some(OpcodeDescription {
+        opcode,
+        mnemonic: info.mnemonic,
+        mode: info.mode,
+        name: format!("{} {}", info.mnemonic, info.mode.name()),
+        length: instruction_length(info.mode),
+        base_cycles: base_cycles(opcode, variant),
+        implemented: CPU::with_variant(variant).is_implemented(opcode),
+        flags_affected: flags_affected.to_vec(),
+        description,
+    })
+}
+
+// Flags each instruction can change, in status register order, and what it
+// does
+fn mnemonic_details(mnemonic: &str) -> (&'static [&'static str], &'static str) {
+    match mnemonic {
+        "ADC" => (&["N", "V", "Z", "C"], "Add memory and carry to the accumulator"),
+        "AND" => (&["N", "Z"], "AND memory with the accumulator"),
+        "ASL" => (&["N", "Z", "C"], "Shift left one bit"),
+        "BCC" => (&[], "Branch if carry clear"),
+        "BCS" => (&[], "Branch if carry set"),
+        "BEQ" => (&[], "Branch if equal (zero set)"),
+        "BIT" => (&["N", "V", "Z"], "Test memory bits against the accumulator"),
+        "BMI" => (&[], "Branch if minus (negative set)"),
+        "BNE" => (&[], "Branch if not equal (zero clear)"),
+        "BPL" => (&[], "Branch if plus (negative clear)"),
+        "BRK" => (&["B", "I"], "Break; this emulator halts"),
+        "BVC" => (&[], "Branch if overflow clear"),
+        "BVS" => (&[], "Branch if overflow set"),
+        "CLC" => (&["C"], "Clear carry"),
+        "CLD" => (&["D"], "Clear decimal mode"),
+        "CLI" => (&["I"], "Clear interrupt disable"),
+        "CLV" => (&["V"], "Clear overflow"),
+        "CMP" => (&["N", "Z", "C"], "Compare memory with the accumulator"),
+        "CPX" => (&["N", "Z", "C"], "Compare memory with X"),
+        "CPY" => (&["N", "Z", "C"], "Compare memory with Y"),
+        "DEC" => (&["N", "Z"], "Decrement memory"),
+        "DEX" => (&["N", "Z"], "Decrement X"),
+        "DEY" => (&["N", "Z"], "Decrement Y"),
+        "EOR" => (&["N", "Z"], "Exclusive-OR memory with the accumulator"),
+        "INC" => (&["N", "Z"], "Increment memory"),
+        "INX" => (&["N", "Z"], "Increment X"),
+        "INY" => (&["N", "Z"], "Increment Y"),
+        "JMP" => (&[], "Jump"),
+        "JSR" => (&[], "Jump to subroutine, pushing the return address"),
+        "LDA" => (&["N", "Z"], "Load the accumulator"),
+        "LDX" => (&["N", "Z"], "Load X"),
+        "LDY" => (&["N", "Z"], "Load Y"),
+        "LSR" => (&["N", "Z", "C"], "Shift right one bit"),
+        "NOP" => (&[], "No operation"),
+        "ORA" => (&["N", "Z"], "OR memory with the accumulator"),
+        "PHA" => (&[], "Push the accumulator"),
+        "PHP" => (&[], "Push the status register"),
+        "PLA" => (&["N", "Z"], "Pull the accumulator"),
+        "PLP" => (&["N", "V", "D", "I", "Z", "C"], "Pull the status register"),
+        "ROL" => (&["N", "Z", "C"], "Rotate left one bit through carry"),
+        "ROR" => (&["N", "Z", "C"], "Rotate right one bit through carry"),
+        "RTI" => (&["N", "V", "D", "I", "Z", "C"], "Return from interrupt"),
+        "RTS" => (&[], "Return from subroutine"),
+        "SBC" => (&["N", "V", "Z", "C"], "Subtract memory and borrow from the accumulator"),
+        "SEC" => (&["C"], "Set carry"),
+        "SED" => (&["D"], "Set decimal mode"),
+        "SEI" => (&["I"], "Set interrupt disable"),
+        "STA" => (&[], "Store the accumulator"),
+        "STX" => (&[], "Store X"),
+        "STY" => (&[], "Store Y"),
+        "TAX" => (&["N", "Z"], "Transfer the accumulator to X"),
+        "TAY" => (&["N", "Z"], "Transfer the accumulator to Y"),
+        "TRB" => (&["Z"], "Test and reset memory bits against the accumulator"),
+        "TSB" => (&["Z"], "Test and set memory bits against the accumulator"),
+        "TSX" => (&["N", "Z"], "Transfer the stack pointer to X"),
+        "TXA" => (&["N", "Z"], "Transfer X to the accumulator"),
+        "TXS" => (&[], "Transfer X to the stack pointer"),
+        "TYA" => (&["N", "Z"], "Transfer Y to the accumulator"),
+        _ => (&[], ""),
+    }
+}
+
+/// Decode the instruction at `address`. `fetch` returns None past the end of
+/// the available bytes. None if the opcode isn't an instruction on `variant`
+/// or its operand is cut short.
+pub fn decode<F: Fn(u16) -> Option<u8>>(fetch: F, address: u16, variant: CpuVariant) -> Option<DecodedInstruction> {
+    let opcode = fetch(address)?;
+    let info = lookup(opcode, variant)?;
+    let operand_bytes = (1..=info.mode.operand_bytes())
+        .map(|i| fetch(address.wrapping_add(i)))
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(DecodedInstruction {
+        address,
+        opcode,
+        mnemonic: info.mnemonic,
+        mode: info.mode,
+        length: 1 + operand_bytes.len() as u8,
+        operand_bytes,
+    })
+}
+
+/// Disassemble the instruction at `address`. An unknown opcode or a
+/// truncated operand comes out as a one-byte `.byte` directive; None only
+/// when there is no byte at `address`.
+pub fn disassemble_at<F: Fn(u16) -> Option<u8>>(fetch: F, address: u16, variant: CpuVariant) -> Option<DisassembledInstruction> {
+    let opcode = fetch(address)?;
+    Some(match decode(&fetch, address, variant) {
+        Some(instruction) => instruction.into(),
+        None => DisassembledInstruction {
+            address,
+            bytes: vec![opcode],
+            mnemonic: ".byte".to_string(),
+            operand: format!("${:02X}", opcode),
+            text: format!(".byte ${:02X}", opcode),
+            branch_target: None,
+        },
+    })
+}
+
+/// Disassemble a byte buffer loaded at `origin`
+pub fn disassemble(data: &[u8], origin: u16, variant: CpuVariant) -> Vec<DisassembledInstruction> {
+    let fetch = |address: u16| data.get(address.wrapping_sub(origin) as usize).copied();
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let address = origin.wrapping_add(offset as u16);
+        match disassemble_at(fetch, address, variant) {
+            Some(instruction) => {
+                offset += instruction.bytes.len();
+                instructions.push(instruction);
+            }
+            None => break,
+        }
+    }
+
+    instructions
+}
+
+/// Memory address the instruction at `pc` will read or write, given the
+/// current index registers. See `DecodedInstruction::effective_address`.
+pub fn effective_address<F: Fn(u16) -> u8>(fetch: F, pc: u16, x: u8, y: u8, variant: CpuVariant) -> Option<u16> {
+    decode(|address| Some(fetch(address)), pc, variant)?.effective_address(fetch, x, y)
+}
+
+/// Describe a branch at `address` by `offset` bytes as signed decimal with
+/// the resolved target, e.g. "-5 ($7FFD)" for an offset byte of $FB at $8000.
+/// Takes an i32 so out-of-range offsets can be shown too.
+pub fn describe_branch(address: u16, offset: i32) -> String {
+    let target = (address as i32 + 2 + offset).rem_euclid(0x10000);
+    format!("{:+} (${:04X})", offset, target)
+}
+
+fn format_operand(instruction: &DecodedInstruction) -> String {
+    use AddressingMode::*;
+    let byte = || instruction.operand_bytes[0];
+    let word = || u16::from_le_bytes([instruction.operand_bytes[0], instruction.operand_bytes[1]]);
+    match instruction.mode {
+        Implicit => String::new(),
+        Accumulator => "A".to_string(),
+        Immediate => format!("#${:02X}", byte()),
+        ZeroPage => format!("${:02X}", byte()),
+        ZeroPageX => format!("${:02X},X", byte()),
+        ZeroPageY => format!("${:02X},Y", byte()),
+        Relative => format!("${:04X}", instruction.branch_target().expect("relative mode")),
+        Absolute => format!("${:04X}", word()),
+        AbsoluteX => format!("${:04X},X", word()),
+        AbsoluteY => format!("${:04X},Y", word()),
+        Indirect => format!("(${:04X})", word()),
+        IndexedIndirect => format!("(${:02X},X)", byte()),
+        IndirectIndexed => format!("(${:02X}),Y", byte()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operand_formats() {
+        let program = [
+            0xA9, 0x42,       // LDA #$42
+            0x9D, 0x00, 0x02, // STA $0200,X
+            0xB1, 0x10,       // LDA ($10),Y
+            0x6C, 0xFC, 0xFF, // JMP ($FFFC)
+            0xD0, 0xF4,       // BNE $8000
+            0x0A,             // ASL A
+            0x00,             // BRK
+        ];
+        let text: Vec<String> = disassemble(&program, 0x8000, CpuVariant::Nmos6502)
+            .into_iter()
+            .map(|i| i.text)
+            .collect();
+
+        assert_eq!(text, vec![
+            "LDA #$42", "STA $0200,X", "LDA ($10),Y", "JMP ($FFFC)", "BNE $8000", "ASL A", "BRK",
+        ]);
+    }
+
+    #[test]
+    fn test_listing_has_bytes_column() {
+        let program = [
+            0xA9, 0x42,       // LDA #$42
+            0x8D, 0x00, 0x02, // STA $0200
+            0xD0, 0xF9,       // BNE $8000
+            0x00,             // BRK
+            0x02,             // not an opcode
+        ];
+        assert_eq!(listing(&program, 0x8000, CpuVariant::Nmos6502), vec![
+            "8000: A9 42    LDA #$42",
+            "8002: 8D 00 02 STA $0200",
+            "8005: D0 F9    BNE $8000",
+            "8007: 00       BRK",
+            "8008: 02       .byte $02",
+        ]);
+    }
+
+    #[test]
+    fn test_no_operand_forms() {
+        let memory = [0x0A, 0xE8]; // ASL A, INX
+        let fetch = |address: u16| memory.get(address as usize).copied();
+        let asl = disassemble_at(fetch, 0, CpuVariant::Nmos6502).unwrap();
+        let inx = disassemble_at(fetch, 1, CpuVariant::Nmos6502).unwrap();
+        assert_eq!((asl.text.as_str(), asl.operand.as_str(), asl.bytes.len()), ("ASL A", "A", 1));
+        assert_eq!((inx.text.as_str(), inx.operand.as_str(), inx.bytes.len()), ("INX", "", 1));
+
+        for mode in [AddressingMode::Accumulator, AddressingMode::Implicit] {
+            assert_eq!(operand_length(mode), 0);
+            assert_eq!(instruction_length(mode), 1);
+        }
+        for address in 0..2 {
+            assert_eq!(effective_address(|a| memory[a as usize % 2], address, 0, 0, CpuVariant::Nmos6502), None);
+        }
+    }
+
+    #[test]
+    fn test_decode_length_and_mode_per_addressing_mode() {
+        use AddressingMode::*;
+        let cases = [
+            (&[0xEA][..], Implicit),
+            (&[0x0A], Accumulator),
+            (&[0xA9, 0x42], Immediate),
+            (&[0xA5, 0x10], ZeroPage),
+            (&[0xB5, 0x10], ZeroPageX),
+            (&[0xB6, 0x10], ZeroPageY),
+            (&[0xD0, 0xFE], Relative),
+            (&[0xAD, 0x00, 0x02], Absolute),
+            (&[0xBD, 0x00, 0x02], AbsoluteX),
+            (&[0xB9, 0x00, 0x02], AbsoluteY),
+            (&[0x6C, 0xFC, 0xFF], Indirect),
+            (&[0xA1, 0x10], IndexedIndirect),
+            (&[0xB1, 0x10], IndirectIndexed),
+        ];
+        for (bytes, mode) in cases {
+            let fetch = |address: u16| bytes.get(address.wrapping_sub(0x8000) as usize).copied();
+            let instruction = decode(fetch, 0x8000, CpuVariant::Nmos6502).unwrap();
+            assert_eq!(instruction.mode, mode, "{:02X}", bytes[0]);
+            assert_eq!(instruction.length as usize, bytes.len(), "{:02X}", bytes[0]);
+            assert_eq!(instruction.operand_bytes, &bytes[1..]);
+
+            // Cut short by one byte, an instruction with an operand doesn't decode
+            if bytes.len() > 1 {
+                let truncated = |address: u16| bytes[..bytes.len() - 1].get(address.wrapping_sub(0x8000) as usize).copied();
+                assert_eq!(decode(truncated, 0x8000, CpuVariant::Nmos6502), None);
+            }
+        }
+        assert_eq!(decode(|_| Some(0x02), 0x8000, CpuVariant::Nmos6502), None);
+    }
+
+    #[test]
+    fn test_every_opcode_is_described() {
+        for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            for opcode in 0..=255u8 {
+                if let Some(description) = describe_opcode(opcode, variant) {
+                    assert!(!description.description.is_empty(), "{}", description.name);
+                }
+            }
+        }
+        assert_eq!(describe_opcode(0x02, CpuVariant::Nmos6502), None);
+    }
+
+    #[test]
+    fn test_mode_for_opcode() {
+        assert_eq!(mode_for_opcode(0xBD), Some(AddressingMode::AbsoluteX));
+        assert_eq!(operand_length(AddressingMode::AbsoluteX), 2);
+        assert_eq!(instruction_length(AddressingMode::AbsoluteX), 3);
+
+        assert_eq!(mode_for_opcode(0xEA), Some(AddressingMode::Implicit));
+        assert_eq!(instruction_length(AddressingMode::Implicit), 1);
+        assert_eq!(mode_for_opcode(0x02), None);
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        let listing = disassemble(&[0xF0, 0x05, 0xD0, 0xFC], 0x8000, CpuVariant::Nmos6502);
+
+        assert_eq!(listing[0].text, "BEQ $8007");
+        assert_eq!(listing[0].branch_target, Some(0x8007));
+        assert_eq!(listing[1].text, "BNE $8000");
+        assert_eq!(listing[1].branch_target, Some(0x8000));
+
+        let jump = disassemble(&[0x4C, 0x00, 0x80], 0x8000, CpuVariant::Nmos6502);
+        assert_eq!(jump[0].branch_target, None);
+    }
+
+    #[test]
+    fn test_describe_branch() {
+        assert_eq!(describe_branch(0x8000, (0xFB_u8 as i8).into()), "-5 ($7FFD)");
+        assert_eq!(describe_branch(0x8000, 0x05), "+5 ($8007)");
+        assert_eq!(describe_branch(0xFFFE, 0x01), "+1 ($0001)");
+        assert_eq!(describe_branch(0x8000, 0x1000), "+4096 ($9002)");
+    }
+
+    #[test]
+    fn test_unknown_and_truncated_bytes() {
+        let listing = disassemble(&[0x04, 0x02, 0xAD, 0x00], 0x1000, CpuVariant::Nmos6502);
+        let text: Vec<&str> = listing.iter().map(|i| i.text.as_str()).collect();
+
+        // $04 is only TSB on the 65C02; LDA absolute is missing its high byte,
+        // so only the opcode becomes data and decoding resumes after it
+        assert_eq!(text, vec![".byte $04", ".byte $02", ".byte $AD", "BRK"]);
+
+        let cmos = disassemble(&[0x04, 0x10], 0x1000, CpuVariant::Cmos65C02);
+        assert_eq!(cmos[0].text, "TSB $10");
+    }
+}
@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
+use crate::cpu::RESET_VECTOR;
+use crate::memory::Memory;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum EmulatorType {
     Micro,      // 100K cycles/sec, 16KB memory
     Small,      // 500K cycles/sec, 32KB memory  
+    #[default]
     Standard,   // 1M cycles/sec, 64KB memory
     Performance,// 5M cycles/sec, 64KB memory
     Turbo,      // 10M cycles/sec, 64KB memory
@@ -16,7 +19,9 @@ pub struct InstanceTemplate {
     pub id: String,
     pub name: String,
     pub description: String,
-    pub rom_image: Vec<u8>,
+    pub rom_image: Vec<u8>, // Legacy single ROM, loaded so it ends at $FFFF
+    #[serde(default)]
+    pub rom_segments: Vec<RomSegment>,
     pub reset_vector: u16,
     pub initial_memory: HashMap<u16, u8>,
     pub emulator_type: EmulatorType,
@@ -26,12 +31,26 @@ pub struct InstanceTemplate {
     pub tags: Vec<String>,
 }
 
+// One ROM image within a template, e.g. BASIC, KERNAL or character ROM
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RomSegment {
+    #[serde(default)]
+    pub name: String,
+    pub base_address: u16,
+    pub bytes: Vec<u8>,
+    pub read_only: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmulatorSpecs {
     pub max_cycles_per_second: u64,
     pub memory_size: u64,
     pub execution_timeout_ms: u64,
     pub pricing_tier: PricingTier,
+    // Total instructions an instance may execute before its usage stats are
+    // reset; None means unmetered
+    #[serde(default)]
+    pub instruction_budget: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +68,54 @@ pub struct CreateInstanceRequest {
     pub name: Option<String>,
     pub tags: Option<Vec<String>>,
     pub auto_start: Option<bool>,
+    pub auto_recover: Option<bool>,
+}
+
+// Limits on what a create request may ask for
+pub const MAX_INSTANCE_NAME_LENGTH: usize = 64;
+pub const MAX_INSTANCE_TAGS: usize = 16;
+pub const MAX_INSTANCE_TAG_LENGTH: usize = 32;
+
+/// A problem with one field of a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl CreateInstanceRequest {
+    /// Check the name and tags, reporting every problem rather than just the
+    /// first. The emulator type is already checked when the body is parsed.
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let mut error = |field, message| errors.push(FieldError { field, message });
+
+        if let Some(name) = &self.name {
+            if name.trim().is_empty() {
+                error("name", "Name must not be empty".to_string());
+            } else if name.chars().count() > MAX_INSTANCE_NAME_LENGTH {
+                error("name", format!("Name must be at most {} characters", MAX_INSTANCE_NAME_LENGTH));
+            }
+        }
+
+        if let Some(tags) = &self.tags {
+            if tags.len() > MAX_INSTANCE_TAGS {
+                error("tags", format!("At most {} tags are allowed, not {}", MAX_INSTANCE_TAGS, tags.len()));
+            }
+            if tags.iter().any(|tag| tag.trim().is_empty()) {
+                error("tags", "Tags must not be empty".to_string());
+            }
+            if let Some(tag) = tags.iter().find(|tag| tag.chars().count() > MAX_INSTANCE_TAG_LENGTH) {
+                error("tags", format!("Tag \"{}\" is longer than {} characters", tag, MAX_INSTANCE_TAG_LENGTH));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,6 +133,10 @@ pub struct EmulatorInstance {
     pub last_activity: DateTime<Utc>,
     pub tags: Vec<String>,
     pub usage_stats: UsageStats,
+    // Roll back to the checkpoint taken at the start of a run when the CPU
+    // hits an error during it
+    #[serde(default)]
+    pub auto_recover: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,7 +158,35 @@ pub struct UsageStats {
     pub last_reset: DateTime<Utc>,
 }
 
+// One entry of the instance type catalogue
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceTypeInfo {
+    pub emulator_type: EmulatorType,
+    pub specs: EmulatorSpecs,
+}
+
 impl EmulatorType {
+    pub fn all() -> Vec<EmulatorType> {
+        vec![
+            EmulatorType::Micro,
+            EmulatorType::Small,
+            EmulatorType::Standard,
+            EmulatorType::Performance,
+            EmulatorType::Turbo,
+        ]
+    }
+    
+    // Every type with its specs, smallest first
+    pub fn catalogue() -> Vec<InstanceTypeInfo> {
+        Self::all()
+            .into_iter()
+            .map(|emulator_type| InstanceTypeInfo {
+                specs: emulator_type.get_specs(),
+                emulator_type,
+            })
+            .collect()
+    }
+    
     pub fn get_specs(&self) -> EmulatorSpecs {
         match self {
             EmulatorType::Micro => EmulatorSpecs {
@@ -95,30 +194,35 @@ impl EmulatorType {
                 memory_size: 16 * 1024,
                 execution_timeout_ms: 1000,
                 pricing_tier: PricingTier::Free,
+                instruction_budget: Some(10_000_000),
             },
             EmulatorType::Small => EmulatorSpecs {
                 max_cycles_per_second: 500_000,
                 memory_size: 32 * 1024,
                 execution_timeout_ms: 5000,
                 pricing_tier: PricingTier::Basic,
+                instruction_budget: Some(100_000_000),
             },
             EmulatorType::Standard => EmulatorSpecs {
                 max_cycles_per_second: 1_000_000,
                 memory_size: 64 * 1024,
                 execution_timeout_ms: 10000,
                 pricing_tier: PricingTier::Standard,
+                instruction_budget: None,
             },
             EmulatorType::Performance => EmulatorSpecs {
                 max_cycles_per_second: 5_000_000,
                 memory_size: 64 * 1024,
                 execution_timeout_ms: 30000,
                 pricing_tier: PricingTier::Standard,
+                instruction_budget: None,
             },
             EmulatorType::Turbo => EmulatorSpecs {
                 max_cycles_per_second: 10_000_000,
                 memory_size: 64 * 1024,
                 execution_timeout_ms: 60000,
                 pricing_tier: PricingTier::Premium,
+                instruction_budget: None,
             },
         }
     }
@@ -134,12 +238,6 @@ impl EmulatorType {
     }
 }
 
-impl Default for EmulatorType {
-    fn default() -> Self {
-        EmulatorType::Standard
-    }
-}
-
 impl Default for UsageStats {
     fn default() -> Self {
         Self {
@@ -153,6 +251,57 @@ impl Default for UsageStats {
 }
 
 impl InstanceTemplate {
+    // All ROM segments, including the legacy rom_image if present
+    pub fn all_rom_segments(&self) -> Vec<RomSegment> {
+        let mut segments = self.rom_segments.clone();
+        if !self.rom_image.is_empty() {
+            segments.push(RomSegment {
+                name: "rom".to_string(),
+                base_address: (0x10000 - self.rom_image.len().min(0x10000)) as u16,
+                bytes: self.rom_image.clone(),
+                read_only: true,
+            });
+        }
+        segments
+    }
+    
+    // Set up a fresh memory from this template: reset vector, initial
+    // memory, then ROMs. Nothing is loaded if a ROM doesn't fit.
+    pub fn apply_to(&self, memory: &mut Memory) -> Result<(), String> {
+        let segments = self.all_rom_segments();
+        for segment in &segments {
+            if segment.bytes.is_empty() || segment.base_address as usize + segment.bytes.len() > 0x10000 {
+                return Err(format!(
+                    "ROM segment '{}' at ${:04X} ({} bytes) does not fit in memory",
+                    segment.name, segment.base_address, segment.bytes.len()
+                ));
+            }
+        }
+        
+        self.load_contents(memory);
+        
+        for segment in &segments {
+            if segment.read_only {
+                let end = segment.base_address + (segment.bytes.len() - 1) as u16;
+                memory.add_read_only_region(&segment.name, segment.base_address, end);
+            }
+        }
+        Ok(())
+    }
+    
+    // Write the reset vector, initial memory and every ROM segment's bytes,
+    // without marking anything read-only. A cold reset reapplies these after
+    // clearing RAM, which also restores writable segments.
+    pub fn load_contents(&self, memory: &mut Memory) {
+        memory.write_u16(RESET_VECTOR, self.reset_vector);
+        for (&address, &value) in &self.initial_memory {
+            memory.write(address, value);
+        }
+        for segment in self.all_rom_segments() {
+            memory.load_rom(&segment.bytes, segment.base_address);
+        }
+    }
+    
     pub fn create_basic_templates() -> Vec<InstanceTemplate> {
         vec![
             InstanceTemplate {
@@ -160,6 +309,7 @@ impl InstanceTemplate {
                 name: "Basic 6502 System".to_string(),
                 description: "Clean 6502 system with reset vector at $8000".to_string(),
                 rom_image: vec![],
+                rom_segments: vec![],
                 reset_vector: 0x8000,
                 initial_memory: {
                     let mut mem = HashMap::new();
@@ -178,6 +328,7 @@ impl InstanceTemplate {
                 name: "Apple II-like System".to_string(),
                 description: "6502 system similar to Apple II with ROM at $F000".to_string(),
                 rom_image: vec![],
+                rom_segments: vec![],
                 reset_vector: 0xF000,
                 initial_memory: {
                     let mut mem = HashMap::new();
@@ -197,6 +348,7 @@ impl InstanceTemplate {
                 name: "NES-like System".to_string(),
                 description: "6502 system similar to NES/Famicom".to_string(),
                 rom_image: vec![],
+                rom_segments: vec![],
                 reset_vector: 0xC000,
                 initial_memory: {
                     let mut mem = HashMap::new();
@@ -216,6 +368,7 @@ impl InstanceTemplate {
                 name: "Commodore 64-like System".to_string(),
                 description: "6502 system with C64-like memory layout".to_string(),
                 rom_image: vec![],
+                rom_segments: vec![],
                 reset_vector: 0xE000,
                 initial_memory: {
                     let mut mem = HashMap::new();
@@ -234,6 +387,7 @@ impl InstanceTemplate {
                 name: "Development System".to_string(),
                 description: "Fast development environment with debugging support".to_string(),
                 rom_image: vec![],
+                rom_segments: vec![],
                 reset_vector: 0x8000,
                 initial_memory: {
                     let mut mem = HashMap::new();
@@ -263,7 +417,7 @@ impl EmulatorInstance {
     ) -> Self {
         let specs = emulator_type.get_specs();
         let instance_name = name.unwrap_or_else(|| {
-            format!("{}-{}", emulator_type.to_string(), uuid::Uuid::new_v4().to_string()[..8].to_string())
+            format!("{}-{}", emulator_type.to_string(), &uuid::Uuid::new_v4().to_string()[..8])
         });
         
         Self {
@@ -280,6 +434,7 @@ impl EmulatorInstance {
             last_activity: Utc::now(),
             tags: tags.unwrap_or_default(),
             usage_stats: UsageStats::default(),
+            auto_recover: false,
         }
     }
     
@@ -316,6 +471,21 @@ impl EmulatorInstance {
         self.usage_stats.api_calls += 1;
     }
     
+    // Instructions left before the budget is exhausted, or None if unmetered
+    pub fn budget_remaining(&self) -> Option<u64> {
+        self.specs
+            .instruction_budget
+            .map(|budget| budget.saturating_sub(self.usage_stats.total_instructions))
+    }
+    
+    pub fn budget_exhausted(&self) -> bool {
+        self.budget_remaining() == Some(0)
+    }
+    
+    pub fn reset_usage_stats(&mut self) {
+        self.usage_stats = UsageStats::default();
+    }
+    
     pub fn get_runtime_seconds(&self) -> u64 {
         if let Some(started) = self.started_at {
             let end_time = if matches!(self.state, InstanceState::Running) {
@@ -47,6 +47,11 @@ pub mod metrics;
 pub mod auth;
 pub mod instance_types;
 pub mod snapshots;
+pub mod programs;
+pub mod disasm;
+pub mod trace;
+pub mod asm;
+pub mod timer;
 
 pub use cpu::CPU;
 pub use memory::Memory;
\ No newline at end of file
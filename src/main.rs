@@ -1,3 +1,5 @@
+#![recursion_limit = "2048"]
+
 pub mod cpu;
 pub mod memory;
 pub mod server;
@@ -5,6 +7,11 @@ pub mod metrics;
 pub mod auth;
 pub mod instance_types;
 pub mod snapshots;
+pub mod programs;
+pub mod disasm;
+pub mod trace;
+pub mod asm;
+pub mod timer;
 
 use std::env;
 
@@ -21,33 +28,46 @@ async fn main() {
     }
 }
 
+// Counts X down from 5, adding 7 to A each time, so A ends up as 35 ($23)
+const EXAMPLE_SOURCE: &str = "
+        LDX #5
+        LDA #0
+loop:   CLC
+        ADC #7
+        DEX
+        BNE loop
+        BRK
+";
+
 async fn run_example() {
-    use cpu::CPU;
+    use asm::load_source;
+    use cpu::{CpuVariant, CPU};
+    use disasm::disassemble;
     use memory::Memory;
     
     let mut memory = Memory::new();
     let mut cpu = CPU::new();
     
-    // Example: Load a simple program
-    memory.write(0x8000, 0xA9); // LDA #$42
-    memory.write(0x8001, 0x42);
-    memory.write(0x8002, 0x00); // BRK
+    // Assemble the example at $8000 and point the reset vector at it
+    let bytes = load_source(&mut memory, EXAMPLE_SOURCE, 0x8000, CpuVariant::Nmos6502)
+        .expect("example program assembles");
     
-    // Set reset vector to 0x8000
-    memory.write(0xFFFC, 0x00);
-    memory.write(0xFFfd, 0x80);
+    println!("Program:");
+    for instruction in disassemble(&bytes, 0x8000, CpuVariant::Nmos6502) {
+        println!("  ${:04X}  {}", instruction.address, instruction.text);
+    }
     
     cpu.reset(&mut memory);
     
-    // Run a few cycles
-    for _ in 0..10 {
+    // Run until BRK
+    for _ in 0..100 {
         cpu.step(&mut memory);
         if cpu.is_halted() {
             break;
         }
     }
     
-    println!("CPU State:");
+    println!("\nCPU State:");
     println!("A: ${:02X}", cpu.get_register_a());
     println!("X: ${:02X}", cpu.get_register_x());
     println!("Y: ${:02X}", cpu.get_register_y());
@@ -1,40 +1,432 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// A memory-mapped device. Reads and writes inside the handler's region are
+/// routed here instead of to RAM; the address passed in is the full bus address.
+pub trait IoHandler: Send {
+    fn read(&self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Advance by `cycles` clock cycles. Returns true to raise an IRQ.
+    fn tick(&mut self, _cycles: u64) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionKind {
+    ReadOnly,
+    Io,
+    Unmapped,
+}
+
+/// Description of a special region, as reported by the memory map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemoryRegion {
+    pub kind: RegionKind,
+    pub name: String,
+    pub start: u16,
+    pub end: u16, // inclusive
+}
+
+impl MemoryRegion {
+    pub fn contains(&self, address: u16) -> bool {
+        address >= self.start && address <= self.end
+    }
+}
+
+struct IoMapping {
+    region: MemoryRegion,
+    handler: Box<dyn IoHandler>,
+}
+
+// Size of the full 6502 address space
+pub const ADDRESS_SPACE: usize = 65536;
+
 pub struct Memory {
-    data: [u8; 65536], // 64KB memory space
+    // Backing store. Smaller than the address space on small instances, in
+    // which case it is mirrored: only the low address lines are decoded, so
+    // each address maps to address % size.
+    data: Vec<u8>,
+    mask: usize,
+    read_only: Vec<MemoryRegion>,
+    io: Vec<IoMapping>,
+    unmapped: Vec<MemoryRegion>,
+    // True while no read-only, I/O or unmapped regions are registered, so
+    // accesses can go straight to the array without searching them
+    flat: bool,
+    // When set, reads from unmapped regions return the last value seen on
+    // the data bus instead of the backing store
+    pub open_bus: bool,
+    bus_value: Cell<u8>,
+    // Bus accesses since creation, including opcode and operand fetches
+    reads: Cell<u64>,
+    writes: u64,
+    // Final value of every address written since start_write_log, if logging
+    write_log: Option<BTreeMap<u16, u8>>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Memory {
     pub fn new() -> Self {
+        Self::with_size(ADDRESS_SPACE)
+    }
+
+    // Memory backed by `size` bytes, which must be a power of two no larger
+    // than 64KB so that it mirrors evenly across the address space
+    pub fn with_size(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two() && size <= ADDRESS_SPACE,
+            "memory size must be a power of two up to 64KB, not {}",
+            size
+        );
         Memory {
-            data: [0; 65536],
+            data: vec![0; size],
+            mask: size - 1,
+            read_only: Vec::new(),
+            io: Vec::new(),
+            unmapped: Vec::new(),
+            flat: true,
+            open_bus: false,
+            bus_value: Cell::new(0),
+            reads: Cell::new(0),
+            writes: 0,
+            write_log: None,
         }
     }
-    
+
+    // Fill RAM with a pseudo-random pattern (SplitMix64) instead of zeros,
+    // to shake out programs that read memory they never initialized. The
+    // same seed always gives the same contents.
+    pub fn new_seeded(seed: u64) -> Self {
+        let mut memory = Self::new();
+        let mut state = seed;
+        for chunk in memory.data.chunks_mut(8) {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^= z >> 31;
+            chunk.copy_from_slice(&z.to_le_bytes());
+        }
+        memory
+    }
+
     pub fn read(&self, address: u16) -> u8 {
-        self.data[address as usize]
+        let value = if self.flat {
+            self.data[address as usize & self.mask]
+        } else if let Some(mapping) = self.io.iter().find(|m| m.region.contains(address)) {
+            mapping.handler.read(address)
+        } else if self.open_bus && self.unmapped.iter().any(|r| r.contains(address)) {
+            // Nothing drives the bus, so it still holds the previous value
+            self.bus_value.get()
+        } else {
+            self.data[address as usize & self.mask]
+        };
+        self.bus_value.set(value);
+        self.reads.set(self.reads.get() + 1);
+        value
     }
-    
+
+    // Read for inspection (tracing, debugging) without counting the access
+    // or disturbing the open-bus value. Gives what `read` would return right
+    // now, so an unmapped address shows the value left on the bus.
+    pub fn peek(&self, address: u16) -> u8 {
+        if self.flat {
+            return self.data[address as usize & self.mask];
+        }
+        if let Some(mapping) = self.io.iter().find(|m| m.region.contains(address)) {
+            return mapping.handler.read(address);
+        }
+        if self.open_bus && self.unmapped.iter().any(|r| r.contains(address)) {
+            return self.bus_value.get();
+        }
+        self.data[address as usize & self.mask]
+    }
+
+    // The only way the CPU stores to memory, whatever the addressing mode,
+    // so read-only and unmapped regions hold for indexed and indirect
+    // stores as much as for direct ones
     pub fn write(&mut self, address: u16, value: u8) {
-        self.data[address as usize] = value;
+        self.bus_value.set(value);
+        self.writes += 1;
+        if self.flat {
+            self.data[address as usize & self.mask] = value;
+            self.log_write(address, value);
+            return;
+        }
+        if let Some(mapping) = self.io.iter_mut().find(|m| m.region.contains(address)) {
+            mapping.handler.write(address, value);
+            self.log_write(address, value);
+            return;
+        }
+        // Writes to ROM or to nothing at all are silently dropped, as on real hardware
+        if self.read_only.iter().chain(self.unmapped.iter()).any(|r| r.contains(address)) {
+            return;
+        }
+        self.data[address as usize & self.mask] = value;
+        self.log_write(address, value);
     }
-    
-    // Load ROM data into memory
+
+    fn log_write(&mut self, address: u16, value: u8) {
+        if let Some(log) = &mut self.write_log {
+            log.insert(address, value);
+        }
+    }
+
+    // Start recording the addresses written from now on, discarding any
+    // earlier log
+    pub fn start_write_log(&mut self) {
+        self.write_log = Some(BTreeMap::new());
+    }
+
+    // Stop recording and return address -> last value written, ordered by
+    // address. None if logging wasn't started.
+    pub fn take_write_log(&mut self) -> Option<BTreeMap<u16, u8>> {
+        self.write_log.take()
+    }
+
+    // Load ROM data into memory. This writes the backing store directly, so
+    // it also works for ranges that have been marked read-only.
     pub fn load_rom(&mut self, data: &[u8], start_address: u16) {
         let start = start_address as usize;
-        let end = (start + data.len()).min(65536);
-        let len = end - start;
-        self.data[start..end].copy_from_slice(&data[..len]);
+        let end = (start + data.len()).min(ADDRESS_SPACE);
+        for (address, &byte) in (start..end).zip(data) {
+            self.data[address & self.mask] = byte;
+        }
+    }
+
+    // Zero every byte of RAM, leaving ROM contents alone. With memory
+    // smaller than the address space, a byte mirrored into a read-only
+    // region counts as ROM.
+    pub fn clear_ram(&mut self) {
+        let mut rom = vec![false; self.data.len()];
+        for region in &self.read_only {
+            for address in region.start..=region.end {
+                rom[address as usize & self.mask] = true;
+            }
+        }
+        for (byte, rom) in self.data.iter_mut().zip(rom) {
+            if !rom {
+                *byte = 0;
+            }
+        }
+    }
+
+    // Bytes in the backing store; addresses beyond it mirror lower ones
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    // Total (reads, writes) performed through read/write so far
+    pub fn access_counts(&self) -> (u64, u64) {
+        (self.reads.get(), self.writes)
+    }
+
+    // Mark start..=end as read-only
+    pub fn add_read_only_region(&mut self, name: &str, start: u16, end: u16) {
+        self.flat = false;
+        self.read_only.push(MemoryRegion {
+            kind: RegionKind::ReadOnly,
+            name: name.to_string(),
+            start,
+            end,
+        });
+    }
+
+    // Mark start..=end as having no device attached
+    pub fn add_unmapped_region(&mut self, name: &str, start: u16, end: u16) {
+        self.flat = false;
+        self.unmapped.push(MemoryRegion {
+            kind: RegionKind::Unmapped,
+            name: name.to_string(),
+            start,
+            end,
+        });
+    }
+
+    // Route start..=end to a device. I/O regions take priority over RAM and ROM.
+    pub fn add_io_handler(&mut self, name: &str, start: u16, end: u16, handler: Box<dyn IoHandler>) {
+        self.flat = false;
+        self.io.push(IoMapping {
+            region: MemoryRegion {
+                kind: RegionKind::Io,
+                name: name.to_string(),
+                start,
+                end,
+            },
+            handler,
+        });
+    }
+
+    // Let devices that count time see `cycles` more clock cycles. Returns
+    // whether any of them raised an IRQ.
+    pub fn tick_devices(&mut self, cycles: u64) -> bool {
+        let mut irq = false;
+        for mapping in &mut self.io {
+            irq |= mapping.handler.tick(cycles);
+        }
+        irq
+    }
+
+    // Whether accesses are plain array reads and writes
+    pub fn is_flat(&self) -> bool {
+        self.flat
     }
     
+    // All special regions, ordered by start address
+    pub fn regions(&self) -> Vec<MemoryRegion> {
+        let mut regions: Vec<MemoryRegion> = self.read_only
+            .iter()
+            .chain(self.unmapped.iter())
+            .cloned()
+            .chain(self.io.iter().map(|m| m.region.clone()))
+            .collect();
+        regions.sort_by_key(|r| (r.start, r.end));
+        regions
+    }
+
     // Read a 16-bit value in little-endian format
     pub fn read_u16(&self, address: u16) -> u16 {
         let low = self.read(address) as u16;
         let high = self.read(address.wrapping_add(1)) as u16;
         (high << 8) | low
     }
-    
-    // Write a 16-bit value in little-endian format
+
+    // Write a 16-bit value in little-endian format. At $FFFF the high byte
+    // wraps around to $0000.
     pub fn write_u16(&mut self, address: u16, value: u16) {
         self.write(address, (value & 0xFF) as u8);
         self.write(address.wrapping_add(1), (value >> 8) as u8);
     }
-}
\ No newline at end of file
+    
+    // Like write_u16, but refuses (writing nothing) if the value would wrap
+    // past $FFFF
+    pub fn write_u16_checked(&mut self, address: u16, value: u16) -> Result<(), String> {
+        if address == 0xFFFF {
+            return Err("16-bit write at $FFFF would wrap to $0000".to_string());
+        }
+        self.write_u16(address, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_memory_mirrors_across_address_space() {
+        let mut small = Memory::with_size(16 * 1024);
+        assert_eq!(small.size(), 16 * 1024);
+        small.write(0x0000, 0x42);
+        assert_eq!(small.read(0x8000), 0x42);
+        small.write_u16(0xFFFC, 0x8000);
+        assert_eq!(small.read_u16(0x3FFC), 0x8000);
+
+        let mut full = Memory::new();
+        assert_eq!(full.size(), ADDRESS_SPACE);
+        full.write(0x0000, 0x42);
+        full.write(0xC000, 0x99);
+        assert_eq!(full.read(0x8000), 0x00);
+        assert_eq!(full.read(0xC000), 0x99);
+    }
+
+    #[test]
+    fn test_open_bus_returns_last_value() {
+        let mut memory = Memory::new();
+        memory.add_unmapped_region("unmapped", 0xD000, 0xDFFF);
+        memory.open_bus = true;
+        memory.write(0x0010, 0x42);
+        memory.write(0x0011, 0x99);
+
+        assert_eq!(memory.read(0x0010), 0x42);
+        assert_eq!(memory.read(0xD000), 0x42);
+        assert_eq!(memory.read(0x0011), 0x99);
+        assert_eq!(memory.read(0xDFFF), 0x99);
+    }
+
+    #[test]
+    fn test_peek_agrees_with_read_on_open_bus() {
+        let mut memory = Memory::new();
+        memory.add_unmapped_region("unmapped", 0xD000, 0xDFFF);
+        memory.open_bus = true;
+        memory.write(0x0010, 0x42);
+
+        assert_eq!(memory.peek(0xD000), 0x42);
+        assert_eq!(memory.peek(0xD000), memory.read(0xD000));
+    }
+
+    #[test]
+    fn test_unmapped_without_open_bus_reads_backing_store() {
+        let mut memory = Memory::new();
+        memory.add_unmapped_region("unmapped", 0xD000, 0xDFFF);
+        memory.write(0x0010, 0x42);
+        memory.write(0xD000, 0x55); // dropped, nothing there
+
+        assert_eq!(memory.read(0x0010), 0x42);
+        assert_eq!(memory.read(0xD000), 0x00);
+    }
+    
+    #[test]
+    fn test_flat_memory_matches_region_checked_path() {
+        let mut flat = Memory::new();
+        let mut checked = Memory::new();
+        // A region nowhere near the addresses used forces the slow path
+        checked.add_read_only_region("rom", 0xF000, 0xF0FF);
+        assert!(flat.is_flat());
+        assert!(!checked.is_flat());
+
+        for i in 0..0x1000u16 {
+            let address = i.wrapping_mul(7);
+            let value = (i ^ (i >> 3)) as u8;
+            flat.write(address, value);
+            checked.write(address, value);
+        }
+        for address in 0..0x8000u16 {
+            assert_eq!(flat.read(address), checked.read(address));
+        }
+        assert_eq!(flat.access_counts(), checked.access_counts());
+    }
+
+    #[test]
+    fn test_write_log_skips_dropped_writes() {
+        let mut memory = Memory::new();
+        memory.add_read_only_region("rom", 0xF000, 0xFFFF);
+        memory.write(0x0010, 0x01); // before logging starts
+        memory.start_write_log();
+        memory.write(0x0020, 0x01);
+        memory.write(0x0020, 0x02);
+        memory.write(0xF000, 0x03); // dropped
+        memory.write(0x0010, 0x04);
+
+        let log = memory.take_write_log().unwrap();
+        assert_eq!(log.into_iter().collect::<Vec<_>>(), vec![(0x0010, 0x04), (0x0020, 0x02)]);
+        assert!(memory.take_write_log().is_none());
+    }
+
+    #[test]
+    fn test_write_u16_at_top_of_memory() {
+        let mut memory = Memory::new();
+        memory.write_u16(0xFFFF, 0x1234);
+        assert_eq!(memory.read(0xFFFF), 0x34);
+        assert_eq!(memory.read(0x0000), 0x12);
+        assert_eq!(memory.read_u16(0xFFFF), 0x1234);
+
+        let mut memory = Memory::new();
+        assert!(memory.write_u16_checked(0xFFFF, 0x1234).is_err());
+        assert_eq!(memory.read(0xFFFF), 0x00);
+        assert_eq!(memory.read(0x0000), 0x00);
+
+        memory.write_u16_checked(0xFFFE, 0x1234).unwrap();
+        assert_eq!(memory.read_u16(0xFFFE), 0x1234);
+    }
+}
@@ -1,7 +1,8 @@
 use lazy_static::lazy_static;
 use prometheus::{
-    Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, Opts, Registry,
+    Counter, CounterVec, Gauge, GaugeVec, HistogramOpts, HistogramVec, Opts, Registry,
 };
+use std::sync::Once;
 use std::time::{Duration, Instant};
 
 lazy_static! {
@@ -14,6 +15,12 @@ lazy_static! {
         &["opcode", "instruction"]
     ).expect("Failed to create CPU instructions counter");
     
+    /// Counter for opcodes the CPU was asked to execute but doesn't implement
+    pub static ref UNIMPLEMENTED_OPCODES_TOTAL: CounterVec = CounterVec::new(
+        Opts::new("unimplemented_opcodes_total", "Total encounters of opcodes the emulator does not implement"),
+        &["opcode"]
+    ).expect("Failed to create unimplemented opcodes counter");
+    
     /// Counter for CPU cycles executed
     pub static ref CPU_CYCLES_TOTAL: Counter = Counter::new(
         "cpu_cycles_total", "Total number of CPU cycles executed"
@@ -26,6 +33,13 @@ lazy_static! {
         &["instruction"]
     ).expect("Failed to create instruction duration histogram");
     
+    /// Histogram for simulated 6502 cycles per instruction, independent of host speed
+    pub static ref INSTRUCTION_CYCLES: HistogramVec = HistogramVec::new(
+        HistogramOpts::new("instruction_cycles", "Simulated CPU cycles spent per instruction")
+            .buckets(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]),
+        &["instruction"]
+    ).expect("Failed to create instruction cycles histogram");
+    
     /// Counter for API requests by endpoint and method
     pub static ref API_REQUESTS_TOTAL: CounterVec = CounterVec::new(
         Opts::new("api_requests_total", "Total number of API requests"),
@@ -75,12 +89,23 @@ lazy_static! {
     ).expect("Failed to create program loads counter");
 }
 
-/// Initialize Prometheus metrics by registering them with the global registry
+static INIT: Once = Once::new();
+
+/// Initialize Prometheus metrics by registering them with the global registry.
+/// Safe to call more than once; only the first call registers anything.
 pub fn init_metrics() {
+    INIT.call_once(register_metrics);
+}
+
+fn register_metrics() {
     REGISTRY
         .register(Box::new(CPU_INSTRUCTIONS_TOTAL.clone()))
         .expect("Failed to register CPU instructions counter");
     
+    REGISTRY
+        .register(Box::new(UNIMPLEMENTED_OPCODES_TOTAL.clone()))
+        .expect("Failed to register unimplemented opcodes counter");
+    
     REGISTRY
         .register(Box::new(CPU_CYCLES_TOTAL.clone()))
         .expect("Failed to register CPU cycles counter");
@@ -89,6 +114,10 @@ pub fn init_metrics() {
         .register(Box::new(INSTRUCTION_DURATION.clone()))
         .expect("Failed to register instruction duration histogram");
     
+    REGISTRY
+        .register(Box::new(INSTRUCTION_CYCLES.clone()))
+        .expect("Failed to register instruction cycles histogram");
+    
     REGISTRY
         .register(Box::new(API_REQUESTS_TOTAL.clone()))
         .expect("Failed to register API requests counter");
@@ -122,17 +151,28 @@ pub fn init_metrics() {
         .expect("Failed to register program loads counter");
 }
 
-/// Record a CPU instruction execution
-pub fn record_instruction(opcode: u8, instruction_name: &str, duration: Duration) {
+/// Record a CPU instruction execution, with its host duration and simulated cycle cost
+pub fn record_instruction(opcode: u8, instruction_name: &str, duration: Duration, cycles: u64) {
     CPU_INSTRUCTIONS_TOTAL
         .with_label_values(&[&format!("0x{:02X}", opcode), instruction_name])
         .inc();
     
-    CPU_CYCLES_TOTAL.inc();
+    CPU_CYCLES_TOTAL.inc_by(cycles as f64);
     
     INSTRUCTION_DURATION
         .with_label_values(&[instruction_name])
         .observe(duration.as_secs_f64());
+    
+    INSTRUCTION_CYCLES
+        .with_label_values(&[instruction_name])
+        .observe(cycles as f64);
+}
+
+/// Record an attempt to execute an opcode the CPU doesn't implement
+pub fn record_unimplemented_opcode(opcode: u8) {
+    UNIMPLEMENTED_OPCODES_TOTAL
+        .with_label_values(&[&format!("0x{:02X}", opcode)])
+        .inc();
 }
 
 /// Record an API request
@@ -238,6 +278,12 @@ pub struct Timer {
     start: Instant,
 }
 
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Timer {
     pub fn new() -> Self {
         Self {
@@ -276,6 +322,9 @@ pub fn get_instruction_name(opcode: u8) -> &'static str {
         0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => "AND",
         0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => "ORA",
         0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => "EOR",
+        0x24 | 0x2C => "BIT",
+        0x04 | 0x0C => "TSB", // 65C02 only
+        0x14 | 0x1C => "TRB", // 65C02 only
         
         // Increment/Decrement
         0xE6 | 0xF6 | 0xEE | 0xFE => "INC",
@@ -297,6 +346,7 @@ pub fn get_instruction_name(opcode: u8) -> &'static str {
         0x4C | 0x6C => "JMP",
         0x20 => "JSR",
         0x60 => "RTS",
+        0x40 => "RTI",
         
         // Flag manipulation
         0x18 => "CLC",
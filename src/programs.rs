@@ -0,0 +1,123 @@
+use serde::Serialize;
+
+/// A small ready-to-run program that can be loaded into an emulator by name.
+#[derive(Debug, Serialize)]
+pub struct ExampleProgram {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub load_address: u16,
+    pub bytes: &'static [u8],
+}
+
+/// Built-in example programs. Each one starts at its load address, which is
+/// also used as the reset vector, and ends with BRK.
+pub const EXAMPLE_PROGRAMS: &[ExampleProgram] = &[
+    ExampleProgram {
+        name: "multiply",
+        description: "Multiplies 6 by 7 with repeated addition; 16-bit result at $12 (low) and $13 (high)",
+        load_address: 0x8000,
+        bytes: &[
+            0xA9, 0x06,       // LDA #$06     ; multiplicand
+            0x85, 0x10,       // STA $10
+            0xA9, 0x07,       // LDA #$07     ; multiplier
+            0x85, 0x11,       // STA $11
+            0xA9, 0x00,       // LDA #$00     ; clear result
+            0x85, 0x12,       // STA $12
+            0x85, 0x13,       // STA $13
+            0xA6, 0x11,       // LDX $11
+            0xF0, 0x0E,       // BEQ done
+            0x18,             // loop: CLC
+            0xA5, 0x12,       // LDA $12
+            0x65, 0x10,       // ADC $10
+            0x85, 0x12,       // STA $12
+            0x90, 0x02,       // BCC skip
+            0xE6, 0x13,       // INC $13
+            0xCA,             // skip: DEX
+            0xD0, 0xF2,       // BNE loop
+            0x00,             // done: BRK
+        ],
+    },
+    ExampleProgram {
+        name: "memory-copy",
+        description: "Copies the 16-byte string \"Hello from 6502!\" to $0200",
+        load_address: 0x8000,
+        bytes: &[
+            0xA2, 0x00,       // LDX #$00
+            0xBD, 0x0E, 0x80, // loop: LDA $800E,X
+            0x9D, 0x00, 0x02, // STA $0200,X
+            0xE8,             // INX
+            0xE0, 0x10,       // CPX #$10
+            0xD0, 0xF5,       // BNE loop
+            0x00,             // BRK
+            // "Hello from 6502!"
+            0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x20, 0x66, 0x72,
+            0x6F, 0x6D, 0x20, 0x36, 0x35, 0x30, 0x32, 0x21,
+        ],
+    },
+    ExampleProgram {
+        name: "fibonacci",
+        description: "Writes the first 14 Fibonacci numbers (0 to 233) to $0300-$030D",
+        load_address: 0x8000,
+        bytes: &[
+            0xA9, 0x00,       // LDA #$00
+            0x8D, 0x00, 0x03, // STA $0300
+            0xA9, 0x01,       // LDA #$01
+            0x8D, 0x01, 0x03, // STA $0301
+            0xA2, 0x00,       // LDX #$00
+            0x18,             // loop: CLC
+            0xBD, 0x00, 0x03, // LDA $0300,X
+            0x7D, 0x01, 0x03, // ADC $0301,X
+            0x9D, 0x02, 0x03, // STA $0302,X
+            0xE8,             // INX
+            0xE0, 0x0C,       // CPX #$0C
+            0xD0, 0xF1,       // BNE loop
+            0x00,             // BRK
+        ],
+    },
+];
+
+/// Look up a built-in example program by name
+pub fn find_example(name: &str) -> Option<&'static ExampleProgram> {
+    EXAMPLE_PROGRAMS.iter().find(|program| program.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Emulator;
+
+    fn run_example(name: &str) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_example(find_example(name).unwrap());
+        let result = emulator.execute_steps(1000);
+        assert!(result.halted, "{} did not reach BRK", name);
+        emulator
+    }
+
+    #[test]
+    fn test_fibonacci_example() {
+        let emulator = run_example("fibonacci");
+
+        let expected = [0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233];
+        assert_eq!(emulator.read_memory(0x0300, 14), expected);
+    }
+
+    #[test]
+    fn test_multiply_example() {
+        let emulator = run_example("multiply");
+
+        assert_eq!(emulator.read_memory(0x12, 2), vec![42, 0]);
+    }
+
+    #[test]
+    fn test_memory_copy_example() {
+        let emulator = run_example("memory-copy");
+
+        assert_eq!(emulator.read_memory(0x0200, 16), b"Hello from 6502!".to_vec());
+    }
+
+    #[test]
+    fn test_unknown_example() {
+        assert!(find_example("does-not-exist").is_none());
+    }
+}
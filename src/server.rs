@@ -1,29 +1,41 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use base64::Engine;
-use warp::{Filter, reject};
-use prometheus::Encoder;
+use warp::Filter;
 
-use crate::cpu::CPU;
-use crate::memory::Memory;
+use crate::cpu::{
+    AddressingMode, CPU, CpuError, CpuVariant, RESET_VECTOR, CARRY_FLAG, ZERO_FLAG, INTERRUPT_DISABLE,
+    DECIMAL_MODE, BREAK_COMMAND, OVERFLOW_FLAG, NEGATIVE_FLAG,
+};
+use crate::memory::{Memory, MemoryRegion, RegionKind};
 use crate::metrics::{
     init_metrics, record_api_request, set_active_emulators, update_cpu_registers,
-    record_memory_operation, record_emulator_reset, record_program_load, Timer, REGISTRY
+    get_instruction_name, Timer, REGISTRY
 };
 use crate::auth::{
-    User, UserStore, init_default_users, with_auth, with_permission, Permission,
+    ApiKey, User, UserStore, init_default_users, with_auth, with_api_key, with_key_expiry,
     LoginRequest, CreateUserRequest, CreateApiKeyRequest, AuthResponse, UserInfo,
-    ApiKeyResponse, create_jwt_token, AuthError,
+    ApiKeyResponse, Permission, create_jwt_token, DEFAULT_JWT_TTL_HOURS,
 };
 use crate::instance_types::{
     EmulatorType, EmulatorInstance, InstanceTemplate, CreateInstanceRequest,
-    InstanceState, UsageStats,
+    InstanceState,
 };
 use crate::snapshots::{
     EmulatorSnapshot, SnapshotStore, CreateSnapshotRequest, RestoreSnapshotRequest,
-    SnapshotListResponse, CheckpointReason,
+    SnapshotListResponse, CheckpointReason, RestoreMode, StateBlob, hash_memory, remove_snapshot,
+};
+use crate::timer::{TimerDevice, TIMER_REGISTERS};
+use crate::programs::{ExampleProgram, EXAMPLE_PROGRAMS, find_example};
+use crate::disasm::{
+    decode, describe_opcode, disassemble, disassemble_at, listing, effective_address, lookup, DisassembledInstruction,
+    OpcodeDescription,
+};
+use crate::trace::{
+    Breakpoint, BreakpointsRequest, PcHistogram, PcHistogramConfig, PcHistogramReport, TraceBuffer, TraceConfig, TraceEntry,
+    DEFAULT_PC_HISTOGRAM_CAPACITY, DEFAULT_TRACE_CAPACITY, flag_names,
 };
 
 #[derive(Debug, Clone, Serialize)]
@@ -34,8 +46,103 @@ pub struct CpuState {
     pub pc: u16,
     pub sp: u8,
     pub status: u8,
-    pub cycles: u64,
+    pub cycles: u64, // Instructions executed since reset
     pub halted: bool,
+    pub variant: CpuVariant,
+    pub next_instruction: DisassembledInstruction, // Decoded at pc
+    pub hex: RegisterHex,
+    pub flags: StatusFlags,
+    pub cpu_cycles: u64, // Simulated clock cycles, as counted by the CPU
+    pub halt_reason: Option<String>,
+    pub last_error: Option<LastError>,
+}
+
+// The most recent CPU error, kept until the next reset
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LastError {
+    pub code: &'static str,
+    pub message: String,
+    pub pc: u16,
+}
+
+impl From<&CpuError> for LastError {
+    fn from(error: &CpuError) -> Self {
+        LastError {
+            code: error.code(),
+            message: error.to_string(),
+            pc: error.pc(),
+        }
+    }
+}
+
+// Content type a client can Accept on the state and step endpoints to get
+// the CPU state in a fixed 24-byte layout instead of JSON:
+//   0-1    pc (little-endian)
+//   2-6    a, x, y, sp, status
+//   7      bit 0 halted, bit 1 set for the 65C02
+//   8-15   cycles (instructions since reset, little-endian)
+//   16-23  cpu_cycles (little-endian)
+pub const BINARY_STATE_CONTENT_TYPE: &str = "application/vnd.ec65.cpu-state";
+
+impl CpuState {
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&[self.a, self.x, self.y, self.sp, self.status]);
+        bytes.push(self.halted as u8 | ((self.variant == CpuVariant::Cmos65C02) as u8) << 1);
+        bytes.extend_from_slice(&self.cycles.to_le_bytes());
+        bytes.extend_from_slice(&self.cpu_cycles.to_le_bytes());
+        bytes
+    }
+}
+
+// Whether the Accept header asks for BINARY_STATE_CONTENT_TYPE. JSON stays
+// the default for anything else, including */*.
+fn wants_binary_state(accept: Option<&str>) -> bool {
+    accept.is_some_and(|accept| {
+        accept.split(',').any(|media| media.split(';').next().unwrap_or("").trim() == BINARY_STATE_CONTENT_TYPE)
+    })
+}
+
+fn binary_state_reply(state: &CpuState) -> warp::reply::Response {
+    warp::Reply::into_response(warp::reply::with_header(state.to_binary(), "content-type", BINARY_STATE_CONTENT_TYPE))
+}
+
+// The registers again as 6502-style hex strings, e.g. "$8000"
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisterHex {
+    pub a: String,
+    pub x: String,
+    pub y: String,
+    pub pc: String,
+    pub sp: String,
+    pub status: String,
+}
+
+// The status register decoded bit by bit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusFlags {
+    pub negative: bool,
+    pub overflow: bool,
+    pub break_command: bool,
+    pub decimal_mode: bool,
+    pub interrupt_disable: bool,
+    pub zero: bool,
+    pub carry: bool,
+}
+
+impl StatusFlags {
+    pub fn from_status(status: u8) -> Self {
+        Self {
+            negative: status & NEGATIVE_FLAG != 0,
+            overflow: status & OVERFLOW_FLAG != 0,
+            break_command: status & BREAK_COMMAND != 0,
+            decimal_mode: status & DECIMAL_MODE != 0,
+            interrupt_disable: status & INTERRUPT_DISABLE != 0,
+            zero: status & ZERO_FLAG != 0,
+            carry: status & CARRY_FLAG != 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -44,6 +151,23 @@ pub struct EmulatorState {
     pub cpu: CpuState,
 }
 
+// Zero page ($0000-$00FF) and stack page ($0100-$01FF). The live part of
+// the stack is stack_page[sp + 1..].
+#[derive(Debug, Serialize)]
+pub struct LowPages {
+    pub zero_page: Vec<u8>,
+    pub stack_page: Vec<u8>,
+    pub sp: u8,
+}
+
+// What the instruction at PC will access, worked out without running it
+#[derive(Debug, Serialize)]
+pub struct NextEffectiveAddress {
+    pub instruction: DisassembledInstruction,
+    // None when the instruction has no memory operand
+    pub effective_address: Option<u16>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MemoryWrite {
     pub address: u16,
@@ -56,28 +180,403 @@ pub struct MemoryRead {
     pub length: Option<u16>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MemoryCopy {
+    pub src: u16,
+    pub dest: u16,
+    pub length: u32, // Up to 65536, like a checksum range
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemoryChecksumQuery {
+    pub address: u16,
+    pub length: u32, // Up to 65536, so the whole address space can be covered
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryChecksum {
+    pub address: u16,
+    pub length: u32,
+    pub sha256: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpcodesUsedQuery {
+    pub address: u16,
+    pub length: u32,
+}
+
+// One distinct opcode found in a disassembled range
+#[derive(Debug, Serialize)]
+pub struct OpcodeUsage {
+    pub opcode: u8,
+    pub mnemonic: String, // .byte for undocumented opcodes
+    pub count: u32,
+    pub implemented: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpcodesUsed {
+    pub address: u16,
+    pub length: u32,
+    pub opcodes: Vec<OpcodeUsage>,
+    pub all_implemented: bool,
+}
+
+// Bytes of straight-line code a validation pass looks at by default
+pub const DEFAULT_VALIDATE_BYTES: u32 = 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateQuery {
+    pub max_bytes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ValidationIssueKind {
+    UnimplementedOpcode,
+    // A branch lands on a byte that isn't an instruction this CPU runs
+    BadBranchTarget,
+    // A store or read-modify-write aimed at the code being checked
+    WriteToCode,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationIssue {
+    pub address: u16,
+    pub kind: ValidationIssueKind,
+    pub message: String,
+}
+
+// Result of a static pass over the code reachable in a straight line from
+// the reset vector
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub entry_point: u16,
+    pub instructions_checked: u32,
+    pub bytes_checked: u32,
+    pub issues: Vec<ValidationIssue>,
+    pub valid: bool,
+}
+
 #[derive(Debug, Serialize)]
 pub struct MemoryData {
     pub address: u16,
     pub data: Vec<u8>,
+    // Set when the requested range ran past $FFFF and was cut short
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttachTimerRequest {
+    pub address: u16, // First of the timer's TIMER_REGISTERS registers
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryPage {
+    pub page: u8,
+    pub address: u16,
+    pub data: Vec<u8>, // Always 256 bytes
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ProgramLoad {
     pub address: u16,
     pub data: Vec<u8>,
+    // If given, point the reset vector here and reset after loading
+    pub entry_point: Option<u16>,
+}
+
+// Upper bound on max_steps for a single load-and-run request
+pub const MAX_LOAD_AND_RUN_STEPS: u32 = 10_000_000;
+
+#[derive(Debug, Deserialize)]
+pub struct LoadAndRun {
+    pub address: u16,
+    pub data: Vec<u8>,
+    pub max_steps: u32,
+    // Point the reset vector at the program and reset before running.
+    // Otherwise registers are left alone and execution starts at `address`.
+    #[serde(default)]
+    pub set_reset_vector: bool,
+    // As for ExecuteSteps; writes made while loading aren't included
+    #[serde(default)]
+    pub memory_diff: bool,
+}
+
+impl LoadAndRun {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.data.is_empty() {
+            return Err("Program data is empty".to_string());
+        }
+        if self.address as usize + self.data.len() > 0x10000 {
+            return Err(format!(
+                "Program of {} bytes at ${:04X} extends past the end of memory",
+                self.data.len(),
+                self.address
+            ));
+        }
+        if self.max_steps == 0 || self.max_steps > MAX_LOAD_AND_RUN_STEPS {
+            return Err(format!("max_steps must be between 1 and {}", MAX_LOAD_AND_RUN_STEPS));
+        }
+        Ok(())
+    }
+}
+
+// A one-shot run on a throwaway emulator: the program is loaded, the reset
+// vector pointed at it, and nothing is kept afterwards
+#[derive(Debug, Deserialize)]
+pub struct RunOnce {
+    pub program_base64: String,
+    pub address: u16,
+    pub max_steps: u32,
+    // Memory range to return along with the final state
+    pub read: Option<MemoryRead>,
+}
+
+// Upper bound on max_steps for run-once. Much lower than load-and-run, as
+// anyone can send one and there's no emulator to cancel it through.
+pub const MAX_RUN_ONCE_STEPS: u32 = 1_000_000;
+
+#[derive(Debug, Serialize)]
+pub struct RunOnceResult {
+    pub execution: ExecutionResult,
+    pub memory: Option<MemoryData>,
+}
+
+// Upper bound on trace-run steps, which bounds the size of the returned trace
+pub const MAX_TRACE_RUN_STEPS: u32 = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct TraceRun {
+    pub steps: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraceRunResult {
+    pub execution: ExecutionResult,
+    pub trace: Vec<TraceEntry>,
+}
+
+// Emulator state as an integrity-checked binary blob, see StateBlob
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateExport {
+    pub data_base64: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteUserQuery {
+    // Required when an admin deletes their own account
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetQuery {
+    // Also clear RAM and reapply the template's initial memory
+    #[serde(default)]
+    pub cold: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteEmulatorQuery {
+    // Cancel a run in progress and delete once it stops
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminUserSummary {
+    #[serde(flatten)]
+    pub user: UserInfo,
+    pub emulator_count: usize,
+    pub snapshot_storage_bytes: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaitHaltQuery {
+    // Defaults to DEFAULT_WAIT_HALT_MS and is capped at MAX_WAIT_HALT_MS
+    pub timeout_ms: Option<u64>,
+}
+
+pub const DEFAULT_WAIT_HALT_MS: u64 = 30_000;
+pub const MAX_WAIT_HALT_MS: u64 = 300_000;
+
+#[derive(Debug, Serialize)]
+pub struct WaitHaltResult {
+    // False if the wait timed out first
+    pub halted: bool,
+    pub cpu: CpuState,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResumeQuery {
+    // Continue from here instead of the current PC
+    pub pc: Option<u16>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetVariantRequest {
+    pub variant: CpuVariant,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisassembleRequest {
+    pub address: u16,
+    pub data_base64: String,
+    #[serde(default)]
+    pub variant: CpuVariant,
+    #[serde(default)]
+    pub format: DisassemblyFormat,
+}
+
+// How POST /disasm returns its result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisassemblyFormat {
+    // One object per instruction
+    #[default]
+    Structured,
+    // One line per instruction with the raw bytes, as a monitor shows it
+    Listing,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DescribeOpcodeQuery {
+    #[serde(default)]
+    pub variant: CpuVariant,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoadExample {
+    pub name: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExecuteSteps {
     pub steps: u32,
+    // Sets the emulator's infinite loop detection threshold; 0 disables it
+    pub loop_threshold: Option<u32>,
+    // Return the addresses written during the run and their new values
+    #[serde(default)]
+    pub memory_diff: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExecuteStream {
+    pub steps: u32,
+    pub report_every: u32,
+}
+
+// One line of a streamed run: a progress report every `report_every`
+// instructions, then the result
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamFrame {
+    Progress { steps_executed: u32, cpu: CpuState },
+    Done(ExecutionResult),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum StopReason {
+    StepLimit,
+    Halted,
+    InfiniteLoop { pc: u16 },
+    BudgetExhausted,
+    Cancelled,
+    CpuError { message: String },
+    // Hit an error and rolled back to the checkpoint taken when the run began
+    Recovered { error: String },
+    // About to execute an instruction matching a breakpoint
+    Breakpoint { pc: u16, breakpoint: Breakpoint },
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StopReason::StepLimit => write!(f, "step limit reached"),
+            StopReason::Halted => write!(f, "CPU halted"),
+            StopReason::InfiniteLoop { pc } => write!(f, "suspected infinite loop at ${:04X}", pc),
+            StopReason::BudgetExhausted => write!(f, "instruction budget exhausted"),
+            StopReason::Cancelled => write!(f, "cancelled"),
+            StopReason::CpuError { message } => write!(f, "{}", message),
+            StopReason::Recovered { error } => write!(f, "{}; restored the last checkpoint", error),
+            StopReason::Breakpoint { pc, .. } => write!(f, "breakpoint at ${:04X}", pc),
+        }
+    }
+}
+
+pub const BUDGET_EXHAUSTED: &str = "Instruction budget exhausted";
+
+// Every handler reports an unknown emulator id with this message and a 404
+pub const EMULATOR_NOT_FOUND: &str = "Emulator not found";
+
+// Refusal for starting a run while another is going; the two would share
+// the cancel flag and the first to finish would clear `running`
+pub const RUN_IN_PROGRESS: &str = "A run is already in progress; cancel it first";
+
+// Most memory differences listed by a comparison; the total is still reported
+pub const MAX_COMPARE_DIFFERENCES: usize = 256;
+
+#[derive(Debug, Serialize)]
+pub struct RegisterDifference {
+    pub register: &'static str,
+    pub left: u64,
+    pub right: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryDifference {
+    pub address: u16,
+    pub left: u8,
+    pub right: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmulatorComparison {
+    pub registers: Vec<RegisterDifference>,
+    pub memory: Vec<MemoryDifference>,
+    pub memory_differences: usize,
+    pub truncated: bool,
+}
+
+// State carried between the chunks of a run
+#[derive(Debug, Default)]
+pub struct RunProgress {
+    pub executed: u32,
+    pub memory_reads: u64,
+    pub memory_writes: u64,
+    // Clock cycles the CPU has run since the start of the run
+    pub cpu_cycles: u64,
+    unchanged_steps: u32,
+    stop_reason: Option<StopReason>,
 }
 
+// Instructions executed per lock acquisition when running from a handler
+pub const EXECUTE_CHUNK_STEPS: u32 = 10_000;
+
+// How long a forced delete waits for a cancelled run to stop before
+// removing the emulator anyway, and how often it checks
+pub const DELETE_CANCEL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const DELETE_CANCEL_POLL: std::time::Duration = std::time::Duration::from_millis(10);
+
 #[derive(Debug, Serialize)]
 pub struct ExecutionResult {
     pub steps_executed: u32,
+    pub memory_reads: u64, // Includes instruction fetches
+    pub memory_writes: u64,
     pub halted: bool,
+    pub stop_reason: StopReason,
     pub final_state: CpuState,
+    // Only present when the run was asked for a memory diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_diff: Option<Vec<MemoryChange>>,
+}
+
+// An address written during a run and the last value written to it
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MemoryChange {
+    pub address: u16,
+    pub value: u8,
 }
 
 #[derive(Debug, Serialize)]
@@ -111,17 +610,105 @@ pub struct Emulator {
     pub cycles: u64,
     pub instance: EmulatorInstance,
     pub last_cycle_time: std::time::Instant,
+    // Stop a run once PC and registers stay unchanged for this many
+    // consecutive steps (e.g. `loop: JMP loop`). Off by default.
+    pub loop_detection: Option<u32>,
+    // Recent instruction history; None when tracing is off
+    pub trace: Option<TraceBuffer>,
+    // Execution count per address; None unless enabled
+    pub pc_histogram: Option<PcHistogram>,
+    // API key the emulator was created with, for per-key limits
+    pub api_key_id: Option<String>,
+    // Set by a cancel request; a run in progress stops at its next chunk
+    pub cancel_requested: bool,
+    // State and instruction count at the start of the latest run, kept for
+    // auto-recovery
+    pub checkpoint: Option<(StateBlob, u64)>,
+    // True from the start of a run until it finishes or its handler is
+    // dropped
+    pub running: bool,
+    // Woken whenever the CPU halts, for clients waiting on it
+    pub halt_notify: Arc<tokio::sync::Notify>,
+    // Checked before each instruction of a run except the first, so a run
+    // started at a breakpoint gets past it
+    pub breakpoints: Vec<Breakpoint>,
+    // Reported in the state until a reset, so the error outlives the
+    // request that hit it
+    pub last_error: Option<LastError>,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Entry point for new emulators, matching the basic-6502 template
+pub const DEFAULT_RESET_VECTOR: u16 = 0x8000;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateEmulatorQuery {
+    pub reset_vector: Option<u16>,
+    // Fill RAM from this seed instead of zeros
+    pub memory_seed: Option<u64>,
+    // Comma-separated labels for organising emulators, e.g. "experiment-a,nightly"
+    pub tags: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListEmulatorsQuery {
+    // Only list emulators carrying this tag
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmulatorSummary {
+    pub id: String,
+    pub tags: Vec<String>,
+    pub cpu: CpuState,
+}
+
+// Split a comma-separated tag list, dropping blanks and duplicates
+fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !parsed.iter().any(|t| t == tag) {
+            parsed.push(tag.to_string());
+        }
+    }
+    parsed
 }
 
 impl Emulator {
     pub fn new_with_instance(instance: EmulatorInstance) -> Self {
-        Self {
+        let mut emulator = Self {
             cpu: CPU::new(),
-            memory: Memory::new(),
+            // Smaller tiers get a smaller backing store, mirrored across the
+            // address space
+            memory: Memory::with_size(instance.specs.memory_size as usize),
             cycles: 0,
             instance,
             last_cycle_time: std::time::Instant::now(),
-        }
+            loop_detection: None,
+            trace: None,
+            pc_histogram: None,
+            api_key_id: None,
+            cancel_requested: false,
+            checkpoint: None,
+            running: false,
+            halt_notify: Arc::new(tokio::sync::Notify::new()),
+            breakpoints: Vec::new(),
+            last_error: None,
+        };
+        // Start at a real entry point rather than running from $0000
+        emulator.set_reset_vector(DEFAULT_RESET_VECTOR);
+        emulator
+    }
+    
+    // Point the reset vector at `address` and reset
+    pub fn set_reset_vector(&mut self, address: u16) {
+        self.memory.write_u16(RESET_VECTOR, address);
+        self.reset();
     }
     
     pub fn new() -> Self {
@@ -137,126 +724,869 @@ impl Emulator {
     }
     
     pub fn get_state(&self) -> CpuState {
+        let (a, x, y, pc, sp, status) = (
+            self.cpu.get_register_a(),
+            self.cpu.get_register_x(),
+            self.cpu.get_register_y(),
+            self.cpu.get_pc(),
+            self.cpu.get_sp(),
+            self.cpu.get_status(),
+        );
         CpuState {
-            a: self.cpu.get_register_a(),
-            x: self.cpu.get_register_x(),
-            y: self.cpu.get_register_y(),
-            pc: self.cpu.get_pc(),
-            sp: self.cpu.get_sp(),
-            status: self.cpu.get_status(),
+            a,
+            x,
+            y,
+            pc,
+            sp,
+            status,
             cycles: self.cycles,
             halted: self.cpu.is_halted(),
+            variant: self.cpu.variant,
+            next_instruction: self.next_instruction(),
+            hex: RegisterHex {
+                a: format!("${:02X}", a),
+                x: format!("${:02X}", x),
+                y: format!("${:02X}", y),
+                pc: format!("${:04X}", pc),
+                sp: format!("${:02X}", sp),
+                status: format!("${:02X}", status),
+            },
+            flags: StatusFlags::from_status(status),
+            cpu_cycles: self.cpu.cycles,
+            // BRK is the only instruction that halts the CPU
+            halt_reason: self.cpu.is_halted().then(|| "Executed BRK".to_string()),
+            last_error: self.last_error.clone(),
+        }
+    }
+    
+    // Disassembly of the instruction at pc; unknown opcodes come out as .byte
+    pub fn next_instruction(&self) -> DisassembledInstruction {
+        let fetch = |address: u16| Some(self.memory.peek(address));
+        disassemble_at(fetch, self.cpu.get_pc(), self.cpu.variant)
+            .expect("memory always yields an opcode")
+    }
+    
+    // Address the next instruction will read or write, from the current
+    // index registers and memory
+    pub fn next_effective_address(&self) -> Option<u16> {
+        let fetch = |address: u16| self.memory.peek(address);
+        effective_address(fetch, self.cpu.get_pc(), self.cpu.get_register_x(), self.cpu.get_register_y(), self.cpu.variant)
+    }
+    
+    // Reset as if powered off and on: RAM is zeroed, ROM kept, and the
+    // template's reset vector, initial memory and ROM segments written again
+    // before the CPU resets. Without a template the reset vector in effect
+    // is kept.
+    pub fn cold_reset(&mut self, template: Option<&InstanceTemplate>) {
+        let vector = u16::from_le_bytes([self.memory.peek(RESET_VECTOR), self.memory.peek(RESET_VECTOR.wrapping_add(1))]);
+        self.memory.clear_ram();
+        match template {
+            Some(template) => template.load_contents(&mut self.memory),
+            None => self.memory.write_u16(RESET_VECTOR, vector),
         }
+        self.reset();
     }
     
     pub fn reset(&mut self) {
         self.cpu.reset(&mut self.memory);
         self.cycles = 0;
+        self.last_error = None;
+        if let Some(histogram) = self.pc_histogram.as_mut() {
+            histogram.clear();
+        }
     }
     
-    pub fn step(&mut self) -> bool {
+    // Execute one instruction. Returns Ok(false) if the CPU is halted.
+    pub fn step(&mut self) -> Result<bool, CpuError> {
         if !self.cpu.is_halted() {
-            self.cpu.step(&mut self.memory);
+            let pc = self.cpu.get_pc();
+            let result = if self.trace.is_some() {
+                self.traced_step()
+            } else {
+                self.cpu.try_step(&mut self.memory)
+            };
+            if let Err(e) = result {
+                self.last_error = Some(LastError::from(&e));
+                return Err(e);
+            }
+            if let Some(histogram) = self.pc_histogram.as_mut() {
+                histogram.record(pc);
+            }
             self.cycles += 1;
-            true
+            self.instance.record_instruction();
+            // A program that halts has finished, so the instance has too
+            if self.cpu.is_halted() {
+                if matches!(self.instance.state, InstanceState::Running) {
+                    self.instance.stop();
+                }
+                self.halt_notify.notify_waiters();
+            }
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
     
-    pub fn execute_steps(&mut self, steps: u32) -> ExecutionResult {
-        let mut executed = 0;
-        
-        for _ in 0..steps {
-            if !self.step() {
-                break;
+    // With auto-recovery on, roll back to the checkpoint after an error.
+    // Returns whether that happened.
+    pub fn recover(&mut self) -> bool {
+        match &self.checkpoint {
+            Some((checkpoint, instructions)) if self.instance.auto_recover => {
+                checkpoint.restore(&mut self.cpu, &mut self.memory);
+                self.cycles = *instructions;
+                true
             }
-            executed += 1;
+            _ => false,
         }
-        
-        ExecutionResult {
-            steps_executed: executed,
-            halted: self.cpu.is_halted(),
-            final_state: self.get_state(),
+    }
+    
+    // Take an IRQ now, leaving the CPU on the first instruction of the
+    // handler. The interrupt sequence counts as one step. Returns false,
+    // changing nothing, if interrupts are disabled.
+    pub fn step_into_irq(&mut self) -> bool {
+        if !self.cpu.irq(&mut self.memory) {
+            return false;
         }
+        self.cycles += 1;
+        true
     }
     
-    pub fn load_program(&mut self, address: u16, data: &[u8]) {
-        for (i, &byte) in data.iter().enumerate() {
-            self.memory.write(address + i as u16, byte);
+    fn traced_step(&mut self) -> Result<(), CpuError> {
+        let pc = self.cpu.pc;
+        let opcode = self.memory.peek(pc);
+        let status_before = self.cpu.status;
+        
+        self.cpu.try_step(&mut self.memory)?;
+        
+        let entry = TraceEntry {
+            step: self.cycles,
+            pc,
+            opcode,
+            instruction: get_instruction_name(opcode),
+            a: self.cpu.a,
+            x: self.cpu.x,
+            y: self.cpu.y,
+            sp: self.cpu.sp,
+            status_before,
+            status_after: self.cpu.status,
+            flags_changed: flag_names(status_before ^ self.cpu.status),
+        };
+        if let Some(trace) = self.trace.as_mut() {
+            trace.record(entry);
         }
+        Ok(())
     }
     
-    pub fn read_memory(&self, address: u16, length: u16) -> Vec<u8> {
-        (0..length).map(|i| self.memory.read(address + i)).collect()
+    // Run with a fresh trace covering exactly these steps. Any trace the
+    // emulator already had is put back afterwards.
+    pub fn trace_run(&mut self, steps: u32) -> TraceRunResult {
+        let previous = self.trace.replace(TraceBuffer::new(steps as usize));
+        let execution = self.execute_steps(steps);
+        let trace = std::mem::replace(&mut self.trace, previous)
+            .map(|t| t.entries())
+            .unwrap_or_default();
+        TraceRunResult { execution, trace }
     }
     
-    pub fn write_memory(&mut self, address: u16, value: u8) {
-        self.memory.write(address, value);
+    pub fn execute_steps(&mut self, steps: u32) -> ExecutionResult {
+        let mut progress = RunProgress::default();
+        self.run_chunk(&mut progress, steps);
+        self.finish_run(progress)
     }
     
-    pub fn get_id(&self) -> String {
-        // For metrics, we'll pass this from the server context
-        "unknown".to_string()
+    // Execute up to `steps` more instructions of a run. Returns true if the
+    // run stopped early (halt or suspected infinite loop).
+    pub fn run_chunk(&mut self, progress: &mut RunProgress, steps: u32) -> bool {
+        // A cancel only applies to the run it was sent during
+        if progress.executed == 0 {
+            self.cancel_requested = false;
+            self.running = true;
+            self.last_cycle_time = std::time::Instant::now();
+            if self.instance.auto_recover {
+                self.checkpoint = Some((StateBlob::capture(&self.cpu, &self.memory), self.cycles));
+            }
+        } else if std::mem::take(&mut self.cancel_requested) {
+            progress.stop_reason = Some(StopReason::Cancelled);
+            return true;
+        }
+        
+        let (reads, writes) = self.memory.access_counts();
+        let cpu_cycles = self.cpu.cycles;
+        let stopped = self.run_chunk_steps(progress, steps);
+        let (reads_after, writes_after) = self.memory.access_counts();
+        progress.memory_reads += reads_after - reads;
+        progress.memory_writes += writes_after - writes;
+        progress.cpu_cycles += self.cpu.cycles.wrapping_sub(cpu_cycles);
+        stopped
     }
     
-    pub fn get_memory_dump(&self) -> Vec<u8> {
-        // Return full 64KB memory dump for snapshots
-        (0..=65535).map(|addr| self.memory.read(addr)).collect()
+    // How long to wait before the next chunk so the run keeps to the
+    // instance's cycles per second. The run should have taken as long as
+    // its cycles at that rate, measured from when it started
+    // (`last_cycle_time`); only what is left of that is slept, so time spent
+    // executing counts towards it. A run already behind doesn't wait.
+    pub fn pacing_delay(&self, progress: &RunProgress) -> std::time::Duration {
+        let rate = self.instance.specs.max_cycles_per_second;
+        if rate == 0 {
+            return std::time::Duration::ZERO;
+        }
+        let nanos = progress.cpu_cycles as u128 * 1_000_000_000 / rate as u128;
+        let target = std::time::Duration::from_nanos(nanos.min(u64::MAX as u128) as u64);
+        target.saturating_sub(self.last_cycle_time.elapsed())
     }
-}
-
-type EmulatorMap = Arc<Mutex<HashMap<String, Emulator>>>;
-
-pub async fn run_server() {
-    // Initialize Prometheus metrics
-    init_metrics();
     
-    // Initialize stores
+    fn run_chunk_steps(&mut self, progress: &mut RunProgress, steps: u32) -> bool {
+        for _ in 0..steps {
+            if self.instance.budget_exhausted() {
+                progress.stop_reason = Some(StopReason::BudgetExhausted);
+                return true;
+            }
+            
+            if progress.executed > 0 {
+                if let Some(breakpoint) = self.breakpoint_at_pc() {
+                    progress.stop_reason = Some(StopReason::Breakpoint { pc: self.cpu.get_pc(), breakpoint });
+                    return true;
+                }
+            }
+            
+            let before = self.register_state();
+            match self.step() {
+                Ok(true) => {}
+                Ok(false) => return true,
+                Err(e) => {
+                    progress.stop_reason = Some(if self.recover() {
+                        StopReason::Recovered { error: e.to_string() }
+                    } else {
+                        StopReason::CpuError { message: e.to_string() }
+                    });
+                    return true;
+                }
+            }
+            progress.executed += 1;
+            
+            if let Some(threshold) = self.loop_detection {
+                if self.register_state() == before {
+                    progress.unchanged_steps += 1;
+                    if progress.unchanged_steps >= threshold {
+                        progress.stop_reason = Some(StopReason::InfiniteLoop { pc: self.cpu.get_pc() });
+                        return true;
+                    }
+                } else {
+                    progress.unchanged_steps = 0;
+                }
+            }
+        }
+        
+        self.cpu.is_halted()
+    }
+    
+    // The first breakpoint the instruction at PC hits, decoding it only
+    // when there are breakpoints to check
+    fn breakpoint_at_pc(&self) -> Option<Breakpoint> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+        let pc = self.cpu.get_pc();
+        let opcode = self.memory.peek(pc);
+        let instruction = decode(|address| Some(self.memory.peek(address)), pc, self.cpu.variant);
+        let mnemonic = instruction.as_ref().map(|instruction| instruction.mnemonic);
+        self.breakpoints.iter().find(|b| b.matches(pc, opcode, mnemonic)).cloned()
+    }
+    
+    pub fn finish_run(&mut self, progress: RunProgress) -> ExecutionResult {
+        self.running = false;
+        let stop_reason = if self.cpu.is_halted() {
+            StopReason::Halted
+        } else {
+            progress.stop_reason.unwrap_or(StopReason::StepLimit)
+        };
+        
+        ExecutionResult {
+            steps_executed: progress.executed,
+            memory_reads: progress.memory_reads,
+            memory_writes: progress.memory_writes,
+            halted: self.cpu.is_halted(),
+            stop_reason,
+            final_state: self.get_state(),
+            memory_diff: self.memory.take_write_log().map(|log| {
+                log.into_iter().map(|(address, value)| MemoryChange { address, value }).collect()
+            }),
+        }
+    }
+    
+    // Everything an instruction can change apart from memory
+    fn register_state(&self) -> (u16, u8, u8, u8, u8, u8) {
+        (self.cpu.pc, self.cpu.a, self.cpu.x, self.cpu.y, self.cpu.sp, self.cpu.status)
+    }
+    
+    // Callers check that the data fits below $10000; anything past it wraps
+    pub fn load_program(&mut self, address: u16, data: &[u8]) {
+        for (i, &byte) in data.iter().enumerate() {
+            self.memory.write(address.wrapping_add(i as u16), byte);
+        }
+    }
+    
+    pub fn load_example(&mut self, program: &ExampleProgram) {
+        self.load_program(program.load_address, program.bytes);
+        self.set_reset_vector(program.load_address);
+    }
+    
+    // Reads stop at $FFFF rather than wrapping, so fewer than `length` bytes
+    // may come back
+    pub fn read_memory(&self, address: u16, length: u16) -> Vec<u8> {
+        let end = (address as u32 + length as u32).min(0x10000);
+        (address as u32..end).map(|addr| self.memory.peek(addr as u16)).collect()
+    }
+    
+    // Copy length bytes from src to dest through the bus, so ROM and I/O
+    // behave as for CPU accesses. The source is read in full before
+    // anything is written, so overlapping ranges copy like memmove.
+    pub fn copy_memory(&mut self, src: u16, dest: u16, length: u32) -> Result<(), String> {
+        range_end(src, length)?;
+        range_end(dest, length)?;
+        let bytes: Vec<u8> = (0..length).map(|i| self.memory.read(src.wrapping_add(i as u16))).collect();
+        for (i, byte) in bytes.into_iter().enumerate() {
+            self.memory.write(dest.wrapping_add(i as u16), byte);
+        }
+        Ok(())
+    }
+    
+    // SHA-256 of address..address + length, read without side effects
+    pub fn memory_checksum(&self, address: u16, length: u32) -> Result<String, String> {
+        let end = range_end(address, length)?;
+        let bytes: Vec<u8> = (address as u32..end).map(|addr| self.memory.peek(addr as u16)).collect();
+        Ok(hash_memory(&bytes))
+    }
+    
+    // Distinct opcodes in a linear disassembly of address..address + length,
+    // in opcode order, and whether this CPU can execute each of them
+    pub fn opcodes_used(&self, address: u16, length: u32) -> Result<Vec<OpcodeUsage>, String> {
+        let end = range_end(address, length)?;
+        let fetch = |addr: u16| ((addr as u32) < end && addr >= address).then(|| self.memory.peek(addr));
+        let mut usage: BTreeMap<u8, OpcodeUsage> = BTreeMap::new();
+        let mut pc = address as u32;
+        
+        while pc < end {
+            let opcode = self.memory.peek(pc as u16);
+            // Unknown opcodes, and operands cut off by the end of the range,
+            // are a single byte of data
+            let (mnemonic, length) = match decode(fetch, pc as u16, self.cpu.variant) {
+                Some(instruction) => (instruction.mnemonic, instruction.length),
+                None => (".byte", 1),
+            };
+            usage
+                .entry(opcode)
+                .or_insert_with(|| OpcodeUsage {
+                    opcode,
+                    mnemonic: mnemonic.to_string(),
+                    count: 0,
+                    implemented: self.cpu.is_implemented(opcode),
+                })
+                .count += 1;
+            pc += length as u32;
+        }
+        
+        // Most used first; ties go by opcode so the order is stable
+        let mut usage: Vec<OpcodeUsage> = usage.into_values().collect();
+        usage.sort_by(|a, b| b.count.cmp(&a.count).then(a.opcode.cmp(&b.opcode)));
+        Ok(usage)
+    }
+    
+    // Walk the code from the reset vector without running it, following
+    // straight-line flow until JMP, RTS, RTI or BRK, an unimplemented
+    // opcode, or `max_bytes` of instructions. Branches and JSR are not
+    // followed, but their targets are checked. Only writes to fixed
+    // (zero page or absolute) addresses are checked against the code.
+    pub fn validate_program(&self, max_bytes: u32) -> ValidationReport {
+        let entry_point = u16::from_le_bytes([self.memory.peek(RESET_VECTOR), self.memory.peek(RESET_VECTOR.wrapping_add(1))]);
+        let fetch = |addr: u16| Some(self.memory.peek(addr));
+        let mut issues = Vec::new();
+        let mut code = std::collections::BTreeSet::new();
+        let mut writes = Vec::new();
+        let (mut pc, mut instructions_checked, mut bytes_checked) = (entry_point, 0, 0);
+        
+        while bytes_checked < max_bytes {
+            let opcode = self.memory.peek(pc);
+            let decoded = decode(fetch, pc, self.cpu.variant);
+            let length = decoded.as_ref().map_or(1, |instruction| instruction.length);
+            instructions_checked += 1;
+            bytes_checked += length as u32;
+            for i in 0..length as u16 {
+                code.insert(pc.wrapping_add(i));
+            }
+            
+            let instruction = match decoded {
+                Some(instruction) if self.cpu.is_implemented(opcode) => instruction,
+                decoded => {
+                    let text = decoded.map_or_else(|| format!(".byte ${:02X}", opcode), |instruction| instruction.text());
+                    issues.push(ValidationIssue {
+                        address: pc,
+                        kind: ValidationIssueKind::UnimplementedOpcode,
+                        message: format!("{} (${:02X}) is not implemented", text, opcode),
+                    });
+                    break;
+                }
+            };
+            
+            if let Some(target) = instruction.branch_target() {
+                let target_opcode = self.memory.peek(target);
+                if !self.cpu.is_implemented(target_opcode) {
+                    issues.push(ValidationIssue {
+                        address: pc,
+                        kind: ValidationIssueKind::BadBranchTarget,
+                        message: format!("Branch target ${:04X} holds ${:02X}, which is not an implemented opcode", target, target_opcode),
+                    });
+                }
+            }
+            
+            let fixed_address = matches!(instruction.mode, AddressingMode::ZeroPage | AddressingMode::Absolute);
+            let writes_memory = matches!(
+                instruction.mnemonic,
+                "STA" | "STX" | "STY" | "STZ" | "INC" | "DEC" | "ASL" | "LSR" | "ROL" | "ROR" | "TSB" | "TRB"
+            );
+            if fixed_address && writes_memory {
+                let target = instruction.effective_address(|addr| self.memory.peek(addr), 0, 0)
+                    .expect("stores have a memory operand");
+                writes.push((pc, instruction.text(), target));
+            }
+            
+            if matches!(instruction.mnemonic, "JMP" | "RTS" | "RTI" | "BRK") {
+                break;
+            }
+            pc = pc.wrapping_add(instruction.length as u16);
+        }
+        
+        // Checked last, so a write to code further along is caught too
+        for (address, text, target) in writes {
+            if code.contains(&target) {
+                issues.push(ValidationIssue {
+                    address,
+                    kind: ValidationIssueKind::WriteToCode,
+                    message: format!("{} writes to code at ${:04X}", text, target),
+                });
+            }
+        }
+        issues.sort_by_key(|issue| issue.address);
+        
+        ValidationReport {
+            entry_point,
+            instructions_checked,
+            bytes_checked,
+            valid: issues.is_empty(),
+            issues,
+        }
+    }
+    
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        self.memory.write(address, value);
+    }
+    
+    // Basic-flow emulators are owned by "system" and shared by all users
+    pub fn can_user_access(&self, user_id: &str) -> bool {
+        self.instance.owner_id == user_id || self.instance.owner_id == "system"
+    }
+    
+    pub fn export_state(&self) -> Vec<u8> {
+        StateBlob::capture(&self.cpu, &self.memory).to_bytes()
+    }
+    
+    // Replace CPU and memory with an exported state. Nothing is changed if
+    // the blob fails its integrity check.
+    pub fn import_state(&mut self, blob: &[u8]) -> Result<(), String> {
+        let state = StateBlob::from_bytes(blob)?;
+        state.restore(&mut self.cpu, &mut self.memory);
+        // The blob carries clock cycles but not how many instructions ran
+        self.cycles = 0;
+        Ok(())
+    }
+    
+    pub fn restore_snapshot(
+        &mut self,
+        snapshot: &EmulatorSnapshot,
+        store: &HashMap<String, EmulatorSnapshot>,
+        mode: RestoreMode,
+    ) -> Result<(), String> {
+        snapshot.restore_from_store(store, &mut self.cpu, &mut self.memory, mode)?;
+        // The instruction count goes with the registers it produced
+        if mode.registers {
+            self.cycles = snapshot.metadata.instruction_count;
+        }
+        Ok(())
+    }
+    
+    // Register and memory differences between two emulators, listing at
+    // most `limit` memory addresses
+    pub fn compare(&self, other: &Emulator, limit: usize) -> EmulatorComparison {
+        let (left, right) = (self.get_state(), other.get_state());
+        let registers = [
+            ("a", left.a as u64, right.a as u64),
+            ("x", left.x as u64, right.x as u64),
+            ("y", left.y as u64, right.y as u64),
+            ("pc", left.pc as u64, right.pc as u64),
+            ("sp", left.sp as u64, right.sp as u64),
+            ("status", left.status as u64, right.status as u64),
+            ("cycles", left.cycles, right.cycles),
+            ("halted", left.halted as u64, right.halted as u64),
+        ]
+        .into_iter()
+        .filter(|(_, l, r)| l != r)
+        .map(|(register, left, right)| RegisterDifference { register, left, right })
+        .collect();
+        
+        let mut memory = Vec::new();
+        let mut memory_differences = 0;
+        for address in 0..=0xFFFF {
+            let (l, r) = (self.memory.peek(address), other.memory.peek(address));
+            if l != r {
+                memory_differences += 1;
+                if memory.len() < limit {
+                    memory.push(MemoryDifference { address, left: l, right: r });
+                }
+            }
+        }
+        
+        EmulatorComparison {
+            registers,
+            truncated: memory_differences > memory.len(),
+            memory,
+            memory_differences,
+        }
+    }
+    
+    pub fn get_id(&self) -> String {
+        // For metrics, we'll pass this from the server context
+        "unknown".to_string()
+    }
+    
+    pub fn get_memory_dump(&self) -> Vec<u8> {
+        // Return full 64KB memory dump for snapshots
+        (0..=65535).map(|addr| self.memory.peek(addr)).collect()
+    }
+}
+
+type EmulatorMap = Arc<Mutex<HashMap<String, Emulator>>>;
+
+// One permit per run-once in flight, sized from max_emulators so throwaway
+// emulators are capped like stored ones
+type RunOnceSlots = Arc<tokio::sync::Semaphore>;
+
+// Lock the emulator map. If a request panicked while holding the lock, the
+// map is taken over as that request left it and the poison cleared, rather
+// than every later request panicking too.
+fn lock_emulators(emulators: &EmulatorMap) -> MutexGuard<'_, HashMap<String, Emulator>> {
+    emulators.lock().unwrap_or_else(|poisoned| {
+        eprintln!("Recovering the emulator map after a panic while it was locked");
+        emulators.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+// End (exclusive) of a non-empty range that fits in the address space
+fn range_end(address: u16, length: u32) -> Result<u32, String> {
+    let end = address as u32 + length;
+    if length == 0 || end > 0x10000 {
+        return Err(format!("Range of {} bytes at ${:04X} is empty or extends past the end of memory", length, address));
+    }
+    Ok(end)
+}
+
+pub const DEFAULT_MAX_EMULATORS: usize = 1000;
+pub const DEFAULT_KEY_EXPIRY_WARNING_DAYS: i64 = 7;
+pub const DEFAULT_MAX_MEMORY_READ: u16 = 4096;
+
+// Server-wide settings, read from the environment at startup
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    // EC65_MAX_EMULATORS: total live emulators across all users
+    pub max_emulators: usize,
+    // EC65_KEY_EXPIRY_WARNING_DAYS: send X-Key-Expires-In-Days on requests
+    // made with an API key this close to expiry
+    pub key_expiry_warning_days: i64,
+    // EC65_MAX_MEMORY_READ: most bytes returned by one memory read
+    pub max_memory_read: u16,
+    // EC65_USER_STORE: JSON file to keep accounts in across restarts;
+    // unset keeps them in memory only
+    pub user_store: Option<String>,
+    // EC65_JWT_TTL_HOURS: lifetime of login tokens; must be positive
+    pub jwt_ttl_hours: i64,
+    // EC65_DEV_MODE: relaxed settings for local development; currently
+    // lets any origin make cross-origin requests when none are configured
+    pub dev_mode: bool,
+    // EC65_CORS_ORIGINS: comma-separated origins allowed to make
+    // cross-origin requests, e.g. `https://app.example.com`; `*` allows any
+    pub cors_origins: CorsOrigins,
+}
+
+// Origins browsers may call the API from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorsOrigins {
+    // Any origin, without credentials
+    Any,
+    // Only these, with credentials; empty allows no cross-origin requests
+    List(Vec<String>),
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_emulators: DEFAULT_MAX_EMULATORS,
+            key_expiry_warning_days: DEFAULT_KEY_EXPIRY_WARNING_DAYS,
+            max_memory_read: DEFAULT_MAX_MEMORY_READ,
+            user_store: None,
+            jwt_ttl_hours: DEFAULT_JWT_TTL_HOURS,
+            dev_mode: false,
+            cors_origins: CorsOrigins::List(Vec::new()),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let dev_mode = env_or("EC65_DEV_MODE", defaults.dev_mode);
+        Self {
+            max_emulators: env_or("EC65_MAX_EMULATORS", defaults.max_emulators),
+            key_expiry_warning_days: env_or("EC65_KEY_EXPIRY_WARNING_DAYS", defaults.key_expiry_warning_days),
+            max_memory_read: env_or("EC65_MAX_MEMORY_READ", defaults.max_memory_read),
+            user_store: std::env::var("EC65_USER_STORE").ok(),
+            jwt_ttl_hours: match env_or("EC65_JWT_TTL_HOURS", defaults.jwt_ttl_hours) {
+                hours if hours > 0 => hours,
+                hours => {
+                    eprintln!("Ignoring non-positive EC65_JWT_TTL_HOURS={}", hours);
+                    defaults.jwt_ttl_hours
+                }
+            },
+            dev_mode,
+            cors_origins: match std::env::var("EC65_CORS_ORIGINS") {
+                Ok(value) => parse_cors_origins(&value),
+                Err(_) if dev_mode => CorsOrigins::Any,
+                Err(_) => defaults.cors_origins,
+            },
+        }
+    }
+}
+
+// Parse a comma-separated origin list. A `*` entry allows any origin;
+// entries that aren't `scheme://host[:port]` are skipped with a warning, as
+// are trailing slashes.
+pub fn parse_cors_origins(value: &str) -> CorsOrigins {
+    let mut origins = Vec::new();
+    for origin in value.split(',').map(str::trim).filter(|origin| !origin.is_empty()) {
+        if origin == "*" {
+            return CorsOrigins::Any;
+        }
+        let origin = origin.trim_end_matches('/');
+        let valid = origin.split_once("://").is_some_and(|(scheme, authority)| {
+            !scheme.is_empty()
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                && !authority.contains('/')
+                && authority.parse::<warp::http::uri::Authority>().is_ok()
+        });
+        if valid {
+            origins.push(origin.to_string());
+        } else {
+            eprintln!("Ignoring invalid origin {:?} in EC65_CORS_ORIGINS", origin);
+        }
+    }
+    CorsOrigins::List(origins)
+}
+
+// Browsers only send credentials cross-origin to a named origin, never to
+// `*`, so credentials are allowed exactly when the origins are listed
+fn cors_filter(origins: &CorsOrigins) -> warp::cors::Builder {
+    let cors = warp::cors()
+        .allow_headers(vec!["content-type", "authorization"])
+        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
+    match origins {
+        CorsOrigins::Any => cors.allow_any_origin(),
+        CorsOrigins::List(origins) => cors.allow_origins(origins.iter().map(String::as_str)).allow_credentials(true),
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            eprintln!("Ignoring invalid {}={}", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+// Error body for requests rejected before reaching a handler
+#[derive(Debug, Serialize)]
+pub struct RejectionResponse {
+    pub success: bool,
+    pub error: String,
+    pub code: &'static str,
+}
+
+// Turn a body that isn't valid JSON, or doesn't fit the endpoint's request
+// type, into a 400 explaining why. Other rejections pass through untouched.
+async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
+    match err.find::<warp::filters::body::BodyDeserializeError>() {
+        Some(e) => {
+            let response = RejectionResponse {
+                success: false,
+                error: format!("Invalid request body: {}", e),
+                code: "InvalidBody",
+            };
+            Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::BAD_REQUEST))
+        }
+        None => Err(err),
+    }
+}
+
+// Add an X-Key-Expires-In-Days header to responses for requests made with
+// an API key that is about to expire, so clients can rotate it in time
+fn warn_expiring_keys<F, R>(
+    routes: F,
+    users: UserStore,
+    warning_days: i64,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    R: warp::Reply,
+{
+    with_key_expiry(users, warning_days)
+        .and(routes)
+        .map(|days: Option<i64>, reply: R| {
+            let mut response = reply.into_response();
+            if let Some(days) = days {
+                response.headers_mut().insert("x-key-expires-in-days", days.into());
+            }
+            response
+        })
+}
+
+// Check whether another emulator may be created, returning the status and
+// message to reject with if not
+fn check_emulator_capacity(
+    emulators: &HashMap<String, Emulator>,
+    config: &ServerConfig,
+    api_key: Option<&ApiKey>,
+) -> Result<(), (warp::http::StatusCode, String)> {
+    if emulators.len() >= config.max_emulators {
+        return Err((
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+            format!("Platform at capacity ({} emulators)", config.max_emulators),
+        ));
+    }
+    
+    if let Some(key) = api_key {
+        let in_use = emulators
+            .values()
+            .filter(|e| e.api_key_id.as_deref() == Some(key.id.as_str()))
+            .count();
+        if in_use >= key.rate_limit.max_concurrent_emulators as usize {
+            return Err((
+                warp::http::StatusCode::TOO_MANY_REQUESTS,
+                format!("API key limit of {} concurrent emulators reached", key.rate_limit.max_concurrent_emulators),
+            ));
+        }
+    }
+    
+    Ok(())
+}
+
+pub async fn run_server() {
+    // Initialize Prometheus metrics
+    init_metrics();
+    
+    let config = Arc::new(ServerConfig::from_env());
+    
+    // Initialize stores
     let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
-    let users: UserStore = Arc::new(Mutex::new(HashMap::new()));
+    let run_once_slots: RunOnceSlots = Arc::new(tokio::sync::Semaphore::new(config.max_emulators));
+    let users = match &config.user_store {
+        Some(path) => UserStore::open(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => UserStore::default(),
+    };
     let snapshots: SnapshotStore = Arc::new(Mutex::new(HashMap::new()));
     let templates: Arc<Mutex<HashMap<String, InstanceTemplate>>> = 
         Arc::new(Mutex::new(HashMap::new()));
     
-    // Initialize default users and templates
-    init_default_users(users.clone());
+    // Initialize default users and templates. A persistent store only gets
+    // the default users the first time.
+    if users.lock().unwrap().is_empty() {
+        init_default_users(users.clone());
+        save_users(&users, &users.lock().unwrap());
+    }
     init_default_templates(templates.clone());
     
     println!("=== 6502 Cloud Computing Platform ===");
     println!("Enterprise-grade 6502 emulation service starting...");
     
-    // CORS
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_headers(vec!["content-type", "authorization"])
-        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
+    let cors = cors_filter(&config.cors_origins);
     
     // Create new emulator instance
     let create_emulator = warp::path("emulator")
         .and(warp::path::end())
         .and(warp::post())
+        .and(warp::query::<CreateEmulatorQuery>())
+        .and(with_api_key(users.clone()))
+        .and(with_config(config.clone()))
         .and(with_emulators(emulators.clone()))
         .and_then(create_emulator_handler);
     
     // Get emulator state
     let get_state = warp::path!("emulator" / String)
         .and(warp::get())
+        .and(warp::header::optional::<String>("accept"))
         .and(with_emulators(emulators.clone()))
         .and_then(get_state_handler);
     
     // Reset emulator
     let reset_emulator = warp::path!("emulator" / String / "reset")
         .and(warp::post())
+        .and(warp::query::<ResetQuery>())
         .and(with_emulators(emulators.clone()))
+        .and(with_templates(templates.clone()))
         .and_then(reset_handler);
     
     // Step execution
     let step_emulator = warp::path!("emulator" / String / "step")
         .and(warp::post())
+        .and(warp::header::optional::<String>("accept"))
         .and(with_emulators(emulators.clone()))
         .and_then(step_handler);
     
+    // Switch between NMOS and CMOS
+    let set_variant = warp::path!("emulator" / String / "variant")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(set_variant_handler);
+    
+    // Clear the halted state so execution can continue past a BRK
+    let resume = warp::path!("emulator" / String / "resume")
+        .and(warp::post())
+        .and(warp::query::<ResumeQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(resume_handler);
+    
+    // Wait for the CPU to halt
+    let wait_halt = warp::path!("emulator" / String / "wait-halt")
+        .and(warp::get())
+        .and(warp::query::<WaitHaltQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(wait_halt_handler);
+    
+    // Cancel a run in progress
+    let cancel_run = warp::path!("emulator" / String / "cancel")
+        .and(warp::post())
+        .and(with_emulators(emulators.clone()))
+        .and_then(cancel_handler);
+    
+    // Inject an IRQ and stop at the handler
+    let step_into_irq = warp::path!("emulator" / String / "step-into-irq")
+        .and(warp::post())
+        .and(with_emulators(emulators.clone()))
+        .and_then(step_into_irq_handler);
+    
     // Execute multiple steps
     let execute_steps = warp::path!("emulator" / String / "execute")
         .and(warp::post())
@@ -264,6 +1594,13 @@ pub async fn run_server() {
         .and(with_emulators(emulators.clone()))
         .and_then(execute_handler);
     
+    // Execute, streaming progress as JSON lines
+    let execute_stream = warp::path!("emulator" / String / "execute-stream")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(execute_stream_handler);
+    
     // Load program
     let load_program = warp::path!("emulator" / String / "program")
         .and(warp::post())
@@ -271,54 +1608,231 @@ pub async fn run_server() {
         .and(with_emulators(emulators.clone()))
         .and_then(load_program_handler);
     
+    // Load a program and run it
+    let load_and_run = warp::path!("emulator" / String / "load-and-run")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(load_and_run_handler);
+    
+    // Run a program on an ephemeral emulator that isn't stored
+    let run_once = warp::path("run-once")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_config(config.clone()))
+        .and(with_run_once_slots(run_once_slots))
+        .and_then(run_once_handler);
+    
+    // Load a built-in example program
+    let load_example = warp::path!("emulator" / String / "load-example")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(load_example_handler);
+    
+    // List built-in example programs
+    let list_programs = warp::path("programs")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(list_programs_handler);
+    
+    // Instance types and their specs
+    let instance_types = warp::path("instance-types")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(instance_types_handler);
+    
+    // Describe a single opcode
+    let describe_opcode_route = warp::path!("opcodes" / String)
+        .and(warp::get())
+        .and(warp::query::<DescribeOpcodeQuery>())
+        .and_then(describe_opcode_handler);
+    
+    // Disassemble a byte buffer without an emulator
+    let disasm = warp::path("disasm")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(disasm_handler);
+    
     // Read memory
     let read_memory = warp::path!("emulator" / String / "memory")
         .and(warp::get())
         .and(warp::query::<MemoryRead>())
+        .and(with_config(config.clone()))
         .and(with_emulators(emulators.clone()))
         .and_then(read_memory_handler);
     
-    // Write memory
-    let write_memory = warp::path!("emulator" / String / "memory")
+    // Read one 256-byte page
+    let read_page = warp::path!("emulator" / String / "page" / u8)
+        .and(warp::get())
+        .and(with_emulators(emulators.clone()))
+        .and_then(read_page_handler);
+    
+    // Configure instruction tracing
+    let configure_trace = warp::path!("emulator" / String / "trace")
         .and(warp::post())
         .and(warp::body::json())
         .and(with_emulators(emulators.clone()))
-        .and_then(write_memory_handler);
+        .and_then(configure_trace_handler);
     
-    // List emulators
-    let list_emulators = warp::path("emulators")
-        .and(warp::path::end())
+    // Read the instruction trace
+    let get_trace = warp::path!("emulator" / String / "trace")
         .and(warp::get())
         .and(with_emulators(emulators.clone()))
-        .and_then(list_emulators_handler);
+        .and_then(get_trace_handler);
     
-    // Delete emulator
-    let delete_emulator = warp::path!("emulator" / String)
-        .and(warp::delete())
+    // Replace the breakpoints
+    let set_breakpoints = warp::path!("emulator" / String / "breakpoints")
+        .and(warp::post())
+        .and(warp::body::json())
         .and(with_emulators(emulators.clone()))
-        .and_then(delete_emulator_handler);
+        .and_then(set_breakpoints_handler);
     
-    // Metrics endpoint
-    let metrics = warp::path("metrics")
-        .and(warp::path::end())
+    // List the breakpoints
+    let get_breakpoints = warp::path!("emulator" / String / "breakpoints")
         .and(warp::get())
-        .and_then(metrics_handler);
-    
-    // === ENTERPRISE AUTHENTICATION ENDPOINTS ===
+        .and(with_emulators(emulators.clone()))
+        .and_then(get_breakpoints_handler);
     
-    // Login endpoint
-    let login = warp::path!("auth" / "login")
+    // Configure the per-address execution counts
+    let configure_pc_histogram = warp::path!("emulator" / String / "pc-histogram")
         .and(warp::post())
         .and(warp::body::json())
-        .and(with_users(users.clone()))
-        .and_then(login_handler);
+        .and(with_emulators(emulators.clone()))
+        .and_then(configure_pc_histogram_handler);
     
-    // Register endpoint
-    let register = warp::path!("auth" / "register")
+    // Read the per-address execution counts
+    let get_pc_histogram = warp::path!("emulator" / String / "pc-histogram")
+        .and(warp::get())
+        .and(with_emulators(emulators.clone()))
+        .and_then(get_pc_histogram_handler);
+    
+    // Run with tracing and return the trace
+    let trace_run = warp::path!("emulator" / String / "trace-run")
         .and(warp::post())
         .and(warp::body::json())
-        .and(with_users(users.clone()))
-        .and_then(register_handler);
+        .and(with_emulators(emulators.clone()))
+        .and_then(trace_run_handler);
+    
+    // Compare two emulators
+    let compare_emulators = warp::path!("emulator" / String / "compare" / String)
+        .and(warp::get())
+        .and(with_auth(users.clone()))
+        .and(with_emulators(emulators.clone()))
+        .and_then(compare_emulators_handler);
+    
+    // Memory checksum
+    let memory_checksum = warp::path!("emulator" / String / "memory" / "checksum")
+        .and(warp::get())
+        .and(warp::query::<MemoryChecksumQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(memory_checksum_handler);
+    
+    // Copy a memory range
+    let copy_memory = warp::path!("emulator" / String / "memory" / "copy")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(copy_memory_handler);
+    
+    // Opcodes used in a memory range
+    let opcodes_used = warp::path!("emulator" / String / "opcodes-used")
+        .and(warp::get())
+        .and(warp::query::<OpcodesUsedQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(opcodes_used_handler);
+    
+    // Zero page and stack
+    let low_pages = warp::path!("emulator" / String / "pages" / "low")
+        .and(warp::get())
+        .and(with_emulators(emulators.clone()))
+        .and_then(low_pages_handler);
+    
+    // Static check of the code at the reset vector
+    let validate = warp::path!("emulator" / String / "validate")
+        .and(warp::post())
+        .and(warp::query::<ValidateQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(validate_handler);
+    
+    // Address the next instruction will access
+    let next_effective_address = warp::path!("emulator" / String / "next-effective-address")
+        .and(warp::get())
+        .and(with_emulators(emulators.clone()))
+        .and_then(next_effective_address_handler);
+    
+    // Memory map
+    let memory_map = warp::path!("emulator" / String / "memory" / "map")
+        .and(warp::get())
+        .and(with_emulators(emulators.clone()))
+        .and_then(memory_map_handler);
+    
+    // Attach an interval timer
+    let attach_timer = warp::path!("emulator" / String / "timer")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(attach_timer_handler);
+    
+    // Export state
+    let export_state = warp::path!("emulator" / String / "export")
+        .and(warp::get())
+        .and(with_emulators(emulators.clone()))
+        .and_then(export_state_handler);
+    
+    // Import state
+    let import_state = warp::path!("emulator" / String / "import")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(import_state_handler);
+    
+    // Write memory
+    let write_memory = warp::path!("emulator" / String / "memory")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_emulators(emulators.clone()))
+        .and_then(write_memory_handler);
+    
+    // List emulators
+    let list_emulators = warp::path("emulators")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ListEmulatorsQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(list_emulators_handler);
+    
+    // Delete emulator
+    let delete_emulator = warp::path!("emulator" / String)
+        .and(warp::delete())
+        .and(warp::query::<DeleteEmulatorQuery>())
+        .and(with_emulators(emulators.clone()))
+        .and_then(delete_emulator_handler);
+    
+    // Metrics endpoint
+    let metrics = warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(metrics_handler);
+    
+    // === ENTERPRISE AUTHENTICATION ENDPOINTS ===
+    
+    // Login endpoint
+    let login = warp::path!("auth" / "login")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_users(users.clone()))
+        .and(with_config(config.clone()))
+        .and_then(login_handler);
+    
+    // Register endpoint
+    let register = warp::path!("auth" / "register")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_users(users.clone()))
+        .and_then(register_handler);
     
     // Get current user info endpoint
     let user_info = warp::path!("auth" / "me")
@@ -326,6 +1840,27 @@ pub async fn run_server() {
         .and(with_auth(users.clone()))
         .and_then(user_info_handler);
     
+    // === ADMIN ENDPOINTS ===
+    
+    // List all users
+    let list_users = warp::path!("admin" / "users")
+        .and(warp::get())
+        .and(with_auth(users.clone()))
+        .and(with_users(users.clone()))
+        .and(with_emulators(emulators.clone()))
+        .and(with_snapshots(snapshots.clone()))
+        .and_then(list_users_handler);
+    
+    // Delete a user along with their emulators and snapshots
+    let delete_user = warp::path!("admin" / "users" / String)
+        .and(warp::delete())
+        .and(warp::query::<DeleteUserQuery>())
+        .and(with_auth(users.clone()))
+        .and(with_users(users.clone()))
+        .and(with_emulators(emulators.clone()))
+        .and(with_snapshots(snapshots.clone()))
+        .and_then(delete_user_handler);
+    
     // === ENTERPRISE API KEY ENDPOINTS ===
     
     // Create API key
@@ -357,6 +1892,8 @@ pub async fn run_server() {
         .and(warp::post())
         .and(warp::body::json())
         .and(with_auth(users.clone()))
+        .and(with_api_key(users.clone()))
+        .and(with_config(config.clone()))
         .and(with_emulators(emulators.clone()))
         .and(with_templates(templates.clone()))
         .and_then(create_instance_handler);
@@ -397,6 +1934,13 @@ pub async fn run_server() {
         .and(with_emulators(emulators.clone()))
         .and_then(pause_instance_handler);
     
+    // Reset instance usage stats, refilling its instruction budget
+    let reset_usage = warp::path!("instances" / String / "usage" / "reset")
+        .and(warp::post())
+        .and(with_auth(users.clone()))
+        .and(with_emulators(emulators.clone()))
+        .and_then(reset_usage_handler);
+    
     // === ENTERPRISE SNAPSHOT ENDPOINTS ===
     
     // Create snapshot
@@ -443,9 +1987,41 @@ pub async fn run_server() {
         .or(get_state)
         .or(reset_emulator)
         .or(step_emulator)
+        .or(step_into_irq)
+        .or(resume)
+        .or(cancel_run)
+        .or(wait_halt)
+        .or(set_variant)
         .or(execute_steps)
+        .or(execute_stream)
         .or(load_program)
+        .or(load_and_run)
+        .or(run_once)
+        .or(load_example)
+        .or(list_programs)
+        .or(instance_types)
+        .or(describe_opcode_route)
+        .or(disasm)
         .or(read_memory)
+        .or(read_page)
+        .or(memory_map)
+        .or(attach_timer)
+        .or(low_pages)
+        .or(next_effective_address)
+        .or(memory_checksum)
+        .or(copy_memory)
+        .or(opcodes_used)
+        .or(validate)
+        .or(export_state)
+        .or(import_state)
+        .or(compare_emulators)
+        .or(configure_trace)
+        .or(get_trace)
+        .or(trace_run)
+        .or(configure_pc_histogram)
+        .or(get_pc_histogram)
+        .or(set_breakpoints)
+        .or(get_breakpoints)
         .or(write_memory)
         .or(list_emulators)
         .or(delete_emulator)
@@ -455,6 +2031,9 @@ pub async fn run_server() {
         .or(register)
         .or(user_info);
         
+    let admin_routes = list_users
+        .or(delete_user);
+        
     let api_key_routes = create_api_key
         .or(list_api_keys)
         .or(delete_api_key);
@@ -464,7 +2043,8 @@ pub async fn run_server() {
         .or(get_instance)
         .or(start_instance)
         .or(stop_instance)
-        .or(pause_instance);
+        .or(pause_instance)
+        .or(reset_usage);
         
     let snapshot_routes = create_snapshot
         .or(list_snapshots)
@@ -474,9 +2054,12 @@ pub async fn run_server() {
     
     let routes = basic_routes
         .or(auth_routes)
+        .or(admin_routes)
         .or(api_key_routes)
         .or(instance_routes)
         .or(snapshot_routes)
+        .recover(handle_rejection);
+    let routes = warn_expiring_keys(routes, users.clone(), config.key_expiry_warning_days)
         .with(cors);
     
     println!("6502 Emulator Server starting on http://localhost:3030");
@@ -484,13 +2067,45 @@ pub async fn run_server() {
     println!("=== BASIC API ENDPOINTS ===");
     println!("  POST   /emulator              - Create new emulator instance");
     println!("  GET    /emulator/:id          - Get emulator state");
-    println!("  POST   /emulator/:id/reset    - Reset emulator");
+    println!("  POST   /emulator/:id/reset    - Reset emulator (?cold=true also clears RAM)");
     println!("  POST   /emulator/:id/step     - Execute single step");
+    println!("  POST   /emulator/:id/step-into-irq - Take an IRQ and stop at the handler");
+    println!("  POST   /emulator/:id/resume   - Clear a halt, optionally moving PC");
+    println!("  POST   /emulator/:id/cancel   - Stop a run in progress");
+    println!("  GET    /emulator/:id/wait-halt - Wait until the CPU halts or a timeout passes");
+    println!("  POST   /emulator/:id/variant  - Switch between NMOS 6502 and CMOS 65C02");
     println!("  POST   /emulator/:id/execute  - Execute multiple steps");
+    println!("  POST   /emulator/:id/execute-stream - Execute, streaming progress as JSON lines");
     println!("  POST   /emulator/:id/program  - Load program");
+    println!("  POST   /emulator/:id/load-and-run - Load a program and run it");
+    println!("  POST   /run-once              - Run a program on a throwaway emulator");
+    println!("  POST   /emulator/:id/load-example - Load a built-in example program");
+    println!("  GET    /programs              - List built-in example programs");
+    println!("  GET    /instance-types        - List instance types and their specs");
+    println!("  GET    /opcodes/:byte         - Describe one opcode (hex byte)");
+    println!("  POST   /disasm                - Disassemble raw bytes");
     println!("  GET    /emulator/:id/memory   - Read memory");
+    println!("  GET    /emulator/:id/page/:n  - Read the 256 bytes of page n");
     println!("  POST   /emulator/:id/memory   - Write memory");
-    println!("  GET    /emulators             - List all emulator instances");
+    println!("  GET    /emulator/:id/memory/map - List read-only and I/O regions");
+    println!("  POST   /emulator/:id/timer    - Map an IRQ-raising interval timer at an address");
+    println!("  GET    /emulator/:id/pages/low - Zero page and stack page with SP");
+    println!("  GET    /emulator/:id/next-effective-address - Address the next instruction will access");
+    println!("  GET    /emulator/:id/memory/checksum - SHA-256 of a memory range");
+    println!("  POST   /emulator/:id/memory/copy - Copy a memory range, overlaps included");
+    println!("  GET    /emulator/:id/opcodes-used - Opcodes in a range and whether they're implemented");
+    println!("  POST   /emulator/:id/validate - Statically check the code at the reset vector");
+    println!("  GET    /emulator/:id/export   - Export CPU and memory as a checksummed blob");
+    println!("  POST   /emulator/:id/import   - Import an exported state blob");
+    println!("  POST   /emulator/:id/trace    - Enable or disable instruction tracing");
+    println!("  GET    /emulator/:id/compare/:other - Compare registers and memory of two emulators");
+    println!("  GET    /emulator/:id/trace    - Read the instruction trace");
+    println!("  POST   /emulator/:id/trace-run - Run steps and return their trace");
+    println!("  POST   /emulator/:id/pc-histogram - Enable or disable PC execution counts");
+    println!("  GET    /emulator/:id/pc-histogram - Execution counts per address, hottest first");
+    println!("  POST   /emulator/:id/breakpoints - Replace the address, opcode and mnemonic breakpoints");
+    println!("  GET    /emulator/:id/breakpoints - List the breakpoints");
+    println!("  GET    /emulators             - List emulators (?tag= filters)");
     println!("  DELETE /emulator/:id          - Delete emulator instance");
     println!("  GET    /metrics               - Prometheus metrics endpoint");
     println!();
@@ -499,6 +2114,10 @@ pub async fn run_server() {
     println!("  POST   /auth/register         - Register new user");
     println!("  GET    /auth/me               - Get current user info");
     println!();
+    println!("=== ADMIN ===");
+    println!("  GET    /admin/users           - List all users (admin only)");
+    println!("  DELETE /admin/users/:id       - Delete a user with their emulators and snapshots (admin only)");
+    println!();
     println!("=== ENTERPRISE API KEYS ===");
     println!("  POST   /api-keys              - Create API key");
     println!("  GET    /api-keys              - List user's API keys");
@@ -511,6 +2130,7 @@ pub async fn run_server() {
     println!("  POST   /instances/:id/start   - Start instance");
     println!("  POST   /instances/:id/stop    - Stop instance");
     println!("  POST   /instances/:id/pause   - Pause instance");
+    println!("  POST   /instances/:id/usage/reset - Reset usage stats and instruction budget");
     println!();
     println!("=== ENTERPRISE SNAPSHOTS ===");
     println!("  POST   /emulator/:id/snapshots - Create snapshot");
@@ -530,6 +2150,14 @@ fn with_emulators(emulators: EmulatorMap) -> impl Filter<Extract = (EmulatorMap,
     warp::any().map(move || emulators.clone())
 }
 
+fn with_config(config: Arc<ServerConfig>) -> impl Filter<Extract = (Arc<ServerConfig>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || config.clone())
+}
+
+fn with_run_once_slots(slots: RunOnceSlots) -> impl Filter<Extract = (RunOnceSlots,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || slots.clone())
+}
+
 fn with_users(users: UserStore) -> impl Filter<Extract = (UserStore,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || users.clone())
 }
@@ -542,14 +2170,34 @@ fn with_templates(templates: Arc<Mutex<HashMap<String, InstanceTemplate>>>) -> i
     warp::any().map(move || templates.clone())
 }
 
-async fn create_emulator_handler(emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+async fn create_emulator_handler(
+    query: CreateEmulatorQuery,
+    api_key: Option<ApiKey>,
+    config: Arc<ServerConfig>,
+    emulators: EmulatorMap,
+) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
     let id = Uuid::new_v4().to_string();
-    let emulator = Emulator::new();
+    let mut emulator = Emulator::new();
+    emulator.api_key_id = api_key.as_ref().map(|k| k.id.clone());
+    if let Some(seed) = query.memory_seed {
+        emulator.memory = Memory::new_seeded(seed);
+    }
+    if query.memory_seed.is_some() || query.reset_vector.is_some() {
+        emulator.set_reset_vector(query.reset_vector.unwrap_or(DEFAULT_RESET_VECTOR));
+    }
+    if let Some(tags) = &query.tags {
+        emulator.instance.tags = parse_tags(tags);
+    }
     let state = emulator.get_state();
     
     {
-        let mut emulators_lock = emulators.lock().unwrap();
+        let mut emulators_lock = lock_emulators(&emulators);
+        if let Err((status, message)) = check_emulator_capacity(&emulators_lock, &config, api_key.as_ref()) {
+            let response: ApiResponse<EmulatorState> = ApiResponse::error(message);
+            record_api_request("POST", "/emulator", status.as_u16(), timer.elapsed());
+            return Ok(warp::reply::with_status(warp::reply::json(&response), status));
+        }
         emulators_lock.insert(id.clone(), emulator);
         set_active_emulators(emulators_lock.len());
     }
@@ -563,825 +2211,4062 @@ async fn create_emulator_handler(emulators: EmulatorMap) -> Result<impl warp::Re
     });
     
     record_api_request("POST", "/emulator", 200, timer.elapsed());
-    Ok(warp::reply::json(&response))
+    Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK))
 }
 
-async fn get_state_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let emulators_lock = emulators.lock().unwrap();
+async fn get_state_handler(id: String, accept: Option<String>, emulators: EmulatorMap) -> Result<warp::reply::Response, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    match emulators_lock.get(&id) {
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) if wants_binary_state(accept.as_deref()) => {
+            record_api_request("GET", "/emulator/:id", 200, timer.elapsed());
+            return Ok(binary_state_reply(&emulator.get_state()));
+        }
         Some(emulator) => {
             let response = ApiResponse::success(EmulatorState {
                 id: id.clone(),
                 cpu: emulator.get_state(),
             });
-            Ok(warp::reply::json(&response))
+            (response, warp::http::StatusCode::OK)
         }
-        None => {
-            let response: ApiResponse<EmulatorState> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("GET", "/emulator/:id", status.as_u16(), timer.elapsed());
+    Ok(warp::Reply::into_response(warp::reply::with_status(warp::reply::json(&response), status)))
+}
+
+async fn reset_handler(
+    id: String,
+    query: ResetQuery,
+    emulators: EmulatorMap,
+    templates: Arc<Mutex<HashMap<String, InstanceTemplate>>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    // Templates before emulators, the order instance creation takes them in
+    let templates_lock = query.cold.then(|| templates.lock().unwrap());
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            if let Some(templates_lock) = &templates_lock {
+                let template = emulator.instance.template_id.as_ref().and_then(|t| templates_lock.get(t));
+                emulator.cold_reset(template);
+            } else {
+                emulator.reset();
+            }
+            let response = ApiResponse::success(EmulatorState {
+                id: id.clone(),
+                cpu: emulator.get_state(),
+            });
+            (response, warp::http::StatusCode::OK)
         }
-    }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/reset", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn reset_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut emulators_lock = emulators.lock().unwrap();
+async fn set_variant_handler(id: String, request: SetVariantRequest, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    match emulators_lock.get_mut(&id) {
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) if emulator.running => {
+            let response: ApiResponse<EmulatorState> = ApiResponse::error("Cannot change the CPU variant while a run is in progress".to_string());
+            (response, warp::http::StatusCode::CONFLICT)
+        }
         Some(emulator) => {
-            emulator.reset();
+            emulator.cpu.set_variant(request.variant);
             let response = ApiResponse::success(EmulatorState {
                 id: id.clone(),
                 cpu: emulator.get_state(),
             });
-            Ok(warp::reply::json(&response))
+            (response, warp::http::StatusCode::OK)
         }
-        None => {
-            let response: ApiResponse<EmulatorState> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/variant", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn resume_handler(id: String, query: ResumeQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            if let Some(pc) = query.pc {
+                emulator.cpu.set_pc(pc);
+            }
+            emulator.cpu.resume();
+            let response = ApiResponse::success(EmulatorState {
+                id: id.clone(),
+                cpu: emulator.get_state(),
+            });
+            (response, warp::http::StatusCode::OK)
         }
-    }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/resume", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn wait_halt_handler(id: String, query: WaitHaltQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let timeout = std::time::Duration::from_millis(query.timeout_ms.unwrap_or(DEFAULT_WAIT_HALT_MS).min(MAX_WAIT_HALT_MS));
+    let deadline = tokio::time::Instant::now() + timeout;
+    
+    let (response, status) = loop {
+        let notify = match lock_emulators(&emulators).get(&id) {
+            Some(emulator) => emulator.halt_notify.clone(),
+            None => break (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+        };
+        // Register before looking at the CPU, so a halt in between isn't missed
+        let notified = notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        
+        let halted = match lock_emulators(&emulators).get(&id) {
+            Some(emulator) if emulator.cpu.is_halted() => Some(emulator.get_state()),
+            Some(_) => None,
+            None => break (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+        };
+        if let Some(cpu) = halted {
+            break (ApiResponse::success(WaitHaltResult { halted: true, cpu }), warp::http::StatusCode::OK);
+        }
+        
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            let response = match lock_emulators(&emulators).get(&id) {
+                Some(emulator) => ApiResponse::success(WaitHaltResult {
+                    halted: emulator.cpu.is_halted(),
+                    cpu: emulator.get_state(),
+                }),
+                None => break (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+            };
+            break (response, warp::http::StatusCode::OK);
+        }
+    };
+    
+    record_api_request("GET", "/emulator/:id/wait-halt", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn step_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+async fn cancel_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
-    let mut emulators_lock = emulators.lock().unwrap();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let result = match emulators_lock.get_mut(&id) {
+    let (response, status) = match emulators_lock.get_mut(&id) {
         Some(emulator) => {
-            emulator.step();
+            emulator.cancel_requested = true;
+            (ApiResponse::success("Cancel requested".to_string()), warp::http::StatusCode::OK)
+        }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/cancel", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn step_handler(id: String, accept: Option<String>, emulators: EmulatorMap) -> Result<warp::reply::Response, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) if emulator.instance.budget_exhausted() => {
+            let response: ApiResponse<EmulatorState> = ApiResponse::error(BUDGET_EXHAUSTED.to_string());
+            (response, warp::http::StatusCode::PAYMENT_REQUIRED)
+        }
+        Some(emulator) => {
+            if let Err(e) = emulator.step() {
+                let message = if emulator.recover() {
+                    StopReason::Recovered { error: e.to_string() }.to_string()
+                } else {
+                    e.to_string()
+                };
+                let response: ApiResponse<EmulatorState> = ApiResponse::error(message);
+                record_api_request("POST", "/emulator/:id/step", 422, timer.elapsed());
+                return Ok(warp::Reply::into_response(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::UNPROCESSABLE_ENTITY)));
+            }
             let state = emulator.get_state();
             
             // Update CPU metrics
             update_cpu_registers(&id, state.a, state.x, state.y, state.pc, state.sp, state.status);
             
+            if wants_binary_state(accept.as_deref()) {
+                record_api_request("POST", "/emulator/:id/step", 200, timer.elapsed());
+                return Ok(binary_state_reply(&state));
+            }
             let response = ApiResponse::success(EmulatorState {
                 id: id.clone(),
                 cpu: state,
             });
-            Ok(warp::reply::json(&response))
-        }
-        None => {
-            let response: ApiResponse<EmulatorState> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+            (response, warp::http::StatusCode::OK)
         }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
     };
     
-    record_api_request("POST", "/emulator/:id/step", 200, timer.elapsed());
-    result
+    record_api_request("POST", "/emulator/:id/step", status.as_u16(), timer.elapsed());
+    Ok(warp::Reply::into_response(warp::reply::with_status(warp::reply::json(&response), status)))
 }
 
-async fn execute_handler(id: String, request: ExecuteSteps, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut emulators_lock = emulators.lock().unwrap();
+async fn step_into_irq_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    match emulators_lock.get_mut(&id) {
+    let (response, status) = match emulators_lock.get_mut(&id) {
         Some(emulator) => {
-            let result = emulator.execute_steps(request.steps);
-            let response = ApiResponse::success(result);
-            Ok(warp::reply::json(&response))
-        }
-        None => {
-            let response: ApiResponse<ExecutionResult> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+            if emulator.step_into_irq() {
+                let response = ApiResponse::success(EmulatorState {
+                    id: id.clone(),
+                    cpu: emulator.get_state(),
+                });
+                (response, warp::http::StatusCode::OK)
+            } else {
+                // The CPU state rules it out, not the request
+                (
+                    ApiResponse::error("IRQ is masked (interrupt disable flag set)".to_string()),
+                    warp::http::StatusCode::CONFLICT,
+                )
+            }
         }
-    }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/step-into-irq", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn load_program_handler(id: String, request: ProgramLoad, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut emulators_lock = emulators.lock().unwrap();
+// Run an emulator in chunks, releasing the lock and yielding to the runtime
+// between chunks so a long run doesn't block other requests. Returns None if
+// the emulator doesn't exist (or is deleted mid-run).
+pub async fn execute_chunked(emulators: &EmulatorMap, id: &str, steps: u32) -> Option<ExecutionResult> {
+    let _guard = RunGuard { emulators, id };
+    let mut progress = RunProgress::default();
     
-    match emulators_lock.get_mut(&id) {
-        Some(emulator) => {
-            emulator.load_program(request.address, &request.data);
-            let response = ApiResponse::success(format!("Loaded {} bytes at address ${:04X}", request.data.len(), request.address));
-            Ok(warp::reply::json(&response))
-        }
-        None => {
-            let response: ApiResponse<String> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+    loop {
+        let delay = {
+            let mut emulators_lock = lock_emulators(emulators);
+            let emulator = emulators_lock.get_mut(id)?;
+            let chunk = (steps - progress.executed).min(EXECUTE_CHUNK_STEPS);
+            let stopped = emulator.run_chunk(&mut progress, chunk);
+            if stopped || progress.executed >= steps {
+                return Some(emulator.finish_run(progress));
+            }
+            emulator.pacing_delay(&progress)
+        };
+        pace(delay).await;
+    }
+}
+
+// Clears `running` however a run ends. warp drops a handler's future when
+// its client disconnects, which would otherwise leave the flag set and the
+// emulator undeletable without force. Must be declared before any lock
+// guard so it drops after the lock is released.
+struct RunGuard<'a> {
+    emulators: &'a EmulatorMap,
+    id: &'a str,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(emulator) = lock_emulators(self.emulators).get_mut(self.id) {
+            emulator.running = false;
         }
     }
 }
 
-async fn read_memory_handler(id: String, query: MemoryRead, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let emulators_lock = emulators.lock().unwrap();
-    
-    match emulators_lock.get(&id) {
-        Some(emulator) => {
-            let length = query.length.unwrap_or(1);
-            let data = emulator.read_memory(query.address, length);
-            let response = ApiResponse::success(MemoryData {
-                address: query.address,
-                data,
-            });
-            Ok(warp::reply::json(&response))
+// Wait out a pacing delay, or just yield when the run isn't ahead
+async fn pace(delay: std::time::Duration) {
+    if delay.is_zero() {
+        tokio::task::yield_now().await;
+    } else {
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn execute_handler(id: String, request: ExecuteSteps, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    if let Some(emulator) = lock_emulators(&emulators).get_mut(&id) {
+        if emulator.instance.budget_exhausted() {
+            let response: ApiResponse<ExecutionResult> = ApiResponse::error(BUDGET_EXHAUSTED.to_string());
+            record_api_request("POST", "/emulator/:id/execute", 402, timer.elapsed());
+            return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::PAYMENT_REQUIRED));
         }
-        None => {
-            let response: ApiResponse<MemoryData> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+        if emulator.running {
+            let response: ApiResponse<ExecutionResult> = ApiResponse::error(RUN_IN_PROGRESS.to_string());
+            record_api_request("POST", "/emulator/:id/execute", 409, timer.elapsed());
+            return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::CONFLICT));
+        }
+        // Claimed under the same lock as the check so two requests can't
+        // both start
+        emulator.running = true;
+        if let Some(threshold) = request.loop_threshold {
+            emulator.loop_detection = if threshold == 0 { None } else { Some(threshold) };
+        }
+        if request.memory_diff {
+            emulator.memory.start_write_log();
         }
     }
+    
+    let (response, status) = match execute_chunked(&emulators, &id, request.steps).await {
+        Some(result) => (ApiResponse::success(result), warp::http::StatusCode::OK),
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/execute", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn write_memory_handler(id: String, request: MemoryWrite, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut emulators_lock = emulators.lock().unwrap();
+// Run like execute_chunked, sending a progress frame every `report_every`
+// instructions. Sending waits for the client to keep up, and a dropped
+// connection ends the run.
+async fn stream_execution(emulators: EmulatorMap, id: String, request: ExecuteStream, mut sender: warp::hyper::body::Sender) {
+    let _guard = RunGuard { emulators: &emulators, id: &id };
+    let mut progress = RunProgress::default();
+    let mut next_report = request.report_every.min(request.steps);
+    let mut closed = false;
     
-    match emulators_lock.get_mut(&id) {
-        Some(emulator) => {
-            emulator.write_memory(request.address, request.value);
-            let response = ApiResponse::success(format!("Wrote ${:02X} to address ${:04X}", request.value, request.address));
-            Ok(warp::reply::json(&response))
-        }
-        None => {
-            let response: ApiResponse<String> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+    loop {
+        let (frame, delay) = {
+            let mut emulators_lock = lock_emulators(&emulators);
+            let emulator = match emulators_lock.get_mut(&id) {
+                Some(emulator) => emulator,
+                None => return,
+            };
+            if closed {
+                // Nobody is listening any more, so the run ends here
+                return;
+            }
+            let chunk = (next_report - progress.executed).min(EXECUTE_CHUNK_STEPS);
+            let stopped = emulator.run_chunk(&mut progress, chunk);
+            let delay = emulator.pacing_delay(&progress);
+            let frame = if stopped || progress.executed >= request.steps {
+                Some(StreamFrame::Done(emulator.finish_run(std::mem::take(&mut progress))))
+            } else if progress.executed == next_report {
+                next_report = next_report.saturating_add(request.report_every).min(request.steps);
+                Some(StreamFrame::Progress {
+                    steps_executed: progress.executed,
+                    cpu: emulator.get_state(),
+                })
+            } else {
+                None
+            };
+            (frame, delay)
+        };
+        let frame = match frame {
+            Some(frame) => frame,
+            None => {
+                pace(delay).await;
+                continue;
+            }
+        };
+        
+        let done = matches!(frame, StreamFrame::Done(_));
+        let mut line = serde_json::to_vec(&frame).unwrap();
+        line.push(b'\n');
+        if done {
+            let _ = sender.send_data(line.into()).await;
+            return;
         }
+        closed = sender.send_data(line.into()).await.is_err();
+        pace(delay).await;
     }
 }
 
-async fn list_emulators_handler(emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
-    let emulators_lock = emulators.lock().unwrap();
+async fn execute_stream_handler(id: String, request: ExecuteStream, emulators: EmulatorMap) -> Result<warp::reply::Response, warp::Rejection> {
+    let timer = Timer::new();
+    let rejection = {
+        let mut emulators_lock = lock_emulators(&emulators);
+        match emulators_lock.get_mut(&id) {
+            None => Some((EMULATOR_NOT_FOUND.to_string(), warp::http::StatusCode::NOT_FOUND)),
+            Some(_) if request.report_every == 0 => Some(("report_every must be at least 1".to_string(), warp::http::StatusCode::BAD_REQUEST)),
+            Some(emulator) if emulator.instance.budget_exhausted() => Some((BUDGET_EXHAUSTED.to_string(), warp::http::StatusCode::PAYMENT_REQUIRED)),
+            Some(emulator) if emulator.running => Some((RUN_IN_PROGRESS.to_string(), warp::http::StatusCode::CONFLICT)),
+            Some(emulator) => {
+                emulator.running = true;
+                None
+            }
+        }
+    };
+    if let Some((message, status)) = rejection {
+        let response: ApiResponse<ExecutionResult> = ApiResponse::error(message);
+        record_api_request("POST", "/emulator/:id/execute-stream", status.as_u16(), timer.elapsed());
+        return Ok(warp::Reply::into_response(warp::reply::with_status(warp::reply::json(&response), status)));
+    }
     
-    let emulator_list: Vec<EmulatorState> = emulators_lock
-        .iter()
-        .map(|(id, emulator)| EmulatorState {
-            id: id.clone(),
-            cpu: emulator.get_state(),
-        })
-        .collect();
+    let (sender, body) = warp::hyper::Body::channel();
+    tokio::spawn(stream_execution(emulators, id, request, sender));
     
-    let response = ApiResponse::success(emulator_list);
-    Ok(warp::reply::json(&response))
+    record_api_request("POST", "/emulator/:id/execute-stream", 200, timer.elapsed());
+    let mut response = warp::reply::Response::new(body);
+    response.headers_mut().insert("content-type", warp::http::HeaderValue::from_static("application/x-ndjson"));
+    Ok(response)
 }
 
-async fn delete_emulator_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+async fn load_program_handler(id: String, request: ProgramLoad, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
-    let mut emulators_lock = emulators.lock().unwrap();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let result = match emulators_lock.remove(&id) {
-        Some(_) => {
-            set_active_emulators(emulators_lock.len());
-            let response = ApiResponse::success(format!("Emulator {} deleted", id));
-            Ok(warp::reply::json(&response))
-        }
-        None => {
-            let response: ApiResponse<String> = ApiResponse::error("Emulator not found".to_string());
-            Ok(warp::reply::json(&response))
+    // An empty load is almost certainly a client bug, so say so rather than
+    // reporting success for writing nothing
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(_) if request.data.is_empty() => (
+            ApiResponse::error("No program data".to_string()),
+            warp::http::StatusCode::BAD_REQUEST,
+        ),
+        Some(_) if request.address as usize + request.data.len() > 0x10000 => (
+            ApiResponse::error(format!(
+                "Program of {} bytes at ${:04X} extends past the end of memory",
+                request.data.len(),
+                request.address
+            )),
+            warp::http::StatusCode::BAD_REQUEST,
+        ),
+        Some(emulator) => {
+            emulator.load_program(request.address, &request.data);
+            let mut message = format!("Loaded {} bytes at address ${:04X}", request.data.len(), request.address);
+            if let Some(entry_point) = request.entry_point {
+                emulator.set_reset_vector(entry_point);
+                message.push_str(&format!(", reset to entry point ${:04X}", entry_point));
+            }
+            (ApiResponse::success(message), warp::http::StatusCode::OK)
         }
+        None => (
+            ApiResponse::<String>::error(EMULATOR_NOT_FOUND.to_string()),
+            warp::http::StatusCode::NOT_FOUND,
+        ),
     };
     
-    record_api_request("DELETE", "/emulator/:id", 200, timer.elapsed());
-    result
+    record_api_request("POST", "/emulator/:id/program", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn metrics_handler() -> Result<impl warp::Reply, warp::Rejection> {
+async fn load_and_run_handler(id: String, request: LoadAndRun, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
-    let encoder = prometheus::TextEncoder::new();
-    let metric_families = REGISTRY.gather();
     
-    match encoder.encode_to_string(&metric_families) {
-        Ok(metrics_text) => {
-            record_api_request("GET", "/metrics", 200, timer.elapsed());
-            Ok(warp::reply::with_header(
-                metrics_text,
-                "content-type",
-                "text/plain; version=0.0.4",
-            ))
+    if let Err(e) = request.validate() {
+        let response: ApiResponse<ExecutionResult> = ApiResponse::error(e);
+        record_api_request("POST", "/emulator/:id/load-and-run", 400, timer.elapsed());
+        return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::BAD_REQUEST));
+    }
+    
+    let loaded = match lock_emulators(&emulators).get_mut(&id) {
+        Some(emulator) if emulator.running => {
+            // Loading over a running program would corrupt it too, so
+            // refuse before touching memory
+            let response: ApiResponse<ExecutionResult> = ApiResponse::error(RUN_IN_PROGRESS.to_string());
+            record_api_request("POST", "/emulator/:id/load-and-run", 409, timer.elapsed());
+            return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::CONFLICT));
         }
-        Err(_) => {
-            record_api_request("GET", "/metrics", 500, timer.elapsed());
-            Ok(warp::reply::with_header(
-                "Error encoding metrics".to_string(),
-                "content-type",
-                "text/plain",
-            ))
+        Some(emulator) => {
+            emulator.running = true;
+            emulator.load_program(request.address, &request.data);
+            if request.set_reset_vector {
+                emulator.set_reset_vector(request.address);
+            } else {
+                emulator.cpu.pc = request.address;
+                emulator.cpu.resume();
+            }
+            if request.memory_diff {
+                emulator.memory.start_write_log();
+            }
+            true
         }
-    }
-}
-
-fn init_default_templates(templates: Arc<Mutex<HashMap<String, InstanceTemplate>>>) {
-    let mut templates_lock = templates.lock().unwrap();
-    let default_templates = InstanceTemplate::create_basic_templates();
+        None => false,
+    };
     
-    for template in default_templates {
-        templates_lock.insert(template.id.clone(), template);
-    }
+    let result = if loaded {
+        execute_chunked(&emulators, &id, request.max_steps).await
+    } else {
+        None
+    };
     
-    println!("Initialized {} default instance templates", templates_lock.len());
+    let (response, status) = match result {
+        Some(result) => (ApiResponse::success(result), warp::http::StatusCode::OK),
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/load-and-run", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-// ========== ENTERPRISE AUTHENTICATION HANDLERS ==========
-
-async fn login_handler(
-    request: LoginRequest, 
-    users: UserStore
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn run_once_handler(request: RunOnce, config: Arc<ServerConfig>, slots: RunOnceSlots) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
     
-    let users_lock = users.lock().unwrap();
-    if let Some(user) = users_lock.values().find(|u| u.username == request.username) {
-        if bcrypt::verify(&request.password, &user.password_hash).unwrap_or(false) {
-            match create_jwt_token(user) {
-                Ok(token) => {
-                    let response = AuthResponse {
-                        token,
-                        user: UserInfo {
-                            id: user.id.clone(),
-                            username: user.username.clone(),
-                            email: user.email.clone(),
-                            quota: user.quota.clone(),
-                            created_at: user.created_at,
-                        },
-                    };
-                    record_api_request("POST", "/auth/login", 200, timer.elapsed());
-                    Ok(warp::reply::with_status(
-                        warp::reply::json(&response),
-                        warp::http::StatusCode::OK,
-                    ))
-                }
-                Err(_) => {
-                    record_api_request("POST", "/auth/login", 500, timer.elapsed());
-                    Ok(warp::reply::with_status(
-                        warp::reply::json(&serde_json::json!({
-                            "error": "Failed to create token"
-                        })),
-                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    ))
+    let prepared = base64::engine::general_purpose::STANDARD
+        .decode(&request.program_base64)
+        .map_err(|e| format!("Invalid base64 data: {}", e))
+        .and_then(|data| {
+            let load = LoadAndRun {
+                address: request.address,
+                data,
+                max_steps: request.max_steps,
+                set_reset_vector: true,
+                memory_diff: false,
+            };
+            load.validate()?;
+            if load.max_steps > MAX_RUN_ONCE_STEPS {
+                return Err(format!("max_steps must be between 1 and {}", MAX_RUN_ONCE_STEPS));
+            }
+            match &request.read {
+                Some(read) if read.length.unwrap_or(1) > config.max_memory_read => {
+                    Err(format!("Reads are limited to {} bytes", config.max_memory_read))
                 }
+                _ => Ok(load),
             }
-        } else {
-            record_api_request("POST", "/auth/login", 401, timer.elapsed());
-            Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "error": "Invalid credentials"
-                })),
-                warp::http::StatusCode::UNAUTHORIZED,
-            ))
+        });
+    let load = match prepared {
+        Ok(load) => load,
+        Err(e) => {
+            let response: ApiResponse<RunOnceResult> = ApiResponse::error(e);
+            record_api_request("POST", "/run-once", 400, timer.elapsed());
+            return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::BAD_REQUEST));
         }
-    } else {
-        record_api_request("POST", "/auth/login", 401, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Invalid credentials"
-            })),
-            warp::http::StatusCode::UNAUTHORIZED,
-        ))
-    }
-}
-
-async fn register_handler(
-    request: CreateUserRequest,
-    users: UserStore
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let timer = Timer::new();
+    };
     
-    let mut users_lock = users.lock().unwrap();
+    // Held until the response is built, or dropped with the future if the
+    // client goes away
+    let _permit = match slots.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            let response: ApiResponse<RunOnceResult> =
+                ApiResponse::error(format!("Platform at capacity ({} emulators)", config.max_emulators));
+            record_api_request("POST", "/run-once", 503, timer.elapsed());
+            return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::SERVICE_UNAVAILABLE));
+        }
+    };
     
-    // Check if username already exists
-    if users_lock.values().any(|u| u.username == request.username) {
-        record_api_request("POST", "/auth/register", 409, timer.elapsed());
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Username already exists"
-            })),
-            warp::http::StatusCode::CONFLICT,
-        ));
-    }
+    let mut emulator = Emulator::new();
+    emulator.load_program(load.address, &load.data);
+    emulator.set_reset_vector(load.address);
     
-    // Check if email already exists
-    if users_lock.values().any(|u| u.email == request.email) {
-        record_api_request("POST", "/auth/register", 409, timer.elapsed());
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Email already exists"
-            })),
-            warp::http::StatusCode::CONFLICT,
-        ));
+    // Same chunking as execute_chunked, so a long run doesn't hog the runtime
+    let mut progress = RunProgress::default();
+    loop {
+        let chunk = (load.max_steps - progress.executed).min(EXECUTE_CHUNK_STEPS);
+        if emulator.run_chunk(&mut progress, chunk) || progress.executed >= load.max_steps {
+            break;
+        }
+        pace(emulator.pacing_delay(&progress)).await;
     }
+    let execution = emulator.finish_run(progress);
     
-    match User::new(request.username, request.email, &request.password) {
-        Ok(user) => {
-            let user_info = UserInfo {
-                id: user.id.clone(),
-                username: user.username.clone(),
-                email: user.email.clone(),
-                quota: user.quota.clone(),
-                created_at: user.created_at,
-            };
-            
-            users_lock.insert(user.id.clone(), user);
-            
-            record_api_request("POST", "/auth/register", 201, timer.elapsed());
-            Ok(warp::reply::with_status(
-                warp::reply::json(&user_info),
-                warp::http::StatusCode::CREATED,
-            ))
-        }
-        Err(e) => {
-            record_api_request("POST", "/auth/register", 500, timer.elapsed());
-            Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "error": format!("Failed to create user: {}", e)
-                })),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-            ))
+    let memory = request.read.map(|read| {
+        let length = read.length.unwrap_or(1);
+        let data = emulator.read_memory(read.address, length);
+        let note = if data.len() < length as usize {
+            Some(format!("Read stopped at $FFFF after {} of {} bytes", data.len(), length))
+        } else {
+            None
+        };
+        MemoryData {
+            address: read.address,
+            data,
+            note,
         }
-    }
+    });
+    
+    let response = ApiResponse::success(RunOnceResult { execution, memory });
+    record_api_request("POST", "/run-once", 200, timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK))
 }
 
-async fn user_info_handler(user: User) -> Result<impl warp::Reply, warp::Rejection> {
+async fn load_example_handler(id: String, request: LoadExample, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let user_info = UserInfo {
-        id: user.id.clone(),
-        username: user.username.clone(),
-        email: user.email.clone(),
-        quota: user.quota.clone(),
-        created_at: user.created_at,
+    let (response, status) = match (emulators_lock.get_mut(&id), find_example(&request.name)) {
+        (Some(emulator), Some(program)) => {
+            emulator.load_example(program);
+            let response = ApiResponse::success(EmulatorState {
+                id: id.clone(),
+                cpu: emulator.get_state(),
+            });
+            (response, warp::http::StatusCode::OK)
+        }
+        (None, _) => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+        (_, None) => (
+            ApiResponse::error(format!("Unknown example program: {}", request.name)),
+            warp::http::StatusCode::BAD_REQUEST,
+        ),
     };
     
-    record_api_request("GET", "/auth/me", 200, timer.elapsed());
-    Ok(warp::reply::json(&user_info))
+    record_api_request("POST", "/emulator/:id/load-example", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-// ========== ENTERPRISE API KEY HANDLERS ==========
-
-async fn create_api_key_handler(
-    request: CreateApiKeyRequest,
-    user: User
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn list_programs_handler() -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let response = ApiResponse::success(EXAMPLE_PROGRAMS);
     
-    // For now, create a mock response since we'd need to modify the user in the store
-    let api_key_response = ApiKeyResponse {
-        id: uuid::Uuid::new_v4().to_string(),
-        name: request.name.clone(),
-        key: format!("mos6502_{}", base64::prelude::BASE64_URL_SAFE.encode(uuid::Uuid::new_v4().as_bytes())),
-        permissions: request.permissions.clone(),
-        expires_at: request.expires_in_days.map(|days| chrono::Utc::now() + chrono::Duration::days(days as i64)),
-    };
+    record_api_request("GET", "/programs", 200, timer.elapsed());
+    Ok(warp::reply::json(&response))
+}
+
+async fn instance_types_handler() -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let response = ApiResponse::success(EmulatorType::catalogue());
     
-    record_api_request("POST", "/api-keys", 201, timer.elapsed());
-    Ok(warp::reply::with_status(
-        warp::reply::json(&api_key_response),
-        warp::http::StatusCode::CREATED,
-    ))
+    record_api_request("GET", "/instance-types", 200, timer.elapsed());
+    Ok(warp::reply::json(&response))
 }
 
-async fn list_api_keys_handler(user: User) -> Result<impl warp::Reply, warp::Rejection> {
+async fn describe_opcode_handler(byte: String, query: DescribeOpcodeQuery) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
     
-    let api_keys: Vec<ApiKeyResponse> = user.api_keys.iter().map(|key| {
-        ApiKeyResponse {
-            id: key.id.clone(),
-            name: key.name.clone(),
-            key: "***hidden***".to_string(), // Don't return the actual key
-            permissions: key.permissions.clone(),
-            expires_at: key.expires_at,
+    // Hex, with or without a 0x or $ prefix
+    let digits = byte.trim_start_matches("0x").trim_start_matches('$');
+    let (response, status) = match u8::from_str_radix(digits, 16) {
+        Ok(opcode) => match describe_opcode(opcode, query.variant) {
+            Some(description) => (ApiResponse::success(description), warp::http::StatusCode::OK),
+            None => (
+                ApiResponse::error(format!("${:02X} is not an instruction on this CPU variant", opcode)),
+                warp::http::StatusCode::NOT_FOUND,
+            ),
+        },
+        Err(_) => {
+            let response: ApiResponse<OpcodeDescription> = ApiResponse::error(format!("Invalid opcode byte: {}", byte));
+            (response, warp::http::StatusCode::BAD_REQUEST)
         }
-    }).collect();
+    };
     
-    record_api_request("GET", "/api-keys", 200, timer.elapsed());
-    Ok(warp::reply::json(&api_keys))
+    record_api_request("GET", "/opcodes/:byte", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn delete_api_key_handler(
-    key_id: String,
-    user: User
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn disasm_handler(request: DisassembleRequest) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
     
-    // In a real implementation, we'd need to modify the user in the store
-    // For now, we'll just check if the key exists
-    if user.api_keys.iter().any(|k| k.id == key_id) {
-        record_api_request("DELETE", &format!("/api-keys/{}", key_id), 200, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({"message": "API key deleted"})),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
-        record_api_request("DELETE", &format!("/api-keys/{}", key_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "API key not found",
-            })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
-    }
+    let (reply, status) = match base64::engine::general_purpose::STANDARD.decode(&request.data_base64) {
+        Ok(data) => {
+            let reply = match request.format {
+                DisassemblyFormat::Structured => {
+                    warp::reply::json(&ApiResponse::success(disassemble(&data, request.address, request.variant)))
+                }
+                DisassemblyFormat::Listing => {
+                    warp::reply::json(&ApiResponse::success(listing(&data, request.address, request.variant)))
+                }
+            };
+            (reply, warp::http::StatusCode::OK)
+        }
+        Err(e) => {
+            let response: ApiResponse<Vec<DisassembledInstruction>> = ApiResponse::error(format!("Invalid base64 data: {}", e));
+            (warp::reply::json(&response), warp::http::StatusCode::BAD_REQUEST)
+        }
+    };
+    
+    record_api_request("POST", "/disasm", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(reply, status))
 }
 
-// ========== ENTERPRISE INSTANCE HANDLERS ==========
-
-async fn create_instance_handler(
-    request: CreateInstanceRequest,
-    user: User,
+async fn read_memory_handler(
+    id: String,
+    query: MemoryRead,
+    config: Arc<ServerConfig>,
     emulators: EmulatorMap,
-    templates: Arc<Mutex<HashMap<String, InstanceTemplate>>>
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
+    let length = query.length.unwrap_or(1);
     
-    // Get template
-    let templates_lock = templates.lock().unwrap();
-    let template_id = request.template_id.unwrap_or_else(|| "basic-6502".to_string());
-    let template = match templates_lock.get(&template_id) {
-        Some(t) => t,
-        None => {
-            record_api_request("POST", "/instances", 404, timer.elapsed());
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "error": "Template not found",
-                })),
-                warp::http::StatusCode::NOT_FOUND,
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(_) if length > config.max_memory_read => {
+            let response: ApiResponse<MemoryData> = ApiResponse::error(format!(
+                "Reads are limited to {} bytes; use GET /emulator/{}/export for all of memory",
+                config.max_memory_read, id
             ));
+            (response, warp::http::StatusCode::BAD_REQUEST)
+        }
+        Some(emulator) => {
+            let data = emulator.read_memory(query.address, length);
+            let note = if data.len() < length as usize {
+                Some(format!("Read stopped at $FFFF after {} of {} bytes", data.len(), length))
+            } else {
+                None
+            };
+            let response = ApiResponse::success(MemoryData {
+                address: query.address,
+                data,
+                note,
+            });
+            (response, warp::http::StatusCode::OK)
         }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
     };
     
-    // Create emulator instance
-    let instance_id = Uuid::new_v4().to_string();
-    let emulator = Emulator::new();
+    record_api_request("GET", "/emulator/:id/memory", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn read_page_handler(id: String, page: u8, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    // Create enterprise instance
-    let mut instance = EmulatorInstance::new(
-        user.id.clone(),
-        request.emulator_type.clone(),
-        request.name.clone(),
-        Some(template_id),
-        request.tags.clone(),
-    );
-    instance.id = instance_id.clone();
-    if request.auto_start.unwrap_or(false) {
-        instance.state = InstanceState::Running;
-    }
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            // Peeked, so viewing a page doesn't count as bus reads or
+            // disturb I/O devices
+            let address = (page as u16) << 8;
+            let response = ApiResponse::success(MemoryPage {
+                page,
+                address,
+                data: (address..=address | 0xFF).map(|addr| emulator.memory.peek(addr)).collect(),
+            });
+            (response, warp::http::StatusCode::OK)
+        }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
     
-    // Store emulator
-    {
-        let mut emulators_lock = emulators.lock().unwrap();
-        emulators_lock.insert(instance_id.clone(), emulator);
-        set_active_emulators(emulators_lock.len());
-    }
-    
-    record_api_request("POST", "/instances", 201, timer.elapsed());
-    Ok(warp::reply::with_status(
-        warp::reply::json(&instance),
-        warp::http::StatusCode::CREATED,
-    ))
+    record_api_request("GET", "/emulator/:id/page/:n", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn list_instances_handler(
-    user: User,
-    emulators: EmulatorMap
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn memory_checksum_handler(id: String, query: MemoryChecksumQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    // In a real implementation, we'd filter by user ownership
-    // For now, return all instances with mock data
-    let emulators_lock = emulators.lock().unwrap();
-    let instances: Vec<EmulatorInstance> = emulators_lock.keys().map(|id| {
-        let mut instance = EmulatorInstance::new(
-            user.id.clone(),
-            EmulatorType::Standard,
-            Some(format!("Instance {}", &id[..8])),
-            Some("basic-6502".to_string()),
-            Some(vec!["test".to_string()]),
-        );
-        instance.id = id.clone();
-        instance.state = InstanceState::Running;
-        instance
-    }).collect();
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => match emulator.memory_checksum(query.address, query.length) {
+            Ok(sha256) => (
+                ApiResponse::success(MemoryChecksum {
+                    address: query.address,
+                    length: query.length,
+                    sha256,
+                }),
+                warp::http::StatusCode::OK,
+            ),
+            Err(e) => (ApiResponse::error(e), warp::http::StatusCode::BAD_REQUEST),
+        },
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
     
-    record_api_request("GET", "/instances", 200, timer.elapsed());
-    Ok(warp::reply::json(&instances))
+    record_api_request("GET", "/emulator/:id/memory/checksum", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn get_instance_handler(
-    instance_id: String,
-    user: User,
-    emulators: EmulatorMap
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn copy_memory_handler(id: String, request: MemoryCopy, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let emulators_lock = emulators.lock().unwrap();
-    if emulators_lock.contains_key(&instance_id) {
-        let mut instance = EmulatorInstance::new(
-            user.id.clone(),
-            EmulatorType::Standard,
-            Some(format!("Instance {}", &instance_id[..8])),
-            Some("basic-6502".to_string()),
-            Some(vec!["test".to_string()]),
-        );
-        instance.id = instance_id.clone();
-        instance.state = InstanceState::Running;
-        
-        record_api_request("GET", &format!("/instances/{}", instance_id), 200, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&instance),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
-        record_api_request("GET", &format!("/instances/{}", instance_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Instance not found",
-            })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
-    }
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => match emulator.copy_memory(request.src, request.dest, request.length) {
+            Ok(()) => (
+                ApiResponse::success(format!("Copied {} bytes from ${:04X} to ${:04X}", request.length, request.src, request.dest)),
+                warp::http::StatusCode::OK,
+            ),
+            Err(e) => (ApiResponse::error(e), warp::http::StatusCode::BAD_REQUEST),
+        },
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/memory/copy", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn start_instance_handler(
-    instance_id: String,
-    user: User,
-    emulators: EmulatorMap
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn opcodes_used_handler(id: String, query: OpcodesUsedQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    let emulators_lock = emulators.lock().unwrap();
-    if emulators_lock.contains_key(&instance_id) {
-        record_api_request("POST", &format!("/instances/{}/start", instance_id), 200, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({"message": "Instance started", "state": "Running"})),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
-        record_api_request("POST", &format!("/instances/{}/start", instance_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Instance not found",
-            })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
-    }
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => match emulator.opcodes_used(query.address, query.length) {
+            Ok(opcodes) => (
+                ApiResponse::success(OpcodesUsed {
+                    address: query.address,
+                    length: query.length,
+                    all_implemented: opcodes.iter().all(|o| o.implemented),
+                    opcodes,
+                }),
+                warp::http::StatusCode::OK,
+            ),
+            Err(e) => (ApiResponse::error(e), warp::http::StatusCode::BAD_REQUEST),
+        },
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("GET", "/emulator/:id/opcodes-used", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn stop_instance_handler(
-    instance_id: String,
-    user: User,
-    emulators: EmulatorMap
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn validate_handler(id: String, query: ValidateQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    let emulators_lock = emulators.lock().unwrap();
-    if emulators_lock.contains_key(&instance_id) {
-        record_api_request("POST", &format!("/instances/{}/stop", instance_id), 200, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({"message": "Instance stopped", "state": "Stopped"})),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
-        record_api_request("POST", &format!("/instances/{}/stop", instance_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Instance not found",
-            })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
-    }
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            let report = emulator.validate_program(query.max_bytes.unwrap_or(DEFAULT_VALIDATE_BYTES));
+            (ApiResponse::success(report), warp::http::StatusCode::OK)
+        }
+        None => {
+            let response: ApiResponse<ValidationReport> = ApiResponse::error(EMULATOR_NOT_FOUND.to_string());
+            (response, warp::http::StatusCode::NOT_FOUND)
+        }
+    };
+    
+    record_api_request("POST", "/emulator/:id/validate", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn pause_instance_handler(
-    instance_id: String,
-    user: User,
-    emulators: EmulatorMap
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn memory_map_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    let emulators_lock = emulators.lock().unwrap();
-    if emulators_lock.contains_key(&instance_id) {
-        record_api_request("POST", &format!("/instances/{}/pause", instance_id), 200, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({"message": "Instance paused", "state": "Paused"})),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
-        record_api_request("POST", &format!("/instances/{}/pause", instance_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Instance not found",
-            })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
-    }
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            (ApiResponse::success(emulator.memory.regions()), warp::http::StatusCode::OK)
+        }
+        None => {
+            let response: ApiResponse<Vec<MemoryRegion>> = ApiResponse::error(EMULATOR_NOT_FOUND.to_string());
+            (response, warp::http::StatusCode::NOT_FOUND)
+        }
+    };
+    
+    record_api_request("GET", "/emulator/:id/memory/map", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-// ========== ENTERPRISE SNAPSHOT HANDLERS ==========
+async fn attach_timer_handler(id: String, request: AttachTimerRequest, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => match request.address.checked_add(TIMER_REGISTERS - 1) {
+            None => (
+                ApiResponse::error(format!("The timer needs {} bytes and doesn't fit at ${:04X}", TIMER_REGISTERS, request.address)),
+                warp::http::StatusCode::BAD_REQUEST,
+            ),
+            Some(end) => {
+                let overlap = emulator.memory.regions().into_iter().find(|region| {
+                    region.kind == RegionKind::Io && region.start <= end && request.address <= region.end
+                });
+                match overlap {
+                    Some(region) => (
+                        ApiResponse::error(format!("${:04X}-${:04X} overlaps I/O region {}", request.address, end, region.name)),
+                        warp::http::StatusCode::CONFLICT,
+                    ),
+                    None => {
+                        emulator.memory.add_io_handler("timer", request.address, end, Box::new(TimerDevice::new(request.address)));
+                        (ApiResponse::success(emulator.memory.regions()), warp::http::StatusCode::OK)
+                    }
+                }
+            }
+        },
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/timer", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
 
-async fn create_snapshot_handler(
-    emulator_id: String,
-    request: CreateSnapshotRequest,
-    user: User,
-    emulators: EmulatorMap,
-    snapshots: SnapshotStore
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn low_pages_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    // Get emulator
-    let emulators_lock = emulators.lock().unwrap();
-    let emulator = match emulators_lock.get(&emulator_id) {
-        Some(e) => e,
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            let page = |base: u16| (base..base + 0x100).map(|addr| emulator.memory.peek(addr)).collect();
+            let response = ApiResponse::success(LowPages {
+                zero_page: page(0x0000),
+                stack_page: page(0x0100),
+                sp: emulator.cpu.get_sp(),
+            });
+            (response, warp::http::StatusCode::OK)
+        }
         None => {
-            record_api_request("POST", &format!("/emulator/{}/snapshots", emulator_id), 404, timer.elapsed());
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "error": "Emulator not found",
-                })),
-                warp::http::StatusCode::NOT_FOUND,
-            ));
+            let response: ApiResponse<LowPages> = ApiResponse::error(EMULATOR_NOT_FOUND.to_string());
+            (response, warp::http::StatusCode::NOT_FOUND)
         }
     };
     
-    // Create snapshot
-    let snapshot_id = Uuid::new_v4().to_string();
-    let emulator_state = emulator.get_state();
-    let cpu_state = &emulator_state; // EmulatorState.cpu field doesn't exist, use the state directly
-    let memory_dump = emulator.get_memory_dump();
-    
-    let memory_size = memory_dump.len() as u64;
-    let snapshot = EmulatorSnapshot {
-        id: snapshot_id.clone(),
-        name: request.name.clone(),
-        description: request.description.unwrap_or_default(),
-        emulator_id: emulator_id.clone(),
-        owner_id: user.id.clone(),
-        cpu_state: crate::snapshots::CpuSnapshot {
-            a: cpu_state.a,
-            x: cpu_state.x,
-            y: cpu_state.y,
-            pc: cpu_state.pc,
-            sp: cpu_state.sp,
-            status: cpu_state.status,
-            cycles: cpu_state.cycles,
-            halted: cpu_state.halted,
-        },
-        memory_dump,
-        metadata: crate::snapshots::SnapshotMetadata {
-            emulator_type: "6502".to_string(),
-            template_id: Some("basic-6502".to_string()),
-            checkpoint_reason: CheckpointReason::Manual,
-            instruction_count: cpu_state.cycles,
-            execution_time_ms: 0,
-            compression_ratio: 0.5,
-        },
-        created_at: chrono::Utc::now(),
-        size_bytes: memory_size,
-        tags: request.tags.unwrap_or_default(),
+    record_api_request("GET", "/emulator/:id/pages/low", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn next_effective_address_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            let response = ApiResponse::success(NextEffectiveAddress {
+                instruction: emulator.next_instruction(),
+                effective_address: emulator.next_effective_address(),
+            });
+            (response, warp::http::StatusCode::OK)
+        }
+        None => {
+            let response: ApiResponse<NextEffectiveAddress> = ApiResponse::error(EMULATOR_NOT_FOUND.to_string());
+            (response, warp::http::StatusCode::NOT_FOUND)
+        }
     };
     
-    // Store snapshot
-    {
-        let mut snapshots_lock = snapshots.lock().unwrap();
-        snapshots_lock.insert(snapshot_id.clone(), snapshot.clone());
-    }
+    record_api_request("GET", "/emulator/:id/next-effective-address", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn export_state_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
     
-    record_api_request("POST", &format!("/emulator/{}/snapshots", emulator_id), 201, timer.elapsed());
-    Ok(warp::reply::with_status(
-        warp::reply::json(&snapshot),
-        warp::http::StatusCode::CREATED,
-    ))
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            let response = ApiResponse::success(StateExport {
+                data_base64: base64::engine::general_purpose::STANDARD.encode(emulator.export_state()),
+            });
+            (response, warp::http::StatusCode::OK)
+        }
+        None => {
+            let response: ApiResponse<StateExport> = ApiResponse::error(EMULATOR_NOT_FOUND.to_string());
+            (response, warp::http::StatusCode::NOT_FOUND)
+        }
+    };
+    
+    record_api_request("GET", "/emulator/:id/export", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn list_snapshots_handler(
-    emulator_id: String,
-    user: User,
-    snapshots: SnapshotStore
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn import_state_handler(id: String, request: StateExport, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let snapshots_lock = snapshots.lock().unwrap();
-    let emulator_snapshots: Vec<&EmulatorSnapshot> = snapshots_lock
-        .values()
-        .filter(|s| s.emulator_id == emulator_id && s.owner_id == user.id)
-        .collect();
+    let (outcome, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            let outcome = base64::engine::general_purpose::STANDARD
+                .decode(&request.data_base64)
+                .map_err(|e| format!("Invalid base64 data: {}", e))
+                .and_then(|blob| emulator.import_state(&blob))
+                .map(|_| emulator.get_state());
+            let status = if outcome.is_ok() { warp::http::StatusCode::OK } else { warp::http::StatusCode::BAD_REQUEST };
+            (outcome, status)
+        }
+        None => (Err(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
     
-    let response = SnapshotListResponse {
-        snapshots: emulator_snapshots.iter().map(|s| s.get_summary()).collect(),
-        total_count: emulator_snapshots.len(),
-        total_size_bytes: emulator_snapshots.iter().map(|s| s.size_bytes).sum(),
+    let response = match outcome {
+        Ok(state) => ApiResponse::success(EmulatorState { id, cpu: state }),
+        Err(e) => ApiResponse::error(e),
     };
     
-    record_api_request("GET", &format!("/emulator/{}/snapshots", emulator_id), 200, timer.elapsed());
-    Ok(warp::reply::json(&response))
+    record_api_request("POST", "/emulator/:id/import", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn get_snapshot_handler(
-    snapshot_id: String,
-    user: User,
-    snapshots: SnapshotStore
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn configure_trace_handler(id: String, config: TraceConfig, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let snapshots_lock = snapshots.lock().unwrap();
-    if let Some(snapshot) = snapshots_lock.get(&snapshot_id) {
-        if snapshot.owner_id == user.id {
-            record_api_request("GET", &format!("/snapshots/{}", snapshot_id), 200, timer.elapsed());
-            Ok(warp::reply::with_status(
-                warp::reply::json(snapshot),
-                warp::http::StatusCode::OK,
-            ))
-        } else {
-            record_api_request("GET", &format!("/snapshots/{}", snapshot_id), 403, timer.elapsed());
-            Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "error": "Access denied",
-                })),
-                warp::http::StatusCode::FORBIDDEN,
-            ))
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            emulator.trace = if config.enabled {
+                Some(TraceBuffer::new(config.capacity.unwrap_or(DEFAULT_TRACE_CAPACITY)))
+            } else {
+                None
+            };
+            let state = if config.enabled { "enabled" } else { "disabled" };
+            (ApiResponse::success(format!("Tracing {}", state)), warp::http::StatusCode::OK)
         }
-    } else {
-        record_api_request("GET", &format!("/snapshots/{}", snapshot_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({
-                "error": "Snapshot not found",
-            })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
-    }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/trace", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
 }
 
-async fn restore_snapshot_handler(
-    snapshot_id: String,
-    request: RestoreSnapshotRequest,
+async fn get_trace_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status): (ApiResponse<Vec<TraceEntry>>, _) = match emulators_lock.get(&id) {
+        Some(emulator) => (
+            ApiResponse::success(emulator.trace.as_ref().map(|t| t.entries()).unwrap_or_default()),
+            warp::http::StatusCode::OK,
+        ),
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("GET", "/emulator/:id/trace", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn set_breakpoints_handler(id: String, request: BreakpointsRequest, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            // A mistyped mnemonic would otherwise never fire
+            let variant = emulator.cpu.variant;
+            let unknown = request.breakpoints.iter().find_map(|breakpoint| match breakpoint {
+                Breakpoint::Mnemonic { mnemonic }
+                    if !(0..=255u8).any(|opcode| lookup(opcode, variant).is_some_and(|info| info.mnemonic.eq_ignore_ascii_case(mnemonic))) =>
+                {
+                    Some(mnemonic.clone())
+                }
+                _ => None,
+            });
+            match unknown {
+                Some(mnemonic) => (
+                    ApiResponse::error(format!("Unknown mnemonic {}", mnemonic)),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ),
+                None => {
+                    emulator.breakpoints = request.breakpoints;
+                    (ApiResponse::success(emulator.breakpoints.clone()), warp::http::StatusCode::OK)
+                }
+            }
+        }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/breakpoints", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn get_breakpoints_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get(&id) {
+        Some(emulator) => (ApiResponse::success(emulator.breakpoints.clone()), warp::http::StatusCode::OK),
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("GET", "/emulator/:id/breakpoints", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn configure_pc_histogram_handler(id: String, config: PcHistogramConfig, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            emulator.pc_histogram = if config.enabled {
+                Some(PcHistogram::new(config.capacity.unwrap_or(DEFAULT_PC_HISTOGRAM_CAPACITY)))
+            } else {
+                None
+            };
+            let state = if config.enabled { "enabled" } else { "disabled" };
+            (ApiResponse::success(format!("PC histogram {}", state)), warp::http::StatusCode::OK)
+        }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/pc-histogram", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn get_pc_histogram_handler(id: String, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status): (ApiResponse<PcHistogramReport>, _) = match emulators_lock.get(&id) {
+        Some(emulator) => {
+            let report = match &emulator.pc_histogram {
+                Some(histogram) => histogram.report(),
+                None => PcHistogramReport { enabled: false, total: 0, untracked: 0, entries: Vec::new() },
+            };
+            (ApiResponse::success(report), warp::http::StatusCode::OK)
+        }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("GET", "/emulator/:id/pc-histogram", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn trace_run_handler(id: String, request: TraceRun, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
+    
+    let (response, status) = if request.steps == 0 || request.steps > MAX_TRACE_RUN_STEPS {
+        (
+            ApiResponse::error(format!("steps must be between 1 and {}", MAX_TRACE_RUN_STEPS)),
+            warp::http::StatusCode::BAD_REQUEST,
+        )
+    } else {
+        match emulators_lock.get_mut(&id) {
+            Some(emulator) => (ApiResponse::success(emulator.trace_run(request.steps)), warp::http::StatusCode::OK),
+            None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+        }
+    };
+    
+    record_api_request("POST", "/emulator/:id/trace-run", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn compare_emulators_handler(
+    id: String,
+    other_id: String,
     user: User,
     emulators: EmulatorMap,
-    snapshots: SnapshotStore
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
     
-    // Get snapshot
-    let snapshots_lock = snapshots.lock().unwrap();
-    let snapshot = match snapshots_lock.get(&snapshot_id) {
-        Some(s) if s.owner_id == user.id => s,
-        Some(_) => {
-            record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 403, timer.elapsed());
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({
-                    "error": "Access denied",
-                })),
-                warp::http::StatusCode::FORBIDDEN,
-            ));
-        }
-        None => {
-            record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 404, timer.elapsed());
+    let emulators_lock = lock_emulators(&emulators);
+    let (left, right) = match (emulators_lock.get(&id), emulators_lock.get(&other_id)) {
+        (Some(left), Some(right)) => (left, right),
+        _ => {
+            record_api_request("GET", "/emulator/:id/compare/:other_id", 404, timer.elapsed());
             return Ok(warp::reply::with_status(
                 warp::reply::json(&serde_json::json!({
-                    "error": "Snapshot not found",
+                    "error": EMULATOR_NOT_FOUND,
                 })),
                 warp::http::StatusCode::NOT_FOUND,
             ));
         }
     };
     
-    // Get emulator and restore
-    let mut emulators_lock = emulators.lock().unwrap();
-    if let Some(emulator) = emulators_lock.get_mut(&snapshot.emulator_id) {
-        // In a real implementation, we'd restore the CPU state and memory
-        record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 200, timer.elapsed());
-        Ok(warp::reply::with_status(
-            warp::reply::json(&serde_json::json!({"message": "Snapshot restored", "snapshot_id": snapshot_id})),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
-        record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 404, timer.elapsed());
-        Ok(warp::reply::with_status(
+    if !left.can_user_access(&user.id) || !right.can_user_access(&user.id) {
+        record_api_request("GET", "/emulator/:id/compare/:other_id", 403, timer.elapsed());
+        return Ok(warp::reply::with_status(
             warp::reply::json(&serde_json::json!({
-                "error": "Emulator not found",
+                "error": "Access denied",
             })),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
+            warp::http::StatusCode::FORBIDDEN,
+        ));
     }
+    
+    let comparison = left.compare(right, MAX_COMPARE_DIFFERENCES);
+    
+    record_api_request("GET", "/emulator/:id/compare/:other_id", 200, timer.elapsed());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&comparison),
+        warp::http::StatusCode::OK,
+    ))
 }
 
-async fn delete_snapshot_handler(
-    snapshot_id: String,
-    user: User,
-    snapshots: SnapshotStore
-) -> Result<impl warp::Reply, warp::Rejection> {
+async fn write_memory_handler(id: String, request: MemoryWrite, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
     let timer = Timer::new();
+    let mut emulators_lock = lock_emulators(&emulators);
     
-    let mut snapshots_lock = snapshots.lock().unwrap();
-    if let Some(snapshot) = snapshots_lock.get(&snapshot_id) {
-        if snapshot.owner_id == user.id {
-            snapshots_lock.remove(&snapshot_id);
-            record_api_request("DELETE", &format!("/snapshots/{}", snapshot_id), 200, timer.elapsed());
-            Ok(warp::reply::with_status(
-                warp::reply::json(&serde_json::json!({"message": "Snapshot deleted"})),
-                warp::http::StatusCode::OK,
+    let (response, status) = match emulators_lock.get_mut(&id) {
+        Some(emulator) => {
+            emulator.write_memory(request.address, request.value);
+            let response = ApiResponse::success(format!("Wrote ${:02X} to address ${:04X}", request.value, request.address));
+            (response, warp::http::StatusCode::OK)
+        }
+        None => (ApiResponse::error(EMULATOR_NOT_FOUND.to_string()), warp::http::StatusCode::NOT_FOUND),
+    };
+    
+    record_api_request("POST", "/emulator/:id/memory", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn list_emulators_handler(query: ListEmulatorsQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let emulators_lock = lock_emulators(&emulators);
+    
+    let emulator_list: Vec<EmulatorSummary> = emulators_lock
+        .iter()
+        .filter(|(_, emulator)| match &query.tag {
+            Some(tag) => emulator.instance.tags.contains(tag),
+            None => true,
+        })
+        .map(|(id, emulator)| EmulatorSummary {
+            id: id.clone(),
+            tags: emulator.instance.tags.clone(),
+            cpu: emulator.get_state(),
+        })
+        .collect();
+    
+    let response = ApiResponse::success(emulator_list);
+    record_api_request("GET", "/emulators", 200, timer.elapsed());
+    Ok(warp::reply::json(&response))
+}
+
+async fn delete_emulator_handler(id: String, query: DeleteEmulatorQuery, emulators: EmulatorMap) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    // A run holds the lock only one chunk at a time, so removing the
+    // emulator under it would cut the run off mid-way. Without force that's
+    // refused; with it the run is cancelled and given time to stop.
+    let busy = match lock_emulators(&emulators).get_mut(&id) {
+        Some(emulator) if emulator.running => {
+            if query.force {
+                emulator.cancel_requested = true;
+            }
+            true
+        }
+        _ => false,
+    };
+    if busy && !query.force {
+        let response: ApiResponse<String> = ApiResponse::error("Emulator is running; cancel it first or delete with force=true".to_string());
+        record_api_request("DELETE", "/emulator/:id", 409, timer.elapsed());
+        return Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::CONFLICT));
+    }
+    if busy {
+        let deadline = std::time::Instant::now() + DELETE_CANCEL_TIMEOUT;
+        while std::time::Instant::now() < deadline {
+            match lock_emulators(&emulators).get(&id) {
+                Some(emulator) if emulator.running => {}
+                _ => break,
+            }
+            tokio::time::sleep(DELETE_CANCEL_POLL).await;
+        }
+    }
+    
+    let mut emulators_lock = lock_emulators(&emulators);
+    let (response, status) = match emulators_lock.remove(&id) {
+        Some(_) => {
+            set_active_emulators(emulators_lock.len());
+            (ApiResponse::success(format!("Emulator {} deleted", id)), warp::http::StatusCode::OK)
+        }
+        None => {
+            let response: ApiResponse<String> = ApiResponse::error(EMULATOR_NOT_FOUND.to_string());
+            (response, warp::http::StatusCode::NOT_FOUND)
+        }
+    };
+    
+    record_api_request("DELETE", "/emulator/:id", status.as_u16(), timer.elapsed());
+    Ok(warp::reply::with_status(warp::reply::json(&response), status))
+}
+
+async fn metrics_handler() -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    let encoder = prometheus::TextEncoder::new();
+    let metric_families = REGISTRY.gather();
+    
+    match encoder.encode_to_string(&metric_families) {
+        Ok(metrics_text) => {
+            record_api_request("GET", "/metrics", 200, timer.elapsed());
+            Ok(warp::reply::with_header(
+                metrics_text,
+                "content-type",
+                "text/plain; version=0.0.4",
+            ))
+        }
+        Err(_) => {
+            record_api_request("GET", "/metrics", 500, timer.elapsed());
+            Ok(warp::reply::with_header(
+                "Error encoding metrics".to_string(),
+                "content-type",
+                "text/plain",
             ))
+        }
+    }
+}
+
+fn init_default_templates(templates: Arc<Mutex<HashMap<String, InstanceTemplate>>>) {
+    let mut templates_lock = templates.lock().unwrap();
+    let default_templates = InstanceTemplate::create_basic_templates();
+    
+    for template in default_templates {
+        templates_lock.insert(template.id.clone(), template);
+    }
+    
+    println!("Initialized {} default instance templates", templates_lock.len());
+}
+
+// ========== ENTERPRISE AUTHENTICATION HANDLERS ==========
+
+async fn login_handler(
+    request: LoginRequest, 
+    users: UserStore,
+    config: Arc<ServerConfig>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let users_lock = users.lock().unwrap();
+    if let Some(user) = users_lock.values().find(|u| u.username == request.username) {
+        if bcrypt::verify(&request.password, &user.password_hash).unwrap_or(false) {
+            match create_jwt_token(user, config.jwt_ttl_hours) {
+                Ok(token) => {
+                    let response = AuthResponse {
+                        token,
+                        user: user.info(),
+                        expires_in_hours: config.jwt_ttl_hours,
+                    };
+                    record_api_request("POST", "/auth/login", 200, timer.elapsed());
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&response),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+                Err(_) => {
+                    record_api_request("POST", "/auth/login", 500, timer.elapsed());
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "error": "Failed to create token"
+                        })),
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    ))
+                }
+            }
         } else {
-            record_api_request("DELETE", &format!("/snapshots/{}", snapshot_id), 403, timer.elapsed());
+            record_api_request("POST", "/auth/login", 401, timer.elapsed());
             Ok(warp::reply::with_status(
                 warp::reply::json(&serde_json::json!({
-                    "error": "Access denied",
+                    "error": "Invalid credentials"
                 })),
-                warp::http::StatusCode::FORBIDDEN,
+                warp::http::StatusCode::UNAUTHORIZED,
             ))
         }
     } else {
-        record_api_request("DELETE", &format!("/snapshots/{}", snapshot_id), 404, timer.elapsed());
+        record_api_request("POST", "/auth/login", 401, timer.elapsed());
         Ok(warp::reply::with_status(
             warp::reply::json(&serde_json::json!({
-                "error": "Snapshot not found",
+                "error": "Invalid credentials"
             })),
-            warp::http::StatusCode::NOT_FOUND,
+            warp::http::StatusCode::UNAUTHORIZED,
         ))
     }
-}
\ No newline at end of file
+}
+
+async fn register_handler(
+    request: CreateUserRequest,
+    users: UserStore
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let mut users_lock = users.lock().unwrap();
+    
+    // Check if username already exists
+    if users_lock.values().any(|u| u.username == request.username) {
+        record_api_request("POST", "/auth/register", 409, timer.elapsed());
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Username already exists"
+            })),
+            warp::http::StatusCode::CONFLICT,
+        ));
+    }
+    
+    // Check if email already exists
+    if users_lock.values().any(|u| u.email == request.email) {
+        record_api_request("POST", "/auth/register", 409, timer.elapsed());
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Email already exists"
+            })),
+            warp::http::StatusCode::CONFLICT,
+        ));
+    }
+    
+    match User::new(request.username, request.email, &request.password) {
+        Ok(user) => {
+            let user_info = user.info();
+            
+            users_lock.insert(user.id.clone(), user);
+            save_users(&users, &users_lock);
+            
+            record_api_request("POST", "/auth/register", 201, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&user_info),
+                warp::http::StatusCode::CREATED,
+            ))
+        }
+        Err(e) => {
+            record_api_request("POST", "/auth/register", 500, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": format!("Failed to create user: {}", e)
+                })),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+async fn user_info_handler(user: User) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let user_info = user.info();
+    
+    record_api_request("GET", "/auth/me", 200, timer.elapsed());
+    Ok(warp::reply::json(&user_info))
+}
+
+// Persist an account change. The change has already been made in memory,
+// so a failure to write it out is logged rather than failing the request.
+fn save_users(users: &UserStore, contents: &HashMap<String, User>) {
+    if let Err(e) = users.save(contents) {
+        eprintln!("{}", e);
+    }
+}
+
+// ========== ADMIN HANDLERS ==========
+
+async fn list_users_handler(
+    user: User,
+    users: UserStore,
+    emulators: EmulatorMap,
+    snapshots: SnapshotStore,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    if !user.has_permission(&Permission::Admin) {
+        record_api_request("GET", "/admin/users", 403, timer.elapsed());
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Admin permission required",
+            })),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+    
+    let mut emulator_counts: HashMap<String, usize> = HashMap::new();
+    for emulator in lock_emulators(&emulators).values() {
+        *emulator_counts.entry(emulator.instance.owner_id.clone()).or_default() += 1;
+    }
+    let mut snapshot_bytes: HashMap<String, u64> = HashMap::new();
+    for snapshot in snapshots.lock().unwrap().values() {
+        *snapshot_bytes.entry(snapshot.owner_id.clone()).or_default() += snapshot.size_bytes;
+    }
+    
+    let mut summaries: Vec<AdminUserSummary> = users.lock().unwrap().values().map(|u| AdminUserSummary {
+        user: u.info(),
+        emulator_count: emulator_counts.get(&u.id).copied().unwrap_or(0),
+        snapshot_storage_bytes: snapshot_bytes.get(&u.id).copied().unwrap_or(0),
+    }).collect();
+    summaries.sort_by(|a, b| a.user.username.cmp(&b.user.username));
+    
+    record_api_request("GET", "/admin/users", 200, timer.elapsed());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&summaries),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+async fn delete_user_handler(
+    user_id: String,
+    query: DeleteUserQuery,
+    user: User,
+    users: UserStore,
+    emulators: EmulatorMap,
+    snapshots: SnapshotStore,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let refusal = if !user.has_permission(&Permission::Admin) {
+        Some((warp::http::StatusCode::FORBIDDEN, "Admin permission required"))
+    } else if user_id == user.id && !query.confirm {
+        Some((warp::http::StatusCode::BAD_REQUEST, "Refusing to delete your own account without confirm=true"))
+    } else {
+        let mut users_lock = users.lock().unwrap();
+        if users_lock.remove(&user_id).is_none() {
+            Some((warp::http::StatusCode::NOT_FOUND, "User not found"))
+        } else {
+            save_users(&users, &users_lock);
+            None
+        }
+    };
+    if let Some((status, message)) = refusal {
+        record_api_request("DELETE", "/admin/users/:id", status.as_u16(), timer.elapsed());
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": message,
+            })),
+            status,
+        ));
+    }
+    
+    let emulators_deleted = {
+        let mut emulators_lock = lock_emulators(&emulators);
+        let before = emulators_lock.len();
+        emulators_lock.retain(|_, emulator| emulator.instance.owner_id != user_id);
+        set_active_emulators(emulators_lock.len());
+        before - emulators_lock.len()
+    };
+    
+    // Deduplicated snapshots only share memory within one owner, so all of
+    // them can go without repointing anything
+    let snapshots_deleted = {
+        let mut snapshots_lock = snapshots.lock().unwrap();
+        let before = snapshots_lock.len();
+        snapshots_lock.retain(|_, snapshot| snapshot.owner_id != user_id);
+        before - snapshots_lock.len()
+    };
+    
+    record_api_request("DELETE", "/admin/users/:id", 200, timer.elapsed());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "message": "User deleted",
+            "emulators_deleted": emulators_deleted,
+            "snapshots_deleted": snapshots_deleted,
+        })),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+// ========== ENTERPRISE API KEY HANDLERS ==========
+
+async fn create_api_key_handler(
+    request: CreateApiKeyRequest,
+    _user: User
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    // For now, create a mock response since we'd need to modify the user in the store
+    let api_key_response = ApiKeyResponse {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: request.name.clone(),
+        key: format!("mos6502_{}", base64::prelude::BASE64_URL_SAFE.encode(uuid::Uuid::new_v4().as_bytes())),
+        permissions: request.permissions.clone(),
+        expires_at: request.expires_in_days.map(|days| chrono::Utc::now() + chrono::Duration::days(days as i64)),
+        last_used: None,
+    };
+    
+    record_api_request("POST", "/api-keys", 201, timer.elapsed());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&api_key_response),
+        warp::http::StatusCode::CREATED,
+    ))
+}
+
+async fn list_api_keys_handler(user: User) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let api_keys: Vec<ApiKeyResponse> = user.api_keys.iter().map(|key| {
+        ApiKeyResponse {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            key: "***hidden***".to_string(), // Don't return the actual key
+            permissions: key.permissions.clone(),
+            expires_at: key.expires_at,
+            last_used: key.last_used,
+        }
+    }).collect();
+    
+    record_api_request("GET", "/api-keys", 200, timer.elapsed());
+    Ok(warp::reply::json(&api_keys))
+}
+
+async fn delete_api_key_handler(
+    key_id: String,
+    user: User
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    // In a real implementation, we'd need to modify the user in the store
+    // For now, we'll just check if the key exists
+    if user.api_keys.iter().any(|k| k.id == key_id) {
+        record_api_request("DELETE", &format!("/api-keys/{}", key_id), 200, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({"message": "API key deleted"})),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        record_api_request("DELETE", &format!("/api-keys/{}", key_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "API key not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+// ========== ENTERPRISE INSTANCE HANDLERS ==========
+
+async fn create_instance_handler(
+    request: CreateInstanceRequest,
+    user: User,
+    api_key: Option<ApiKey>,
+    config: Arc<ServerConfig>,
+    emulators: EmulatorMap,
+    templates: Arc<Mutex<HashMap<String, InstanceTemplate>>>
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    if let Err(errors) = request.validate() {
+        record_api_request("POST", "/instances", 400, timer.elapsed());
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Invalid instance request",
+                "fields": errors,
+            })),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+    
+    // Get template
+    let templates_lock = templates.lock().unwrap();
+    let template_id = request.template_id.unwrap_or_else(|| "basic-6502".to_string());
+    let template = match templates_lock.get(&template_id) {
+        Some(t) => t,
+        None => {
+            record_api_request("POST", "/instances", 404, timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Template not found",
+                })),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+    
+    // Create emulator instance
+    let instance_id = Uuid::new_v4().to_string();
+    
+    // Create enterprise instance
+    let mut instance = EmulatorInstance::new(
+        user.id.clone(),
+        request.emulator_type.clone(),
+        request.name.clone(),
+        Some(template_id),
+        request.tags.clone(),
+    );
+    instance.id = instance_id.clone();
+    if request.auto_start.unwrap_or(false) {
+        instance.state = InstanceState::Running;
+    }
+    instance.auto_recover = request.auto_recover.unwrap_or(false);
+    let mut emulator = Emulator::new_with_instance(instance.clone());
+    emulator.api_key_id = api_key.as_ref().map(|k| k.id.clone());
+    if let Err(e) = template.apply_to(&mut emulator.memory) {
+        record_api_request("POST", "/instances", 400, timer.elapsed());
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": e,
+            })),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+    emulator.reset();
+    
+    // Store emulator
+    {
+        let mut emulators_lock = lock_emulators(&emulators);
+        if let Err((status, message)) = check_emulator_capacity(&emulators_lock, &config, api_key.as_ref()) {
+            record_api_request("POST", "/instances", status.as_u16(), timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": message,
+                })),
+                status,
+            ));
+        }
+        emulators_lock.insert(instance_id.clone(), emulator);
+        set_active_emulators(emulators_lock.len());
+    }
+    
+    record_api_request("POST", "/instances", 201, timer.elapsed());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&instance),
+        warp::http::StatusCode::CREATED,
+    ))
+}
+
+async fn list_instances_handler(
+    user: User,
+    emulators: EmulatorMap
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    // In a real implementation, we'd filter by user ownership
+    // For now, return all instances with mock data
+    let emulators_lock = lock_emulators(&emulators);
+    let instances: Vec<EmulatorInstance> = emulators_lock.keys().map(|id| {
+        let mut instance = EmulatorInstance::new(
+            user.id.clone(),
+            EmulatorType::Standard,
+            Some(format!("Instance {}", &id[..8])),
+            Some("basic-6502".to_string()),
+            Some(vec!["test".to_string()]),
+        );
+        instance.id = id.clone();
+        instance.state = InstanceState::Running;
+        instance
+    }).collect();
+    
+    record_api_request("GET", "/instances", 200, timer.elapsed());
+    Ok(warp::reply::json(&instances))
+}
+
+async fn get_instance_handler(
+    instance_id: String,
+    user: User,
+    emulators: EmulatorMap
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let emulators_lock = lock_emulators(&emulators);
+    if emulators_lock.contains_key(&instance_id) {
+        let mut instance = EmulatorInstance::new(
+            user.id.clone(),
+            EmulatorType::Standard,
+            Some(format!("Instance {}", &instance_id[..8])),
+            Some("basic-6502".to_string()),
+            Some(vec!["test".to_string()]),
+        );
+        instance.id = instance_id.clone();
+        instance.state = InstanceState::Running;
+        
+        record_api_request("GET", &format!("/instances/{}", instance_id), 200, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&instance),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        record_api_request("GET", &format!("/instances/{}", instance_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Instance not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+async fn start_instance_handler(
+    instance_id: String,
+    _user: User,
+    emulators: EmulatorMap
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let emulators_lock = lock_emulators(&emulators);
+    if emulators_lock.contains_key(&instance_id) {
+        record_api_request("POST", &format!("/instances/{}/start", instance_id), 200, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({"message": "Instance started", "state": "Running"})),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        record_api_request("POST", &format!("/instances/{}/start", instance_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Instance not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+async fn stop_instance_handler(
+    instance_id: String,
+    _user: User,
+    emulators: EmulatorMap
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let emulators_lock = lock_emulators(&emulators);
+    if emulators_lock.contains_key(&instance_id) {
+        record_api_request("POST", &format!("/instances/{}/stop", instance_id), 200, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({"message": "Instance stopped", "state": "Stopped"})),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        record_api_request("POST", &format!("/instances/{}/stop", instance_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Instance not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+async fn pause_instance_handler(
+    instance_id: String,
+    _user: User,
+    emulators: EmulatorMap
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let emulators_lock = lock_emulators(&emulators);
+    if emulators_lock.contains_key(&instance_id) {
+        record_api_request("POST", &format!("/instances/{}/pause", instance_id), 200, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({"message": "Instance paused", "state": "Paused"})),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        record_api_request("POST", &format!("/instances/{}/pause", instance_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Instance not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+async fn reset_usage_handler(
+    instance_id: String,
+    user: User,
+    emulators: EmulatorMap
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let mut emulators_lock = lock_emulators(&emulators);
+    match emulators_lock.get_mut(&instance_id) {
+        Some(emulator) if !emulator.can_user_access(&user.id) => {
+            record_api_request("POST", "/instances/:id/usage/reset", 403, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Access denied",
+                })),
+                warp::http::StatusCode::FORBIDDEN,
+            ))
+        }
+        Some(emulator) => {
+            emulator.instance.reset_usage_stats();
+            record_api_request("POST", "/instances/:id/usage/reset", 200, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "message": "Usage stats reset",
+                    "budget_remaining": emulator.instance.budget_remaining(),
+                })),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        None => {
+            record_api_request("POST", "/instances/:id/usage/reset", 404, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Instance not found",
+                })),
+                warp::http::StatusCode::NOT_FOUND,
+            ))
+        }
+    }
+}
+
+// ========== ENTERPRISE SNAPSHOT HANDLERS ==========
+
+async fn create_snapshot_handler(
+    emulator_id: String,
+    request: CreateSnapshotRequest,
+    user: User,
+    emulators: EmulatorMap,
+    snapshots: SnapshotStore
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    // Get emulator
+    let emulators_lock = lock_emulators(&emulators);
+    let emulator = match emulators_lock.get(&emulator_id) {
+        Some(e) => e,
+        None => {
+            record_api_request("POST", &format!("/emulator/{}/snapshots", emulator_id), 404, timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": EMULATOR_NOT_FOUND,
+                })),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+    
+    // Create snapshot
+    let mut snapshot = EmulatorSnapshot::create_from_emulator(
+        request.name.clone(),
+        request.description.unwrap_or_default(),
+        emulator_id.clone(),
+        user.id.clone(),
+        &emulator.cpu,
+        &emulator.memory,
+        "6502".to_string(),
+        Some("basic-6502".to_string()),
+        CheckpointReason::Manual,
+        emulator.cycles,
+        0,
+        request.tags.unwrap_or_default(),
+    );
+    let snapshot_id = snapshot.id.clone();
+    
+    // Store snapshot
+    {
+        let mut snapshots_lock = snapshots.lock().unwrap();
+        if request.dedup.unwrap_or(false) {
+            snapshot.deduplicate(&snapshots_lock);
+        }
+        snapshots_lock.insert(snapshot_id.clone(), snapshot.clone());
+    }
+    
+    record_api_request("POST", &format!("/emulator/{}/snapshots", emulator_id), 201, timer.elapsed());
+    Ok(warp::reply::with_status(
+        warp::reply::json(&snapshot),
+        warp::http::StatusCode::CREATED,
+    ))
+}
+
+async fn list_snapshots_handler(
+    emulator_id: String,
+    user: User,
+    snapshots: SnapshotStore
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let snapshots_lock = snapshots.lock().unwrap();
+    let emulator_snapshots: Vec<&EmulatorSnapshot> = snapshots_lock
+        .values()
+        .filter(|s| s.emulator_id == emulator_id && s.owner_id == user.id)
+        .collect();
+    
+    let response = SnapshotListResponse {
+        snapshots: emulator_snapshots.iter().map(|s| s.get_summary()).collect(),
+        total_count: emulator_snapshots.len(),
+        total_size_bytes: emulator_snapshots.iter().map(|s| s.size_bytes).sum(),
+    };
+    
+    record_api_request("GET", &format!("/emulator/{}/snapshots", emulator_id), 200, timer.elapsed());
+    Ok(warp::reply::json(&response))
+}
+
+async fn get_snapshot_handler(
+    snapshot_id: String,
+    user: User,
+    snapshots: SnapshotStore
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let snapshots_lock = snapshots.lock().unwrap();
+    if let Some(snapshot) = snapshots_lock.get(&snapshot_id) {
+        if snapshot.can_user_access(&user.id, user.has_permission(&Permission::Admin)) {
+            record_api_request("GET", &format!("/snapshots/{}", snapshot_id), 200, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(snapshot),
+                warp::http::StatusCode::OK,
+            ))
+        } else {
+            record_api_request("GET", &format!("/snapshots/{}", snapshot_id), 403, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Access denied",
+                })),
+                warp::http::StatusCode::FORBIDDEN,
+            ))
+        }
+    } else {
+        record_api_request("GET", &format!("/snapshots/{}", snapshot_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Snapshot not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+async fn restore_snapshot_handler(
+    snapshot_id: String,
+    request: RestoreSnapshotRequest,
+    user: User,
+    emulators: EmulatorMap,
+    snapshots: SnapshotStore
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    // Get snapshot
+    let snapshots_lock = snapshots.lock().unwrap();
+    let snapshot = match snapshots_lock.get(&snapshot_id) {
+        Some(s) if s.can_user_access(&user.id, user.has_permission(&Permission::Admin)) => s,
+        Some(_) => {
+            record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 403, timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Access denied",
+                })),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+        None => {
+            record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 404, timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Snapshot not found",
+                })),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+    
+    // Get emulator and restore. By default the snapshot goes back into the
+    // emulator it was taken from, but it can be restored into any emulator
+    // the caller owns.
+    let target_id = request.target_emulator_id.unwrap_or_else(|| snapshot.emulator_id.clone());
+    let mut emulators_lock = lock_emulators(&emulators);
+    if let Some(emulator) = emulators_lock.get_mut(&target_id) {
+        if !emulator.can_user_access(&user.id) {
+            record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 403, timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Access denied",
+                })),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+        
+        if let Err(e) = emulator.restore_snapshot(snapshot, &snapshots_lock, request.restore_mode) {
+            record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 500, timer.elapsed());
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": format!("Failed to restore snapshot: {}", e),
+                })),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+        
+        record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 200, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "message": "Snapshot restored",
+                "snapshot_id": snapshot_id,
+                "emulator_id": target_id,
+            })),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        record_api_request("POST", &format!("/snapshots/{}/restore", snapshot_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": EMULATOR_NOT_FOUND,
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+async fn delete_snapshot_handler(
+    snapshot_id: String,
+    user: User,
+    snapshots: SnapshotStore
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let timer = Timer::new();
+    
+    let mut snapshots_lock = snapshots.lock().unwrap();
+    if let Some(snapshot) = snapshots_lock.get(&snapshot_id) {
+        if snapshot.can_user_access(&user.id, user.has_permission(&Permission::Admin)) {
+            remove_snapshot(&mut snapshots_lock, &snapshot_id);
+            record_api_request("DELETE", &format!("/snapshots/{}", snapshot_id), 200, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({"message": "Snapshot deleted"})),
+                warp::http::StatusCode::OK,
+            ))
+        } else {
+            record_api_request("DELETE", &format!("/snapshots/{}", snapshot_id), 403, timer.elapsed());
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "error": "Access denied",
+                })),
+                warp::http::StatusCode::FORBIDDEN,
+            ))
+        }
+    } else {
+        record_api_request("DELETE", &format!("/snapshots/{}", snapshot_id), 404, timer.elapsed());
+        Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": "Snapshot not found",
+            })),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{IoHandler, RegionKind};
+    use crate::instance_types::RomSegment;
+    use crate::cpu::{CARRY_FLAG, ZERO_FLAG};
+    
+    struct NullDevice;
+    
+    impl IoHandler for NullDevice {
+        fn read(&self, _address: u16) -> u8 {
+            0xEA
+        }
+        
+        fn write(&mut self, _address: u16, _value: u8) {}
+    }
+    
+    fn test_user(id: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: id.to_string(),
+            email: format!("{}@localhost", id),
+            password_hash: String::new(),
+            api_keys: Vec::new(),
+            quota: Default::default(),
+            created_at: chrono::Utc::now(),
+            is_active: true,
+        }
+    }
+    
+    fn owned_emulator(owner: &str) -> Emulator {
+        Emulator::new_with_instance(EmulatorInstance::new(
+            owner.to_string(),
+            EmulatorType::Standard,
+            None,
+            None,
+            None,
+        ))
+    }
+    
+    #[tokio::test]
+    async fn test_restore_snapshot_into_different_emulator() {
+        let user = test_user("alice");
+        let mut source = owned_emulator("alice");
+        source.load_program(0x8000, &[
+            0xA9, 0x42,       // LDA #$42
+            0x8D, 0x00, 0x02, // STA $0200
+            0xA2, 0x07,       // LDX #$07
+            0x00,             // BRK
+        ]);
+        source.memory.write_u16(0xFFFC, 0x8000);
+        source.reset();
+        source.execute_steps(3);
+        
+        let snapshot = EmulatorSnapshot::create_from_emulator(
+            "checkpoint".to_string(),
+            String::new(),
+            "source".to_string(),
+            user.id.clone(),
+            &source.cpu,
+            &source.memory,
+            "6502".to_string(),
+            None,
+            CheckpointReason::Manual,
+            source.cycles,
+            0,
+            Vec::new(),
+        );
+        let snapshot_id = snapshot.id.clone();
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("source".to_string(), source);
+        emulators.lock().unwrap().insert("fresh".to_string(), owned_emulator("alice"));
+        let snapshots: SnapshotStore = Arc::new(Mutex::new(HashMap::new()));
+        snapshots.lock().unwrap().insert(snapshot_id.clone(), snapshot);
+        
+        let request = RestoreSnapshotRequest {
+            snapshot_id: snapshot_id.clone(),
+            force: None,
+            target_emulator_id: Some("fresh".to_string()),
+            restore_mode: RestoreMode::default(),
+        };
+        let reply = restore_snapshot_handler(snapshot_id, request, user, emulators.clone(), snapshots)
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+        
+        let emulators_lock = emulators.lock().unwrap();
+        let source = &emulators_lock["source"];
+        let fresh = &emulators_lock["fresh"];
+        assert_eq!(fresh.get_state().a, 0x42);
+        assert_eq!(fresh.get_state().x, 0x07);
+        assert_eq!(fresh.get_state().pc, 0x8007);
+        assert_eq!(fresh.get_state().cycles, source.get_state().cycles);
+        assert_eq!(fresh.get_memory_dump(), source.get_memory_dump());
+    }
+    
+    #[tokio::test]
+    async fn test_system_snapshot_is_admin_only() {
+        let emulator = Emulator::new();
+        // No authenticated user to own it
+        let snapshot = EmulatorSnapshot::create_from_emulator(
+            "local".to_string(),
+            String::new(),
+            "local-1".to_string(),
+            String::new(),
+            &emulator.cpu,
+            &emulator.memory,
+            "6502".to_string(),
+            None,
+            CheckpointReason::Manual,
+            0,
+            0,
+            Vec::new(),
+        );
+        assert_eq!(snapshot.owner_id, crate::snapshots::SYSTEM_OWNER);
+        let snapshot_id = snapshot.id.clone();
+        let snapshots: SnapshotStore = Arc::new(Mutex::new(HashMap::new()));
+        snapshots.lock().unwrap().insert(snapshot_id.clone(), snapshot);
+        
+        let mut admin = test_user("root");
+        admin.create_api_key("admin".to_string(), vec![Permission::Admin], None);
+        let reply = get_snapshot_handler(snapshot_id.clone(), admin, snapshots.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+        
+        let reply = get_snapshot_handler(snapshot_id.clone(), test_user("bob"), snapshots.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::FORBIDDEN);
+        let reply = delete_snapshot_handler(snapshot_id, test_user("bob"), snapshots.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::FORBIDDEN);
+    }
+    
+    #[tokio::test]
+    async fn test_restore_snapshot_rejects_foreign_target() {
+        let user = test_user("alice");
+        let source = owned_emulator("alice");
+        let snapshot = EmulatorSnapshot::create_from_emulator(
+            "checkpoint".to_string(),
+            String::new(),
+            "source".to_string(),
+            user.id.clone(),
+            &source.cpu,
+            &source.memory,
+            "6502".to_string(),
+            None,
+            CheckpointReason::Manual,
+            0,
+            0,
+            Vec::new(),
+        );
+        let snapshot_id = snapshot.id.clone();
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("bobs".to_string(), owned_emulator("bob"));
+        let snapshots: SnapshotStore = Arc::new(Mutex::new(HashMap::new()));
+        snapshots.lock().unwrap().insert(snapshot_id.clone(), snapshot);
+        
+        let request = RestoreSnapshotRequest {
+            snapshot_id: snapshot_id.clone(),
+            force: None,
+            target_emulator_id: Some("bobs".to_string()),
+            restore_mode: RestoreMode::default(),
+        };
+        let reply = restore_snapshot_handler(snapshot_id, request, user, emulators, snapshots)
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::FORBIDDEN);
+    }
+    
+    #[tokio::test]
+    async fn test_long_execution_does_not_starve_other_requests() {
+        let mut busy = Emulator::new();
+        busy.load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+        busy.memory.write_u16(0xFFFC, 0x8000);
+        busy.reset();
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("busy".to_string(), busy);
+        emulators.lock().unwrap().insert("idle".to_string(), Emulator::new());
+        
+        // The default test runtime is single-threaded, so the short request
+        // can only make progress if the long run yields
+        let long_run = tokio::spawn(execute_handler(
+            "busy".to_string(),
+            ExecuteSteps { steps: EXECUTE_CHUNK_STEPS * 20, loop_threshold: None, memory_diff: false },
+            emulators.clone(),
+        ));
+        tokio::task::yield_now().await;
+        
+        let start = std::time::Instant::now();
+        get_state_handler("idle".to_string(), None, emulators.clone()).await.unwrap();
+        let waited = start.elapsed();
+        
+        assert!(!long_run.is_finished(), "short request only ran after the long run finished");
+        assert!(waited < std::time::Duration::from_millis(250), "short request waited {:?}", waited);
+        
+        long_run.await.unwrap().unwrap();
+        assert_eq!(emulators.lock().unwrap()["busy"].cycles, (EXECUTE_CHUNK_STEPS * 20) as u64);
+    }
+    
+    #[tokio::test]
+    async fn test_execute_chunked_matches_execute_steps() {
+        let mut emulator = Emulator::new();
+        emulator.load_example(crate::programs::find_example("fibonacci").unwrap());
+        let expected = {
+            let mut copy = Emulator::new();
+            copy.load_example(crate::programs::find_example("fibonacci").unwrap());
+            copy.execute_steps(1000)
+        };
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("fib".to_string(), emulator);
+        
+        let result = execute_chunked(&emulators, "fib", 1000).await.unwrap();
+        assert_eq!(result.steps_executed, expected.steps_executed);
+        assert_eq!(result.stop_reason, StopReason::Halted);
+        assert!(execute_chunked(&emulators, "missing", 10).await.is_none());
+    }
+    
+    async fn reply_json(reply: impl warp::Reply) -> serde_json::Value {
+        let body = warp::hyper::body::to_bytes(reply.into_response().into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+    
+    fn single_emulator(id: &str) -> EmulatorMap {
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert(id.to_string(), Emulator::new());
+        emulators
+    }
+    
+    #[tokio::test]
+    async fn test_load_and_run_computes_result() {
+        let emulators = single_emulator("calc");
+        let request = LoadAndRun {
+            address: 0x8000,
+            data: vec![
+                0xA9, 0x05,       // LDA #$05
+                0x18,             // CLC
+                0x69, 0x07,       // ADC #$07
+                0x8D, 0x00, 0x02, // STA $0200
+                0x00,             // BRK
+            ],
+            max_steps: 100,
+            set_reset_vector: true,
+            memory_diff: false,
+        };
+        
+        let json = reply_json(load_and_run_handler("calc".to_string(), request, emulators.clone()).await.unwrap()).await;
+        
+        assert_eq!(json["success"], true);
+        assert_eq!(json["data"]["halted"], true);
+        assert_eq!(json["data"]["steps_executed"], 5);
+        assert_eq!(json["data"]["final_state"]["a"], 12);
+        let emulators_lock = emulators.lock().unwrap();
+        assert_eq!(emulators_lock["calc"].read_memory(0x0200, 1), vec![12]);
+        assert_eq!(emulators_lock["calc"].memory.read_u16(0xFFFC), 0x8000);
+    }
+    
+    #[tokio::test]
+    async fn test_load_and_run_validates_request() {
+        let emulators = single_emulator("calc");
+        let past_end = LoadAndRun { address: 0xFFFF, data: vec![0xEA, 0x00], max_steps: 10, set_reset_vector: false, memory_diff: false };
+        let too_many_steps = LoadAndRun { address: 0x8000, data: vec![0x00], max_steps: MAX_LOAD_AND_RUN_STEPS + 1, set_reset_vector: false, memory_diff: false };
+        
+        for request in [past_end, too_many_steps] {
+            let response = warp::Reply::into_response(load_and_run_handler("calc".to_string(), request, emulators.clone()).await.unwrap());
+            assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+            assert_eq!(reply_json(response).await["success"], false);
+        }
+        
+        // Nothing was loaded
+        assert_eq!(emulators.lock().unwrap()["calc"].read_memory(0x8000, 1), vec![0]);
+    }
+    
+    #[tokio::test]
+    async fn test_new_emulator_starts_at_default_entry_point() {
+        assert_eq!(Emulator::new().get_state().pc, DEFAULT_RESET_VECTOR);
+        assert_ne!(DEFAULT_RESET_VECTOR, 0x0000);
+        
+        let config = Arc::new(ServerConfig::default());
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        
+        let reply = create_emulator_handler(CreateEmulatorQuery { reset_vector: None, memory_seed: None, tags: None }, None, config.clone(), emulators.clone())
+            .await
+            .unwrap();
+        assert_eq!(reply_json(reply).await["data"]["cpu"]["pc"], DEFAULT_RESET_VECTOR);
+        
+        let reply = create_emulator_handler(CreateEmulatorQuery { reset_vector: Some(0xC000), memory_seed: None, tags: None }, None, config, emulators)
+            .await
+            .unwrap();
+        assert_eq!(reply_json(reply).await["data"]["cpu"]["pc"], 0xC000);
+    }
+    
+    #[tokio::test]
+    async fn test_list_emulators_filters_by_tag() {
+        let config = Arc::new(ServerConfig::default());
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        let mut ids = Vec::new();
+        for tags in ["experiment-a", "experiment-b, nightly", "experiment-a,nightly"] {
+            let query = CreateEmulatorQuery { reset_vector: None, memory_seed: None, tags: Some(tags.to_string()) };
+            let reply = create_emulator_handler(query, None, config.clone(), emulators.clone()).await.unwrap();
+            ids.push(reply_json(reply).await["data"]["id"].as_str().unwrap().to_string());
+        }
+        
+        let listed = |body: serde_json::Value| {
+            let mut ids: Vec<String> = body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["id"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+        let mut expected = vec![ids[0].clone(), ids[2].clone()];
+        expected.sort();
+        
+        let reply = list_emulators_handler(ListEmulatorsQuery { tag: Some("experiment-a".to_string()) }, emulators.clone()).await.unwrap();
+        assert_eq!(listed(reply_json(reply).await), expected);
+        
+        let reply = list_emulators_handler(ListEmulatorsQuery { tag: Some("nightly".to_string()) }, emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+        assert!(body["data"][0]["tags"].as_array().unwrap().contains(&serde_json::json!("nightly")));
+        
+        let reply = list_emulators_handler(ListEmulatorsQuery { tag: None }, emulators).await.unwrap();
+        assert_eq!(listed(reply_json(reply).await).len(), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_global_emulator_cap() {
+        let config = Arc::new(ServerConfig { max_emulators: 2, ..Default::default() });
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        
+        for _ in 0..2 {
+            let reply = create_emulator_handler(CreateEmulatorQuery { reset_vector: None, memory_seed: None, tags: None }, None, config.clone(), emulators.clone()).await.unwrap();
+            assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+        }
+        let reply = create_emulator_handler(CreateEmulatorQuery { reset_vector: None, memory_seed: None, tags: None }, None, config.clone(), emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(emulators.lock().unwrap().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_per_key_emulator_cap() {
+        let mut user = test_user("alice");
+        let raw_key = user.create_api_key("limited".to_string(), vec![], None).key;
+        user.api_keys[0].rate_limit.max_concurrent_emulators = 1;
+        let users = UserStore::default();
+        users.lock().unwrap().insert(user.id.clone(), user.clone());
+        
+        // The key is picked up from the Authorization header
+        let api_key = warp::test::request()
+            .header("authorization", format!("ApiKey {}", raw_key))
+            .filter(&crate::auth::with_api_key(users.clone()))
+            .await
+            .unwrap()
+            .expect("API key should be recognised");
+        
+        let config = Arc::new(ServerConfig::default());
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        let templates = Arc::new(Mutex::new(HashMap::new()));
+        init_default_templates(templates.clone());
+        let request = || CreateInstanceRequest {
+            template_id: None,
+            emulator_type: EmulatorType::Standard,
+            name: None,
+            tags: None,
+            auto_start: None,
+            auto_recover: None,
+        };
+        
+        let reply = create_instance_handler(request(), user.clone(), Some(api_key.clone()), config.clone(), emulators.clone(), templates.clone())
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::CREATED);
+        
+        let reply = create_instance_handler(request(), user.clone(), Some(api_key), config.clone(), emulators.clone(), templates.clone())
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::TOO_MANY_REQUESTS);
+        
+        // Other credentials aren't affected by the key's limit
+        let reply = create_instance_handler(request(), user, None, config, emulators.clone(), templates)
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::CREATED);
+        assert_eq!(emulators.lock().unwrap().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_compare_reports_diverging_store() {
+        let program = [
+            0xA9, 0x42,       // LDA #$42
+            0x8D, 0x00, 0x02, // STA $0200
+        ];
+        let mut left = owned_emulator("alice");
+        let mut right = owned_emulator("alice");
+        for emulator in [&mut left, &mut right] {
+            emulator.load_program(0x8000, &program);
+            emulator.memory.write_u16(0xFFFC, 0x8000);
+            emulator.reset();
+        }
+        left.execute_steps(2);
+        right.execute_steps(1);
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("left".to_string(), left);
+        emulators.lock().unwrap().insert("right".to_string(), right);
+        
+        let reply = compare_emulators_handler("left".to_string(), "right".to_string(), test_user("alice"), emulators.clone())
+            .await
+            .unwrap();
+        let json = reply_json(reply).await;
+        
+        assert_eq!(json["memory_differences"], 1);
+        assert_eq!(json["memory"][0]["address"], 0x0200);
+        assert_eq!(json["memory"][0]["left"], 0x42);
+        assert_eq!(json["memory"][0]["right"], 0x00);
+        assert_eq!(json["truncated"], false);
+        let registers: Vec<&str> = json["registers"].as_array().unwrap().iter()
+            .map(|r| r["register"].as_str().unwrap())
+            .collect();
+        assert_eq!(registers, vec!["pc", "cycles"]);
+        
+        let reply = compare_emulators_handler("left".to_string(), "right".to_string(), test_user("bob"), emulators)
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::FORBIDDEN);
+    }
+    
+    #[test]
+    fn test_compare_caps_output() {
+        let left = Emulator::new();
+        let mut right = Emulator::new();
+        for address in 0x1000..0x1010 {
+            right.write_memory(address, 0xFF);
+        }
+        
+        let comparison = left.compare(&right, 4);
+        
+        assert_eq!(comparison.memory.len(), 4);
+        assert_eq!(comparison.memory_differences, 16);
+        assert!(comparison.truncated);
+        assert!(comparison.registers.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_disasm_without_emulator() {
+        let bytes = [0xA9, 0x42, 0x8D, 0x00, 0x02, 0x00]; // LDA #$42, STA $0200, BRK
+        let request = DisassembleRequest {
+            address: 0x8000,
+            data_base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+            variant: CpuVariant::Nmos6502,
+            format: DisassemblyFormat::Structured,
+        };
+        
+        let json = reply_json(disasm_handler(request).await.unwrap()).await;
+        
+        assert_eq!(json["success"], true);
+        let listing = json["data"].as_array().unwrap();
+        let mnemonics: Vec<&str> = listing.iter().map(|i| i["mnemonic"].as_str().unwrap()).collect();
+        assert_eq!(mnemonics, vec!["LDA", "STA", "BRK"]);
+        assert_eq!(listing[1]["address"], 0x8002);
+        assert_eq!(listing[1]["text"], "STA $0200");
+        
+        let request = DisassembleRequest {
+            address: 0x8000,
+            data_base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+            variant: CpuVariant::Nmos6502,
+            format: DisassemblyFormat::Listing,
+        };
+        let json = reply_json(disasm_handler(request).await.unwrap()).await;
+        assert_eq!(json["data"], serde_json::json!(["8000: A9 42    LDA #$42", "8002: 8D 00 02 STA $0200", "8005: 00       BRK"]));
+    }
+    
+    #[tokio::test]
+    async fn test_disasm_rejects_bad_base64() {
+        let request = DisassembleRequest {
+            address: 0,
+            data_base64: "not base64!".to_string(),
+            variant: CpuVariant::default(),
+            format: DisassemblyFormat::default(),
+        };
+        
+        let response = warp::Reply::into_response(disasm_handler(request).await.unwrap());
+        
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(reply_json(response).await["success"], false);
+    }
+    
+    #[test]
+    fn test_memory_map_lists_regions() {
+        let mut emulator = Emulator::new();
+        emulator.memory.add_read_only_region("rom", 0xC000, 0xFFFF);
+        emulator.memory.add_io_handler("device", 0x6000, 0x600F, Box::new(NullDevice));
+        
+        let map = emulator.memory.regions();
+        
+        assert_eq!(map, vec![
+            MemoryRegion { kind: RegionKind::Io, name: "device".to_string(), start: 0x6000, end: 0x600F },
+            MemoryRegion { kind: RegionKind::ReadOnly, name: "rom".to_string(), start: 0xC000, end: 0xFFFF },
+        ]);
+        
+        // The regions are live, not just descriptive
+        emulator.write_memory(0xC000, 0x42);
+        assert_eq!(emulator.read_memory(0xC000, 1), vec![0x00]);
+        assert_eq!(emulator.read_memory(0x6005, 1), vec![0xEA]);
+    }
+    
+    #[test]
+    fn test_loop_detection_stops_self_jump() {
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &[
+            0xA9, 0x01,       // LDA #$01
+            0x4C, 0x02, 0x80, // loop: JMP loop
+        ]);
+        emulator.memory.write_u16(0xFFFC, 0x8000);
+        emulator.reset();
+        emulator.loop_detection = Some(5);
+        
+        let result = emulator.execute_steps(1000);
+        
+        assert_eq!(result.stop_reason, StopReason::InfiniteLoop { pc: 0x8002 });
+        assert_eq!(result.stop_reason.to_string(), "suspected infinite loop at $8002");
+        assert_eq!(result.steps_executed, 6); // LDA plus five identical JMPs
+        assert!(!result.halted);
+    }
+    
+    #[test]
+    fn test_execution_counts_memory_accesses() {
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &[
+            0xA2, 0x00,       // LDX #$00
+            0x9D, 0x00, 0x02, // loop: STA $0200,X
+            0xE8,             // INX
+            0xE0, 0x10,       // CPX #$10
+            0xD0, 0xF8,       // BNE loop
+            0x00,             // BRK
+        ]);
+        emulator.memory.write_u16(0xFFFC, 0x8000);
+        emulator.reset();
+        
+        let result = emulator.execute_steps(1000);
+        
+        assert!(result.halted);
+        assert_eq!(result.memory_writes, 16); // one per STA
+        // Fetches: LDX 2, then 16 x (STA 3 + INX 1 + CPX 2 + BNE 2), then BRK 1
+        assert_eq!(result.memory_reads, 2 + 16 * 8 + 1);
+    }
+    
+    #[test]
+    fn test_trace_records_changed_flags() {
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &[
+            0xA9, 0xFF, // LDA #$FF
+            0x18,       // CLC
+            0x69, 0x01, // ADC #$01
+        ]);
+        emulator.memory.write_u16(0xFFFC, 0x8000);
+        emulator.reset();
+        emulator.trace = Some(TraceBuffer::new(16));
+        
+        emulator.execute_steps(3);
+        
+        let entries = emulator.trace.as_ref().unwrap().entries();
+        assert_eq!(entries.len(), 3);
+        let adc = &entries[2];
+        assert_eq!(adc.pc, 0x8003);
+        assert_eq!(adc.instruction, "ADC");
+        assert_eq!(adc.a, 0x00);
+        // $FF + 1 carries out and gives zero; N was set by LDA and is now clear
+        assert_eq!(adc.flags_changed, vec!["N", "Z", "C"]);
+        assert_eq!(adc.status_after & !adc.status_before, ZERO_FLAG | CARRY_FLAG);
+        // CLC changed nothing since carry was already clear
+        assert!(entries[1].flags_changed.is_empty());
+    }
+    
+    #[test]
+    fn test_trace_is_opt_in_and_bounded() {
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+        emulator.memory.write_u16(0xFFFC, 0x8000);
+        emulator.reset();
+        
+        emulator.execute_steps(10);
+        assert!(emulator.trace.is_none());
+        
+        emulator.trace = Some(TraceBuffer::new(4));
+        emulator.execute_steps(10);
+        let entries = emulator.trace.as_ref().unwrap().entries();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[3].step, 19);
+    }
+    
+    #[test]
+    fn test_loop_detection_is_opt_in() {
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+        emulator.memory.write_u16(0xFFFC, 0x8000);
+        emulator.reset();
+        
+        let result = emulator.execute_steps(100);
+        
+        assert_eq!(result.stop_reason, StopReason::StepLimit);
+        assert_eq!(result.steps_executed, 100);
+    }
+    
+    #[test]
+    fn test_loop_detection_ignores_progressing_loop() {
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &[
+            0xE8,             // loop: INX
+            0x4C, 0x00, 0x80, // JMP loop
+        ]);
+        emulator.memory.write_u16(0xFFFC, 0x8000);
+        emulator.reset();
+        emulator.loop_detection = Some(2);
+        
+        let result = emulator.execute_steps(50);
+        
+        assert_eq!(result.stop_reason, StopReason::StepLimit);
+    }
+    
+    #[tokio::test]
+    async fn test_instruction_budget_refuses_until_reset() {
+        let mut emulator = owned_emulator("alice");
+        emulator.instance.specs.instruction_budget = Some(3);
+        emulator.load_program(0x8000, &[0xEA; 16]); // NOPs
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("metered".to_string(), emulator);
+        
+        let reply = execute_handler(
+            "metered".to_string(),
+            ExecuteSteps { steps: 10, loop_threshold: None, memory_diff: false },
+            emulators.clone(),
+        ).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["steps_executed"], 3);
+        assert_eq!(body["data"]["stop_reason"], "BudgetExhausted");
+        
+        let reply = step_handler("metered".to_string(), None, emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::PAYMENT_REQUIRED);
+        let reply = execute_handler(
+            "metered".to_string(),
+            ExecuteSteps { steps: 1, loop_threshold: None, memory_diff: false },
+            emulators.clone(),
+        ).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::PAYMENT_REQUIRED);
+        
+        let reply = reset_usage_handler("metered".to_string(), test_user("alice"), emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+        
+        let reply = step_handler("metered".to_string(), None, emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["success"], true);
+        assert_eq!(body["data"]["cpu"]["pc"], 0x8004);
+    }
+    
+    #[tokio::test]
+    async fn test_state_includes_next_instruction() {
+        let emulators = single_emulator("debug");
+        {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("debug").unwrap();
+            emulator.load_program(0x8000, &[
+                0xA9, 0x42, // LDA #$42
+                0x02,       // not a 6502 opcode
+            ]);
+            emulator.reset();
+        }
+        
+        let body = reply_json(get_state_handler("debug".to_string(), None, emulators.clone()).await.unwrap()).await;
+        assert_eq!(body["data"]["cpu"]["next_instruction"]["text"], "LDA #$42");
+        assert_eq!(body["data"]["cpu"]["next_instruction"]["address"], 0x8000);
+        
+        let body = reply_json(step_handler("debug".to_string(), None, emulators.clone()).await.unwrap()).await;
+        assert_eq!(body["data"]["cpu"]["next_instruction"]["text"], ".byte $02");
+    }
+    
+    #[tokio::test]
+    async fn test_template_with_multiple_rom_segments() {
+        let templates = Arc::new(Mutex::new(HashMap::new()));
+        init_default_templates(templates.clone());
+        let mut template = templates.lock().unwrap()["commodore-64-like"].clone();
+        template.id = "two-roms".to_string();
+        template.rom_segments = vec![
+            RomSegment {
+                name: "basic".to_string(),
+                base_address: 0xA000,
+                bytes: vec![0x11; 0x2000],
+                read_only: true,
+            },
+            RomSegment {
+                name: "kernal".to_string(),
+                base_address: 0xE000,
+                bytes: vec![0x22; 0x2000],
+                read_only: true,
+            },
+        ];
+        templates.lock().unwrap().insert(template.id.clone(), template);
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        let request = CreateInstanceRequest {
+            template_id: Some("two-roms".to_string()),
+            emulator_type: EmulatorType::Standard,
+            name: None,
+            tags: None,
+            auto_start: None,
+            auto_recover: None,
+        };
+        let reply = create_instance_handler(request, test_user("alice"), None, Arc::new(ServerConfig::default()), emulators.clone(), templates)
+            .await
+            .unwrap();
+        let instance = reply_json(reply).await;
+        
+        let mut emulators_lock = emulators.lock().unwrap();
+        let emulator = emulators_lock.get_mut(instance["id"].as_str().unwrap()).unwrap();
+        assert_eq!(emulator.memory.read(0xA000), 0x11);
+        assert_eq!(emulator.memory.read(0xBFFF), 0x11);
+        assert_eq!(emulator.memory.read(0xE000), 0x22);
+        assert_eq!(emulator.memory.read(0xFFFF), 0x22);
+        assert_eq!(emulator.memory.read(0xC000), 0x00);
+        
+        emulator.write_memory(0xA000, 0x99);
+        emulator.write_memory(0xE000, 0x99);
+        assert_eq!(emulator.memory.read(0xA000), 0x11);
+        assert_eq!(emulator.memory.read(0xE000), 0x22);
+        assert_eq!(emulator.memory.regions().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_admin_lists_users_demo_forbidden() {
+        let users = UserStore::default();
+        init_default_users(users.clone());
+        let find = |name: &str| users.lock().unwrap().values().find(|u| u.username == name).unwrap().clone();
+        let (admin, demo) = (find("admin"), find("demo"));
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("mine".to_string(), owned_emulator(&demo.id));
+        let snapshots: SnapshotStore = Arc::new(Mutex::new(HashMap::new()));
+        
+        let reply = list_users_handler(admin, users.clone(), emulators.clone(), snapshots.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        let listed = body.as_array().unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[1]["username"], "demo");
+        assert_eq!(listed[1]["emulator_count"], 1);
+        assert!(listed[1].get("password_hash").is_none());
+        assert!(listed[1].get("api_keys").is_none());
+        
+        let reply = list_users_handler(demo, users, emulators, snapshots).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::FORBIDDEN);
+    }
+    
+    #[tokio::test]
+    async fn test_delete_user_cascades() {
+        let users = UserStore::default();
+        let admin = {
+            let mut admin = test_user("root");
+            admin.create_api_key("admin".to_string(), vec![Permission::Admin], None);
+            admin
+        };
+        users.lock().unwrap().insert(admin.id.clone(), admin.clone());
+        users.lock().unwrap().insert("bob".to_string(), test_user("bob"));
+        
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("bob-1".to_string(), owned_emulator("bob"));
+        emulators.lock().unwrap().insert("bob-2".to_string(), owned_emulator("bob"));
+        emulators.lock().unwrap().insert("root-1".to_string(), owned_emulator("root"));
+        
+        let snapshots: SnapshotStore = Arc::new(Mutex::new(HashMap::new()));
+        for owner in ["bob", "root"] {
+            let emulator = owned_emulator(owner);
+            let snapshot = EmulatorSnapshot::create_from_emulator(
+                "checkpoint".to_string(),
+                String::new(),
+                format!("{}-1", owner),
+                owner.to_string(),
+                &emulator.cpu,
+                &emulator.memory,
+                "6502".to_string(),
+                None,
+                CheckpointReason::Manual,
+                0,
+                0,
+                Vec::new(),
+            );
+            snapshots.lock().unwrap().insert(snapshot.id.clone(), snapshot);
+        }
+        
+        let reply = delete_user_handler(
+            "bob".to_string(),
+            DeleteUserQuery { confirm: false },
+            admin.clone(),
+            users.clone(),
+            emulators.clone(),
+            snapshots.clone(),
+        ).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["emulators_deleted"], 2);
+        assert_eq!(body["snapshots_deleted"], 1);
+        
+        assert!(!users.lock().unwrap().contains_key("bob"));
+        assert_eq!(emulators.lock().unwrap().keys().collect::<Vec<_>>(), vec!["root-1"]);
+        assert!(snapshots.lock().unwrap().values().all(|s| s.owner_id == "root"));
+        
+        // Deleting yourself needs explicit confirmation
+        let reply = delete_user_handler(
+            "root".to_string(),
+            DeleteUserQuery { confirm: false },
+            admin,
+            users.clone(),
+            emulators,
+            snapshots,
+        ).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+        assert!(users.lock().unwrap().contains_key("root"));
+    }
+    
+    #[test]
+    fn test_jwt_token_lifetime_follows_config() {
+        let user = test_user("alice");
+        let token = create_jwt_token(&user, 1).unwrap();
+        let claims = crate::auth::verify_jwt_token(&token).unwrap();
+        let expected = chrono::Utc::now().timestamp() + 3600;
+        assert!((claims.exp - expected).abs() <= 5, "exp is {}s from an hour out", claims.exp - expected);
+        assert_eq!(ServerConfig::default().jwt_ttl_hours, DEFAULT_JWT_TTL_HOURS);
+    }
+    
+    #[tokio::test]
+    async fn test_api_key_last_used_is_recorded() {
+        let mut user = test_user("alice");
+        let raw_key = user.create_api_key("laptop".to_string(), vec![], None).key;
+        let users = UserStore::default();
+        users.lock().unwrap().insert(user.id.clone(), user);
+        assert!(users.lock().unwrap()["alice"].api_keys[0].last_used.is_none());
+        
+        let user = warp::test::request()
+            .header("authorization", format!("ApiKey {}", raw_key))
+            .filter(&with_auth(users.clone()))
+            .await
+            .unwrap();
+        assert!(users.lock().unwrap()["alice"].api_keys[0].last_used.is_some());
+        
+        let body = reply_json(list_api_keys_handler(user).await.unwrap()).await;
+        assert!(body[0]["last_used"].is_string());
+    }
+    
+    #[tokio::test]
+    async fn test_malformed_json_body_is_rejected_with_400() {
+        let emulators = single_emulator("target");
+        let route = warp::path!("emulator" / String / "program")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(with_emulators(emulators))
+            .and_then(load_program_handler)
+            .recover(handle_rejection);
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/emulator/target/program")
+            .header("content-type", "application/json")
+            .body("{\"address\": 32768, \"data\": [")
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], "InvalidBody");
+        assert!(body["error"].as_str().unwrap().starts_with("Invalid request body"));
+    }
+    
+    #[tokio::test]
+    async fn test_expiring_key_warning_header() {
+        let mut user = test_user("alice");
+        let expiring = user.create_api_key("expiring".to_string(), vec![], Some(2)).key;
+        let long_lived = user.create_api_key("long-lived".to_string(), vec![], Some(365)).key;
+        let users = UserStore::default();
+        users.lock().unwrap().insert(user.id.clone(), user);
+        
+        let routes = warn_expiring_keys(warp::path::end().map(warp::reply), users, DEFAULT_KEY_EXPIRY_WARNING_DAYS);
+        
+        let response = warp::test::request()
+            .header("authorization", format!("ApiKey {}", expiring))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.headers()["x-key-expires-in-days"], "2");
+        
+        let response = warp::test::request()
+            .header("authorization", format!("ApiKey {}", long_lived))
+            .reply(&routes)
+            .await;
+        assert!(response.headers().get("x-key-expires-in-days").is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_describe_adc_immediate() {
+        let query = || DescribeOpcodeQuery { variant: CpuVariant::Nmos6502 };
+        let reply = describe_opcode_handler("69".to_string(), query()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["name"], "ADC immediate");
+        assert_eq!(body["data"]["flags_affected"], serde_json::json!(["N", "V", "Z", "C"]));
+        assert_eq!(body["data"]["length"], 2);
+        assert_eq!(body["data"]["base_cycles"], 2);
+        
+        let reply = describe_opcode_handler("0x02".to_string(), query()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::NOT_FOUND);
+        let reply = describe_opcode_handler("zz".to_string(), query()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn test_validate_flags_unimplemented_opcode_in_linear_flow() {
+        let emulators = single_emulator("checked");
+        emulators.lock().unwrap().get_mut("checked").unwrap().load_program(0x8000, &[
+            0xA9, 0x01,       // LDA #$01
+            0x8D, 0x01, 0x80, // STA $8001, patching its own code
+            0x0A,             // ASL A, not implemented
+            0x00,             // BRK
+        ]);
+        
+        let reply = validate_handler("checked".to_string(), ValidateQuery { max_bytes: None }, emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["entry_point"], 0x8000);
+        assert_eq!(body["data"]["valid"], false);
+        assert_eq!(body["data"]["instructions_checked"], 3);
+        let issues: Vec<(u64, &str)> = body["data"]["issues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| (i["address"].as_u64().unwrap(), i["kind"].as_str().unwrap()))
+            .collect();
+        assert_eq!(issues, vec![(0x8002, "WriteToCode"), (0x8005, "UnimplementedOpcode")]);
+        
+        // A program that stays within implemented opcodes passes
+        emulators.lock().unwrap().get_mut("checked").unwrap().load_program(0x8000, &[
+            0xA2, 0x03, // LDX #$03
+            0xCA,       // DEX
+            0xD0, 0xFD, // BNE $8002
+            0x00,       // BRK
+        ]);
+        let reply = validate_handler("checked".to_string(), ValidateQuery { max_bytes: None }, emulators).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["valid"], true);
+        assert_eq!(body["data"]["bytes_checked"], 6);
+    }
+    
+    #[tokio::test]
+    async fn test_next_effective_address_of_indirect_indexed_load() {
+        let emulators = single_emulator("debug");
+        {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("debug").unwrap();
+            emulator.load_program(0x8000, &[
+                0xB1, 0x20, // LDA ($20),Y
+                0xE8,       // INX
+            ]);
+            emulator.reset();
+            emulator.memory.write_u16(0x0020, 0x12F0);
+            emulator.cpu.y = 0x15;
+        }
+        
+        let reply = next_effective_address_handler("debug".to_string(), emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["instruction"]["text"], "LDA ($20),Y");
+        assert_eq!(body["data"]["effective_address"], 0x12F0 + 0x15);
+        // Nothing was executed
+        assert_eq!(emulators.lock().unwrap()["debug"].cpu.get_pc(), 0x8000);
+        
+        emulators.lock().unwrap().get_mut("debug").unwrap().step().unwrap();
+        let reply = next_effective_address_handler("debug".to_string(), emulators).await.unwrap();
+        assert!(reply_json(reply).await["data"]["effective_address"].is_null());
+    }
+    
+    #[tokio::test]
+    async fn test_low_pages_show_pushed_value() {
+        let emulators = single_emulator("stack");
+        let sp_before = {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("stack").unwrap();
+            emulator.load_program(0x8000, &[
+                0xA9, 0x42, // LDA #$42
+                0x85, 0x10, // STA $10
+            ]);
+            emulator.reset();
+            emulator.execute_steps(2);
+            let sp = emulator.cpu.get_sp();
+            emulator.cpu.push(&mut emulator.memory, 0x42);
+            sp
+        };
+        
+        let body = reply_json(low_pages_handler("stack".to_string(), emulators).await.unwrap()).await;
+        let data = &body["data"];
+        assert_eq!(data["sp"], sp_before.wrapping_sub(1));
+        assert_eq!(data["stack_page"][sp_before as usize], 0x42);
+        assert_eq!(data["stack_page"].as_array().unwrap().len(), 256);
+        assert_eq!(data["zero_page"][0x10], 0x42);
+    }
+    
+    #[tokio::test]
+    async fn test_pc_histogram_shows_hot_loop() {
+        let emulators = single_emulator("profiled");
+        emulators.lock().unwrap().get_mut("profiled").unwrap().load_program(0x8000, &[
+            0xA2, 0x14,       // LDX #20
+            0xCA,             // loop: DEX
+            0xD0, 0xFD,       // BNE loop
+            0x00,             // BRK
+        ]);
+        
+        let reply = get_pc_histogram_handler("profiled".to_string(), emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["enabled"], false);
+        
+        let config = PcHistogramConfig { enabled: true, capacity: None };
+        configure_pc_histogram_handler("profiled".to_string(), config, emulators.clone()).await.unwrap();
+        {
+            let mut lock = emulators.lock().unwrap();
+            let emulator = lock.get_mut("profiled").unwrap();
+            emulator.reset();
+            emulator.execute_steps(100);
+        }
+        
+        let reply = get_pc_histogram_handler("profiled".to_string(), emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["total"], 42);
+        let entries = body["data"]["entries"].as_array().unwrap();
+        let top: Vec<(u64, u64)> = entries
+            .iter()
+            .map(|e| (e["pc"].as_u64().unwrap(), e["count"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(top, vec![(0x8002, 20), (0x8003, 20), (0x8000, 1), (0x8005, 1)]);
+        
+        emulators.lock().unwrap().get_mut("profiled").unwrap().reset();
+        let report = emulators.lock().unwrap()["profiled"].pc_histogram.as_ref().unwrap().report();
+        assert_eq!(report.total, 0);
+        assert!(report.entries.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_trace_run_returns_each_step() {
+        let emulators = single_emulator("traced");
+        emulators.lock().unwrap().get_mut("traced").unwrap().load_program(0x8000, &[
+            0xA9, 0x01,       // LDA #$01
+            0xAA,             // TAX
+            0xE8,             // INX
+            0x8D, 0x00, 0x02, // STA $0200
+            0xEA,             // NOP
+        ]);
+        
+        let reply = trace_run_handler("traced".to_string(), TraceRun { steps: 5 }, emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        let pcs: Vec<u64> = body["data"]["trace"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["pc"].as_u64().unwrap())
+            .collect();
+        assert_eq!(pcs, vec![0x8000, 0x8002, 0x8003, 0x8004, 0x8007]);
+        assert_eq!(body["data"]["execution"]["final_state"]["pc"], 0x8008);
+        
+        // Tracing is off again afterwards
+        assert!(emulators.lock().unwrap()["traced"].trace.is_none());
+        
+        let reply = trace_run_handler("traced".to_string(), TraceRun { steps: MAX_TRACE_RUN_STEPS + 1 }, emulators).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(reply_json(response).await["success"], false);
+    }
+    
+    #[tokio::test]
+    async fn test_empty_program_load_is_rejected() {
+        let emulators = single_emulator("empty");
+        let request = ProgramLoad { address: 0x8000, data: Vec::new(), entry_point: None };
+        
+        let reply = load_program_handler("empty".to_string(), request, emulators.clone()).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(reply_json(response).await["error"], "No program data");
+        
+        let request = ProgramLoad { address: 0xFFFF, data: vec![0xEA, 0x00], entry_point: None };
+        let reply = load_program_handler("empty".to_string(), request, emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(emulators.lock().unwrap()["empty"].memory.read(0xFFFF), 0);
+    }
+    
+    #[tokio::test]
+    async fn test_memory_checksum() {
+        let emulators = single_emulator("sum");
+        emulators.lock().unwrap().get_mut("sum").unwrap().load_program(0x0200, b"abc");
+        let query = || MemoryChecksumQuery { address: 0x0200, length: 3 };
+        
+        let body = reply_json(memory_checksum_handler("sum".to_string(), query(), emulators.clone()).await.unwrap()).await;
+        // Standard SHA-256 test vector for "abc"
+        assert_eq!(body["data"]["sha256"], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        
+        emulators.lock().unwrap().get_mut("sum").unwrap().write_memory(0x0201, b'B');
+        let body = reply_json(memory_checksum_handler("sum".to_string(), query(), emulators.clone()).await.unwrap()).await;
+        assert_ne!(body["data"]["sha256"], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        
+        let query = MemoryChecksumQuery { address: 0xFFFF, length: 2 };
+        let response = warp::Reply::into_response(memory_checksum_handler("sum".to_string(), query, emulators).await.unwrap());
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(reply_json(response).await["success"], false);
+    }
+    
+    #[tokio::test]
+    async fn test_step_into_irq_stops_at_handler() {
+        let emulators = single_emulator("irq");
+        {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("irq").unwrap();
+            emulator.load_program(0x8000, &[0xEA]); // NOP
+            emulator.load_program(0x9000, &[0x40]); // handler: RTI
+            emulator.memory.write_u16(crate::cpu::IRQ_VECTOR, 0x9000);
+        }
+        
+        // Reset leaves interrupts disabled
+        let body = reply_json(step_into_irq_handler("irq".to_string(), emulators.clone()).await.unwrap()).await;
+        assert_eq!(body["success"], false);
+        
+        emulators.lock().unwrap().get_mut("irq").unwrap().cpu.set_flag(crate::cpu::INTERRUPT_DISABLE, false);
+        let body = reply_json(step_into_irq_handler("irq".to_string(), emulators.clone()).await.unwrap()).await;
+        assert_eq!(body["data"]["cpu"]["pc"], 0x9000);
+        assert_eq!(body["data"]["cpu"]["next_instruction"]["text"], "RTI");
+    }
+    
+    #[tokio::test]
+    async fn test_step_into_irq_masked_is_a_conflict() {
+        let emulators = single_emulator("masked");
+        {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("masked").unwrap();
+            emulator.load_program(0x8000, &[0xEA]); // NOP
+            emulator.memory.write_u16(crate::cpu::IRQ_VECTOR, 0x9000);
+            emulator.cpu.set_flag(crate::cpu::INTERRUPT_DISABLE, true);
+        }
+        let before = emulators.lock().unwrap()["masked"].get_state();
+        
+        let reply = step_into_irq_handler("masked".to_string(), emulators.clone()).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::CONFLICT);
+        assert!(reply_json(response).await["error"].as_str().unwrap().contains("masked"));
+        
+        // Nothing was pushed or stepped
+        let after = emulators.lock().unwrap()["masked"].get_state();
+        assert_eq!((after.pc, after.sp, after.cycles), (before.pc, before.sp, before.cycles));
+    }
+    
+    #[tokio::test]
+    async fn test_instance_types_lists_all_tiers() {
+        let body = reply_json(instance_types_handler().await.unwrap()).await;
+        let rates: Vec<(String, u64)> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| (
+                t["emulator_type"].as_str().unwrap().to_string(),
+                t["specs"]["max_cycles_per_second"].as_u64().unwrap(),
+            ))
+            .collect();
+        
+        assert_eq!(rates, vec![
+            ("Micro".to_string(), 100_000),
+            ("Small".to_string(), 500_000),
+            ("Standard".to_string(), 1_000_000),
+            ("Performance".to_string(), 5_000_000),
+            ("Turbo".to_string(), 10_000_000),
+        ]);
+    }
+    
+    #[tokio::test]
+    async fn test_program_load_with_entry_point_resets_there() {
+        let emulators = single_emulator("entry");
+        let request = ProgramLoad {
+            address: 0xC000,
+            data: vec![0xEA, 0xA9, 0x01], // NOP; entry: LDA #$01
+            entry_point: Some(0xC001),
+        };
+        
+        load_program_handler("entry".to_string(), request, emulators.clone()).await.unwrap();
+        
+        let emulators_lock = emulators.lock().unwrap();
+        assert_eq!(emulators_lock["entry"].cpu.get_pc(), 0xC001);
+        assert_eq!(emulators_lock["entry"].memory.read_u16(0xFFFC), 0xC001);
+    }
+
+    #[tokio::test]
+    async fn test_read_memory_stops_at_end_of_address_space() {
+        let emulators = single_emulator("e1");
+        let data = emulators.lock().unwrap()["e1"].read_memory(0xFFF8, 0x100);
+        assert_eq!(data.len(), 8);
+
+        let config = Arc::new(ServerConfig::default());
+        let reply = read_memory_handler("e1".to_string(), MemoryRead { address: 0xFFF8, length: Some(0x100) }, config.clone(), emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["data"].as_array().unwrap().len(), 8);
+        assert!(body["data"]["note"].as_str().unwrap().contains("8 of 256"));
+
+        let reply = read_memory_handler("e1".to_string(), MemoryRead { address: 0x8000, length: Some(4) }, config, emulators).await.unwrap();
+        let body = reply_json(reply).await;
+        assert!(body["data"]["note"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_memory_is_reproducible() {
+        let config = Arc::new(ServerConfig::default());
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        let mut ids = Vec::new();
+        for seed in [42, 42, 43] {
+            let query = CreateEmulatorQuery { reset_vector: None, memory_seed: Some(seed), tags: None };
+            let reply = create_emulator_handler(query, None, config.clone(), emulators.clone()).await.unwrap();
+            let body = reply_json(reply).await;
+            assert_eq!(body["data"]["cpu"]["pc"], 0x8000);
+            ids.push(body["data"]["id"].as_str().unwrap().to_string());
+        }
+
+        let emulators_lock = emulators.lock().unwrap();
+        let dumps: Vec<Vec<u8>> = ids.iter().map(|id| emulators_lock[id].get_memory_dump()).collect();
+        assert_eq!(dumps[0], dumps[1]);
+        assert_ne!(dumps[0], dumps[2]);
+        assert!(dumps[0][..0x100].iter().any(|&b| b != 0));
+    }
+
+    #[tokio::test]
+    async fn test_opcodes_used_flags_unimplemented() {
+        let emulators = single_emulator("ops");
+        {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("ops").unwrap();
+            // LDA #$01, PHA, LDA #$02, BRK; PHA isn't implemented yet
+            emulator.load_program(0x0200, &[0xA9, 0x01, 0x48, 0xA9, 0x02, 0x00]);
+        }
+
+        let query = OpcodesUsedQuery { address: 0x0200, length: 6 };
+        let body = reply_json(opcodes_used_handler("ops".to_string(), query, emulators.clone()).await.unwrap()).await;
+        assert_eq!(body["data"]["all_implemented"], false);
+        let opcodes = body["data"]["opcodes"].as_array().unwrap();
+        assert_eq!(opcodes.len(), 3);
+        assert_eq!(opcodes[0]["mnemonic"], "LDA");
+        assert_eq!(opcodes[0]["count"], 2);
+        assert_eq!(opcodes[0]["implemented"], true);
+        assert_eq!(opcodes[1]["mnemonic"], "BRK");
+        assert_eq!(opcodes[2]["mnemonic"], "PHA");
+        assert_eq!(opcodes[2]["implemented"], false);
+
+        let query = OpcodesUsedQuery { address: 0x0200, length: 2 };
+        let body = reply_json(opcodes_used_handler("ops".to_string(), query, emulators.clone()).await.unwrap()).await;
+        assert_eq!(body["data"]["all_implemented"], true);
+
+        let query = OpcodesUsedQuery { address: 0xFFFF, length: 2 };
+        let response = warp::Reply::into_response(opcodes_used_handler("ops".to_string(), query, emulators).await.unwrap());
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_reports_progress() {
+        let emulators = single_emulator("stream");
+        emulators.lock().unwrap().get_mut("stream").unwrap().load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+
+        let request = ExecuteStream { steps: 1000, report_every: 100 };
+        let response = execute_stream_handler("stream".to_string(), request, emulators.clone()).await.unwrap();
+        assert_eq!(response.status(), warp::http::StatusCode::OK);
+        let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let frames: Vec<serde_json::Value> = body
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+
+        // Progress at 100..=900, then the result covers the last 100
+        assert_eq!(frames.len(), 10);
+        for (i, frame) in frames[..9].iter().enumerate() {
+            assert_eq!(frame["type"], "progress");
+            assert_eq!(frame["steps_executed"], (i as u64 + 1) * 100);
+        }
+        assert_eq!(frames[9]["type"], "done");
+        assert_eq!(frames[9]["steps_executed"], 1000);
+
+        let request = ExecuteStream { steps: 1000, report_every: 0 };
+        let response = execute_stream_handler("stream".to_string(), request, emulators).await.unwrap();
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_read_memory_length_cap() {
+        let emulators = single_emulator("e1");
+        let config = Arc::new(ServerConfig { max_memory_read: 16, ..Default::default() });
+
+        let reply = read_memory_handler("e1".to_string(), MemoryRead { address: 0x0200, length: Some(16) }, config.clone(), emulators.clone()).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::OK);
+        let body = reply_json(response).await;
+        assert_eq!(body["data"]["data"].as_array().unwrap().len(), 16);
+
+        let reply = read_memory_handler("e1".to_string(), MemoryRead { address: 0x0200, length: Some(17) }, config, emulators).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        let body = reply_json(response).await;
+        assert!(body["error"].as_str().unwrap().contains("limited to 16 bytes"));
+    }
+
+    #[test]
+    fn test_halting_stops_running_instance() {
+        let mut instance = EmulatorInstance::new("u1".to_string(), EmulatorType::Standard, None, None, None);
+        instance.start();
+        let mut emulator = Emulator::new_with_instance(instance);
+        emulator.load_program(0x8000, &[0xA9, 0x01, 0x00]); // LDA #$01, BRK
+
+        emulator.step().unwrap();
+        assert!(matches!(emulator.instance.state, InstanceState::Running));
+        assert!(emulator.instance.stopped_at.is_none());
+
+        let result = emulator.execute_steps(10);
+        assert!(result.halted);
+        assert!(matches!(emulator.instance.state, InstanceState::Stopped));
+        assert!(emulator.instance.stopped_at.is_some());
+    }
+
+    #[test]
+    fn test_pacing_only_sleeps_the_remaining_time() {
+        let instance = EmulatorInstance::new("system".to_string(), EmulatorType::Turbo, None, None, None);
+        let mut emulator = Emulator::new_with_instance(instance);
+        let rate = emulator.instance.specs.max_cycles_per_second;
+        let progress = RunProgress { cpu_cycles: rate / 10, ..Default::default() }; // 100ms of work
+        
+        // 40ms already went on executing, so only the rest of the 100ms is slept
+        emulator.last_cycle_time = std::time::Instant::now() - std::time::Duration::from_millis(40);
+        let delay = emulator.pacing_delay(&progress);
+        assert!(delay <= std::time::Duration::from_millis(60), "{:?}", delay);
+        assert!(delay > std::time::Duration::from_millis(50), "{:?}", delay);
+        
+        // A CPU-bound run that is already behind its rate doesn't sleep at all
+        emulator.last_cycle_time = std::time::Instant::now() - std::time::Duration::from_millis(150);
+        assert_eq!(emulator.pacing_delay(&progress), std::time::Duration::ZERO);
+    }
+    
+    #[tokio::test]
+    async fn test_cancel_stops_long_run() {
+        let emulators = single_emulator("spin");
+        emulators.lock().unwrap().get_mut("spin").unwrap().load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+
+        let cap = 100 * EXECUTE_CHUNK_STEPS;
+        let run = tokio::spawn({
+            let emulators = emulators.clone();
+            async move { execute_chunked(&emulators, "spin", cap).await }
+        });
+        tokio::task::yield_now().await;
+        cancel_handler("spin".to_string(), emulators.clone()).await.unwrap();
+
+        let result = run.await.unwrap().unwrap();
+        assert_eq!(result.stop_reason, StopReason::Cancelled);
+        assert!(result.steps_executed < cap / 10);
+
+        // The flag doesn't leak into the next run
+        let result = execute_chunked(&emulators, "spin", 100).await.unwrap();
+        assert_eq!(result.stop_reason, StopReason::StepLimit);
+        assert_eq!(result.steps_executed, 100);
+    }
+    
+    #[test]
+    fn test_auto_recover_restores_checkpoint_after_cpu_error() {
+        let program = [
+            0xA9, 0x42, // LDA #$42
+            0x02,       // not a 6502 opcode
+        ];
+        
+        let mut emulator = Emulator::new();
+        emulator.load_program(0x8000, &program);
+        emulator.reset();
+        let result = emulator.execute_steps(10);
+        assert_eq!(result.stop_reason, StopReason::CpuError { message: "Unknown opcode: $02 at PC: $8002".to_string() });
+        assert_eq!(emulator.cpu.pc, 0x8002);
+        assert_eq!(emulator.cpu.a, 0x42);
+        
+        let mut emulator = Emulator::new();
+        emulator.instance.auto_recover = true;
+        emulator.load_program(0x8000, &program);
+        emulator.reset();
+        let result = emulator.execute_steps(10);
+        assert!(matches!(result.stop_reason, StopReason::Recovered { .. }));
+        assert_eq!(emulator.cpu.pc, 0x8000);
+        assert_eq!(emulator.cpu.a, 0x00);
+    }
+    
+    #[tokio::test]
+    async fn test_set_variant_fixes_indirect_jmp() {
+        let emulators = single_emulator("variant");
+        {
+            let mut emulators_lock = emulators.lock().unwrap();
+            let emulator = emulators_lock.get_mut("variant").unwrap();
+            emulator.load_program(0x8000, &[0x6C, 0xFF, 0x30]); // JMP ($30FF)
+            emulator.load_program(0x30FF, &[0x00]);
+            emulator.load_program(0x3000, &[0x40]); // NMOS takes the high byte from here
+            emulator.load_program(0x3100, &[0x50]);
+        }
+        
+        let reply = set_variant_handler("variant".to_string(), SetVariantRequest { variant: CpuVariant::Cmos65C02 }, emulators.clone())
+            .await
+            .unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["success"], true);
+        assert_eq!(body["data"]["cpu"]["variant"], "cmos65c02");
+        
+        let reply = step_handler("variant".to_string(), None, emulators.clone()).await.unwrap();
+        assert_eq!(reply_json(reply).await["data"]["cpu"]["pc"], 0x5000);
+        
+        // Refused while a run is in progress
+        emulators.lock().unwrap().get_mut("variant").unwrap().running = true;
+        let reply = set_variant_handler("variant".to_string(), SetVariantRequest { variant: CpuVariant::Nmos6502 }, emulators.clone())
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::CONFLICT);
+        assert_eq!(emulators.lock().unwrap()["variant"].cpu.variant, CpuVariant::Cmos65C02);
+    }
+    
+    #[tokio::test]
+    async fn test_delete_running_emulator_needs_force() {
+        let emulators = single_emulator("busy");
+        {
+            let mut lock = emulators.lock().unwrap();
+            let emulator = lock.get_mut("busy").unwrap();
+            emulator.load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+            emulator.cpu.set_pc(0x8000);
+        }
+        
+        let run = tokio::spawn({
+            let emulators = emulators.clone();
+            async move { execute_chunked(&emulators, "busy", u32::MAX).await }
+        });
+        while !emulators.lock().unwrap()["busy"].running {
+            tokio::task::yield_now().await;
+        }
+        
+        let reply = delete_emulator_handler("busy".to_string(), DeleteEmulatorQuery { force: false }, emulators.clone())
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::CONFLICT);
+        assert!(emulators.lock().unwrap().contains_key("busy"));
+        
+        // Forcing cancels the run and waits for it to stop before removing
+        let reply = delete_emulator_handler("busy".to_string(), DeleteEmulatorQuery { force: true }, emulators.clone())
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+        assert!(emulators.lock().unwrap().is_empty());
+        let result = run.await.unwrap().unwrap();
+        assert_eq!(result.stop_reason, StopReason::Cancelled);
+    }
+    
+    #[tokio::test]
+    async fn test_dropped_execute_clears_running() {
+        let emulators = single_emulator("dropped");
+        {
+            let mut lock = emulators.lock().unwrap();
+            let emulator = lock.get_mut("dropped").unwrap();
+            emulator.load_program(0x8000, &[0x4C, 0x00, 0x80]); // JMP $8000
+            emulator.cpu.set_pc(0x8000);
+        }
+        
+        let request = || ExecuteSteps { steps: u32::MAX, loop_threshold: None, memory_diff: false };
+        let run = tokio::spawn(execute_handler("dropped".to_string(), request(), emulators.clone()));
+        while !emulators.lock().unwrap()["dropped"].running {
+            tokio::task::yield_now().await;
+        }
+        
+        // A second run can't start alongside the first
+        let reply = execute_handler("dropped".to_string(), request(), emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::CONFLICT);
+        
+        // As warp does when the client disconnects
+        run.abort();
+        assert!(matches!(run.await, Err(e) if e.is_cancelled()));
+        assert!(!emulators.lock().unwrap()["dropped"].running);
+        
+        let reply = delete_emulator_handler("dropped".to_string(), DeleteEmulatorQuery { force: false }, emulators.clone())
+            .await
+            .unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+        assert!(emulators.lock().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_page_returns_the_whole_stack_page() {
+        let emulators = single_emulator("page");
+        {
+            let mut lock = emulators.lock().unwrap();
+            let emulator = lock.get_mut("page").unwrap();
+            for i in 0..=0xFFu16 {
+                emulator.memory.write(0x0100 + i, i as u8 ^ 0x5A);
+            }
+            // Neighbouring pages must not leak in
+            emulator.memory.write(0x00FF, 0x11);
+            emulator.memory.write(0x0200, 0x22);
+        }
+        let counts = emulators.lock().unwrap()["page"].memory.access_counts();
+        
+        let reply = read_page_handler("page".to_string(), 1, emulators.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["address"], 0x0100);
+        let data: Vec<u8> = serde_json::from_value(body["data"]["data"].clone()).unwrap();
+        assert_eq!(data.len(), 256);
+        
+        let mut lock = emulators.lock().unwrap();
+        let emulator = lock.get_mut("page").unwrap();
+        assert_eq!(emulator.memory.access_counts(), counts);
+        let direct: Vec<u8> = (0x0100..=0x01FF).map(|address| emulator.memory.read(address)).collect();
+        assert_eq!(data, direct);
+    }
+    
+    #[tokio::test]
+    async fn test_page_and_memory_reads_agree_on_open_bus() {
+        let emulators = single_emulator("bus");
+        {
+            let mut lock = emulators.lock().unwrap();
+            let emulator = lock.get_mut("bus").unwrap();
+            emulator.memory.add_unmapped_region("unmapped", 0xD000, 0xDFFF);
+            emulator.memory.open_bus = true;
+            emulator.memory.write(0x0010, 0x42);
+        }
+        let counts = emulators.lock().unwrap()["bus"].memory.access_counts();
+        
+        let reply = read_page_handler("bus".to_string(), 0xD0, emulators.clone()).await.unwrap();
+        let page: Vec<u8> = serde_json::from_value(reply_json(reply).await["data"]["data"].clone()).unwrap();
+        let query = MemoryRead { address: 0xD000, length: Some(256) };
+        let reply = read_memory_handler("bus".to_string(), query, Arc::new(ServerConfig::default()), emulators.clone()).await.unwrap();
+        let memory: Vec<u8> = serde_json::from_value(reply_json(reply).await["data"]["data"].clone()).unwrap();
+        
+        assert_eq!(page, vec![0x42; 256]);
+        assert_eq!(memory, page);
+        // Neither read moved the bus or counted as an access
+        assert_eq!(emulators.lock().unwrap()["bus"].memory.access_counts(), counts);
+    }
+    
+    #[tokio::test]
+    async fn test_import_rejects_bad_blobs() {
+        let emulators = single_emulator("import");
+        let import = |data_base64: String| import_state_handler("import".to_string(), StateExport { data_base64 }, emulators.clone());
+        
+        let reply = import("not base64!".to_string()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+        
+        let mut blob = {
+            let lock = emulators.lock().unwrap();
+            StateBlob::capture(&lock["import"].cpu, &lock["import"].memory).to_bytes()
+        };
+        blob[6] ^= 0x01;
+        let reply = import(base64::engine::general_purpose::STANDARD.encode(&blob)).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        assert!(reply_json(response).await["error"].as_str().unwrap().contains("integrity"));
+    }
+    
+    #[tokio::test]
+    async fn test_wait_halt_returns_once_the_run_halts() {
+        let emulators = single_emulator("waited");
+        emulators.lock().unwrap().get_mut("waited").unwrap().load_program(0x8000, &[
+            0xA2, 0x05, // LDX #$05
+            0xCA,       // DEX
+            0xD0, 0xFD, // BNE $8002
+            0x00,       // BRK
+        ]);
+        
+        // Not halted yet, so a short wait times out
+        let reply = wait_halt_handler("waited".to_string(), WaitHaltQuery { timeout_ms: Some(10) }, emulators.clone()).await.unwrap();
+        assert_eq!(reply_json(reply).await["data"]["halted"], false);
+        
+        let waiter = tokio::spawn(wait_halt_handler("waited".to_string(), WaitHaltQuery { timeout_ms: Some(10_000) }, emulators.clone()));
+        tokio::task::yield_now().await;
+        let result = execute_chunked(&emulators, "waited", 100).await.unwrap();
+        assert_eq!(result.steps_executed, 12);
+        
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(2), waiter).await.unwrap().unwrap().unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["halted"], true);
+        assert_eq!(body["data"]["cpu"]["x"], 0);
+    }
+    
+    #[tokio::test]
+    async fn test_binary_state_matches_json() {
+        let emulators = single_emulator("compact");
+        emulators.lock().unwrap().get_mut("compact").unwrap().load_program(0x8000, &[0xA2, 0x7E]); // LDX #$7E
+        
+        let accept = Some(format!("{}, application/json;q=0.5", BINARY_STATE_CONTENT_TYPE));
+        let reply = step_handler("compact".to_string(), accept, emulators.clone()).await.unwrap();
+        assert_eq!(reply.headers()["content-type"], BINARY_STATE_CONTENT_TYPE);
+        let bytes = warp::hyper::body::to_bytes(reply.into_body()).await.unwrap();
+        assert_eq!(bytes.len(), 24);
+        
+        let json = reply_json(get_state_handler("compact".to_string(), None, emulators.clone()).await.unwrap()).await;
+        let cpu = &json["data"]["cpu"];
+        assert_eq!(u16::from_le_bytes([bytes[0], bytes[1]]), cpu["pc"]);
+        for (i, register) in ["a", "x", "y", "sp", "status"].iter().enumerate() {
+            assert_eq!(bytes[2 + i], cpu[register], "{}", register);
+        }
+        assert_eq!(bytes[7] & 1 != 0, cpu["halted"]);
+        assert_eq!(u64::from_le_bytes(bytes[8..16].try_into().unwrap()), cpu["cycles"]);
+        assert_eq!(u64::from_le_bytes(bytes[16..24].try_into().unwrap()), cpu["cpu_cycles"]);
+        assert_eq!(bytes[3], 0x7E);
+        
+        // Anything else still gets JSON
+        let reply = get_state_handler("compact".to_string(), Some("*/*".to_string()), emulators).await.unwrap();
+        assert_eq!(reply_json(reply).await["success"], true);
+    }
+    
+    #[tokio::test]
+    async fn test_mnemonic_breakpoint_stops_at_first_jsr() {
+        let emulators = single_emulator("breaks");
+        emulators.lock().unwrap().get_mut("breaks").unwrap().load_program(0x8000, &[
+            0xA9, 0x01,       // LDA #$01
+            0xA2, 0x02,       // LDX #$02
+            0x20, 0x10, 0x80, // JSR $8010
+            0x20, 0x10, 0x80, // JSR $8010
+            0x00,             // BRK
+        ]);
+        emulators.lock().unwrap().get_mut("breaks").unwrap().load_program(0x8010, &[0x60]); // RTS
+        
+        let request = BreakpointsRequest { breakpoints: vec![Breakpoint::Mnemonic { mnemonic: "jsr".to_string() }] };
+        let reply = set_breakpoints_handler("breaks".to_string(), request, emulators.clone()).await.unwrap();
+        assert_eq!(reply_json(reply).await["success"], true);
+        
+        let result = execute_chunked(&emulators, "breaks", 100).await.unwrap();
+        assert_eq!(result.steps_executed, 2);
+        assert_eq!(result.final_state.pc, 0x8004);
+        assert!(matches!(result.stop_reason, StopReason::Breakpoint { pc: 0x8004, .. }));
+        
+        // Running again gets past it, to the next JSR
+        let result = execute_chunked(&emulators, "breaks", 100).await.unwrap();
+        assert_eq!(result.stop_reason, StopReason::Breakpoint {
+            pc: 0x8007,
+            breakpoint: Breakpoint::Mnemonic { mnemonic: "jsr".to_string() },
+        });
+        
+        // The same by opcode
+        emulators.lock().unwrap().get_mut("breaks").unwrap().reset();
+        emulators.lock().unwrap().get_mut("breaks").unwrap().breakpoints = vec![Breakpoint::Opcode { opcode: 0x20 }];
+        let result = execute_chunked(&emulators, "breaks", 100).await.unwrap();
+        assert_eq!(result.final_state.pc, 0x8004);
+        
+        let request = BreakpointsRequest { breakpoints: vec![Breakpoint::Mnemonic { mnemonic: "JSX".to_string() }] };
+        let reply = set_breakpoints_handler("breaks".to_string(), request, emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn test_create_instance_rejects_long_name_and_too_many_tags() {
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        let templates = Arc::new(Mutex::new(HashMap::new()));
+        init_default_templates(templates.clone());
+        let request = CreateInstanceRequest {
+            template_id: None,
+            emulator_type: EmulatorType::Standard,
+            name: Some("x".repeat(crate::instance_types::MAX_INSTANCE_NAME_LENGTH + 1)),
+            tags: Some((0..=crate::instance_types::MAX_INSTANCE_TAGS).map(|i| format!("tag{}", i)).collect()),
+            auto_start: None,
+            auto_recover: None,
+        };
+        
+        let reply = create_instance_handler(request, test_user("alice"), None, Arc::new(ServerConfig::default()), emulators.clone(), templates)
+            .await
+            .unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::BAD_REQUEST);
+        let body = reply_json(response).await;
+        let fields: Vec<&str> = body["fields"].as_array().unwrap().iter().map(|f| f["field"].as_str().unwrap()).collect();
+        assert_eq!(fields, vec!["name", "tags"]);
+        assert_eq!(body["fields"][0]["message"], "Name must be at most 64 characters");
+        assert_eq!(body["fields"][1]["message"], "At most 16 tags are allowed, not 17");
+        assert!(emulators.lock().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_copy_memory_overlapping_forward_is_memmove() {
+        let emulators = single_emulator("copy");
+        emulators.lock().unwrap().get_mut("copy").unwrap().load_program(0x0300, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        
+        // Destination overlaps the end of the source; a naive forward copy
+        // would smear the first bytes along
+        let request = MemoryCopy { src: 0x0300, dest: 0x0303, length: 6 };
+        let reply = copy_memory_handler("copy".to_string(), request, emulators.clone()).await.unwrap();
+        assert_eq!(reply_json(reply).await["success"], true);
+        
+        let mut expected = [1u8, 2, 3, 4, 5, 6, 7, 8, 0];
+        expected.copy_within(0..6, 3);
+        assert_eq!(emulators.lock().unwrap()["copy"].read_memory(0x0300, 9), expected);
+        assert_eq!(expected, [1, 2, 3, 1, 2, 3, 4, 5, 6]);
+        
+        // Either range running past $FFFF is refused
+        let request = MemoryCopy { src: 0x0300, dest: 0xFFFE, length: 3 };
+        let reply = copy_memory_handler("copy".to_string(), request, emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(emulators.lock().unwrap()["copy"].read_memory(0xFFFE, 2), [0, 0]);
+    }
+    
+    #[tokio::test]
+    async fn test_last_error_is_kept_until_reset() {
+        let emulators = single_emulator("broken");
+        emulators.lock().unwrap().get_mut("broken").unwrap().load_program(0x8000, &[0x02]); // not an opcode
+        
+        let reply = step_handler("broken".to_string(), None, emulators.clone()).await.unwrap();
+        assert_eq!(reply.status(), warp::http::StatusCode::UNPROCESSABLE_ENTITY);
+        
+        for _ in 0..2 {
+            let body = reply_json(get_state_handler("broken".to_string(), None, emulators.clone()).await.unwrap()).await;
+            let error = &body["data"]["cpu"]["last_error"];
+            assert_eq!(error["code"], "UnknownOpcode");
+            assert_eq!(error["pc"], 0x8000);
+            assert_eq!(error["message"], "Unknown opcode: $02 at PC: $8000");
+        }
+        
+        reset_handler("broken".to_string(), ResetQuery { cold: false }, emulators.clone(), Arc::new(Mutex::new(HashMap::new()))).await.unwrap();
+        let body = reply_json(get_state_handler("broken".to_string(), None, emulators).await.unwrap()).await;
+        assert!(body["data"]["cpu"]["last_error"].is_null());
+    }
+    
+    #[tokio::test]
+    async fn test_emulator_usable_after_panic_while_locked() {
+        let emulators = single_emulator("wedged");
+        let held = emulators.clone();
+        let panicked = std::thread::spawn(move || {
+            let _lock = held.lock().unwrap();
+            panic!("simulated handler panic");
+        })
+        .join();
+        assert!(panicked.is_err());
+        assert!(emulators.is_poisoned());
+
+        let json = reply_json(step_handler("wedged".to_string(), None, emulators.clone()).await.unwrap()).await;
+        assert_eq!(json["success"], true);
+        let json = reply_json(get_state_handler("wedged".to_string(), None, emulators.clone()).await.unwrap()).await;
+        assert_eq!(json["success"], true);
+        assert!(!emulators.is_poisoned());
+    }
+    
+    #[test]
+    fn test_parse_cors_origins() {
+        assert_eq!(
+            parse_cors_origins("https://app.example.com, http://localhost:3000/,,"),
+            CorsOrigins::List(vec!["https://app.example.com".to_string(), "http://localhost:3000".to_string()])
+        );
+        assert_eq!(parse_cors_origins("*"), CorsOrigins::Any);
+        assert_eq!(parse_cors_origins("https://app.example.com, *"), CorsOrigins::Any);
+        assert_eq!(
+            parse_cors_origins("example.com, https://app.example.com/path, https://ok.example.com"),
+            CorsOrigins::List(vec!["https://ok.example.com".to_string()])
+        );
+        assert_eq!(parse_cors_origins(""), CorsOrigins::List(Vec::new()));
+        
+        // Whatever is accepted can be handed to warp without panicking
+        let origins = parse_cors_origins("https://a.example.com:8443, http://[::1]:3030");
+        assert!(matches!(&origins, CorsOrigins::List(list) if list.len() == 2));
+        cors_filter(&origins).build();
+    }
+    
+    #[tokio::test]
+    async fn test_cold_reset_clears_ram_but_warm_reset_keeps_it() {
+        let templates = Arc::new(Mutex::new(HashMap::new()));
+        init_default_templates(templates.clone());
+        let instance = EmulatorInstance::new("alice".to_string(), EmulatorType::Standard, None, Some("basic-6502".to_string()), None);
+        let mut emulator = Emulator::new_with_instance(instance);
+        templates.lock().unwrap()["basic-6502"].apply_to(&mut emulator.memory).unwrap();
+        emulator.memory.load_rom(&[0xEA; 16], 0xF000);
+        emulator.memory.add_read_only_region("rom", 0xF000, 0xF00F);
+        emulator.memory.write(0x0200, 0x42);
+        emulator.memory.write_u16(RESET_VECTOR, 0x1234);
+        let emulators: EmulatorMap = Arc::new(Mutex::new(HashMap::new()));
+        emulators.lock().unwrap().insert("boot".to_string(), emulator);
+        
+        let json = reply_json(reset_handler("boot".to_string(), ResetQuery { cold: false }, emulators.clone(), templates.clone()).await.unwrap()).await;
+        assert_eq!(json["success"], true);
+        assert_eq!(json["data"]["cpu"]["pc"], 0x1234);
+        assert_eq!(emulators.lock().unwrap()["boot"].memory.read(0x0200), 0x42);
+        
+        // Cold: RAM is zeroed, ROM kept, and the template's reset vector is back
+        let json = reply_json(reset_handler("boot".to_string(), ResetQuery { cold: true }, emulators.clone(), templates.clone()).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["pc"], 0x8000);
+        {
+            let emulators_lock = emulators.lock().unwrap();
+            assert_eq!(emulators_lock["boot"].memory.read(0x0200), 0);
+            assert_eq!(emulators_lock["boot"].memory.read(0xF000), 0xEA);
+        }
+        
+        // Without a template, the reset vector set at creation survives
+        let mut plain = Emulator::new();
+        plain.set_reset_vector(0x9000);
+        plain.memory.write(0x0200, 0x42);
+        emulators.lock().unwrap().insert("plain".to_string(), plain);
+        let json = reply_json(reset_handler("plain".to_string(), ResetQuery { cold: true }, emulators.clone(), templates.clone()).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["pc"], 0x9000);
+        assert_eq!(emulators.lock().unwrap()["plain"].memory.read(0x0200), 0);
+    }
+    
+    #[tokio::test]
+    async fn test_load_example_rejects_unknown_program() {
+        let emulators = single_emulator("demo");
+        let reply = load_example_handler("demo".to_string(), LoadExample { name: "no-such-program".to_string() }, emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+        
+        let reply = load_example_handler("demo".to_string(), LoadExample { name: "multiply".to_string() }, emulators).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn test_get_state_is_counted() {
+        let counter = crate::metrics::API_REQUESTS_TOTAL.with_label_values(&["GET", "/emulator/:id", "200"]);
+        let before = counter.get();
+        
+        get_state_handler("counted".to_string(), None, single_emulator("counted")).await.unwrap();
+        assert!(counter.get() >= before + 1.0);
+    }
+    
+    #[tokio::test]
+    async fn test_unknown_emulator_is_404() {
+        let emulators = single_emulator("present");
+        
+        let reply = get_state_handler("missing".to_string(), None, emulators.clone()).await.unwrap();
+        let response = warp::Reply::into_response(reply);
+        assert_eq!(response.status(), warp::http::StatusCode::NOT_FOUND);
+        let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], EMULATOR_NOT_FOUND);
+        
+        let reply = step_handler("missing".to_string(), None, emulators.clone()).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::NOT_FOUND);
+        
+        let reply = get_state_handler("present".to_string(), None, emulators).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn test_metrics_include_simulated_cycles() {
+        init_metrics();
+        let emulators = single_emulator("cycles");
+        {
+            let mut lock = emulators.lock().unwrap();
+            let emulator = lock.get_mut("cycles").unwrap();
+            emulator.load_program(0x8000, &[0xEA, 0xEA]); // NOP; NOP
+            emulator.reset();
+            emulator.execute_steps(2);
+        }
+        
+        let reply = metrics_handler().await.unwrap();
+        let body = warp::hyper::body::to_bytes(warp::Reply::into_response(reply).into_body()).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        let count = text
+            .lines()
+            .find(|line| line.starts_with("instruction_cycles_count{instruction=\"NOP\"}"))
+            .and_then(|line| line.rsplit(' ').next())
+            .and_then(|n| n.parse::<f64>().ok())
+            .unwrap();
+        assert!(count >= 2.0);
+    }
+    
+    #[tokio::test]
+    async fn test_run_once_computes_result() {
+        // The handler has no emulator map, so there's nothing it could leave behind
+        let request = RunOnce {
+            program_base64: base64::engine::general_purpose::STANDARD.encode([
+                0xA9, 0x05,       // LDA #$05
+                0x18,             // CLC
+                0x69, 0x07,       // ADC #$07
+                0x8D, 0x00, 0x02, // STA $0200
+                0x00,             // BRK
+            ]),
+            address: 0x8000,
+            max_steps: 4,
+            read: Some(MemoryRead { address: 0x0200, length: Some(1) }),
+        };
+        
+        let slots = Arc::new(tokio::sync::Semaphore::new(1));
+        let reply = run_once_handler(request, Arc::new(ServerConfig::default()), slots.clone()).await.unwrap();
+        let body = reply_json(reply).await;
+        assert_eq!(body["data"]["execution"]["final_state"]["a"], 12);
+        assert_eq!(body["data"]["memory"]["data"], serde_json::json!([12]));
+        assert_eq!(slots.available_permits(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_run_once_is_capped() {
+        let request = |max_steps| RunOnce {
+            program_base64: base64::engine::general_purpose::STANDARD.encode([0xEA]), // NOP
+            address: 0x8000,
+            max_steps,
+            read: None,
+        };
+        let config = Arc::new(ServerConfig::default());
+        
+        // Every slot is taken, as by a platform full of run-once requests
+        let slots = Arc::new(tokio::sync::Semaphore::new(0));
+        let reply = run_once_handler(request(1), config.clone(), slots).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::SERVICE_UNAVAILABLE);
+        
+        // Load-and-run's ceiling is too high for run-once
+        let slots = Arc::new(tokio::sync::Semaphore::new(1));
+        let reply = run_once_handler(request(MAX_RUN_ONCE_STEPS + 1), config, slots).await.unwrap();
+        assert_eq!(warp::Reply::into_response(reply).status(), warp::http::StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn test_execute_returns_memory_diff() {
+        let emulators = single_emulator("stores");
+        emulators.lock().unwrap().get_mut("stores").unwrap().load_program(0x8000, &[
+            0xA9, 0x11,       // LDA #$11
+            0x8D, 0x00, 0x02, // STA $0200
+            0x8D, 0x00, 0x03, // STA $0300
+            0xA9, 0x22,       // LDA #$22
+            0x8D, 0x01, 0x02, // STA $0201
+            0xA9, 0x33,       // LDA #$33
+            0x8D, 0x00, 0x02, // STA $0200, overwriting the first store
+        ]);
+        
+        let request = ExecuteSteps { steps: 7, loop_threshold: None, memory_diff: true };
+        let json = reply_json(execute_handler("stores".to_string(), request, emulators.clone()).await.unwrap()).await;
+        assert_eq!(json["data"]["memory_diff"], serde_json::json!([
+            { "address": 0x0200, "value": 0x33 },
+            { "address": 0x0201, "value": 0x22 },
+            { "address": 0x0300, "value": 0x11 },
+        ]));
+        
+        // Not asked for, not returned
+        let request = ExecuteSteps { steps: 1, loop_threshold: None, memory_diff: false };
+        let json = reply_json(execute_handler("stores".to_string(), request, emulators).await.unwrap()).await;
+        assert!(json["data"].get("memory_diff").is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_step_returns_enriched_state() {
+        let emulators = single_emulator("rich");
+        emulators.lock().unwrap().get_mut("rich").unwrap().load_program(0x8000, &[
+            0xA9, 0x80, // LDA #$80
+            0x00,       // BRK
+        ]);
+        
+        let json = reply_json(step_handler("rich".to_string(), None, emulators.clone()).await.unwrap()).await;
+        let cpu = &json["data"]["cpu"];
+        assert_eq!(cpu["a"], 0x80);
+        assert_eq!(cpu["pc"], 0x8002);
+        assert_eq!(cpu["hex"]["a"], "$80");
+        assert_eq!(cpu["hex"]["pc"], "$8002");
+        assert_eq!(cpu["flags"]["negative"], true);
+        assert_eq!(cpu["flags"]["zero"], false);
+        assert_eq!(cpu["cycles"], 1);
+        assert!(cpu["cpu_cycles"].as_u64().unwrap() > 0);
+        assert_eq!(cpu["halted"], false);
+        assert!(cpu["halt_reason"].is_null());
+        assert_eq!(cpu["next_instruction"]["mnemonic"], "BRK");
+        
+        let json = reply_json(step_handler("rich".to_string(), None, emulators).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["halted"], true);
+        assert_eq!(json["data"]["cpu"]["halt_reason"], "Executed BRK");
+    }
+    
+    #[test]
+    fn test_file_user_store_survives_reopening() {
+        let path = std::env::temp_dir().join(format!("ec65-users-{}.json", Uuid::new_v4()));
+        
+        let users = UserStore::open(&path).unwrap();
+        assert!(users.lock().unwrap().is_empty());
+        let mut carol = test_user("carol");
+        let key = carol.create_api_key("ci".to_string(), vec![Permission::ReadEmulator], None);
+        {
+            let mut users_lock = users.lock().unwrap();
+            users_lock.insert(carol.id.clone(), carol);
+            users.save(&users_lock).unwrap();
+        }
+        // Using the key writes last_used out without any account change
+        crate::auth::authenticate_api_key(users.clone(), &key.key).unwrap();
+        
+        let reopened = UserStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let users_lock = reopened.lock().unwrap();
+        assert_eq!(users_lock["carol"].email, "carol@localhost");
+        assert_eq!(users_lock["carol"].api_keys[0].key_hash, crate::auth::hash_api_key(&key.key));
+        assert!(users_lock["carol"].api_keys[0].last_used.is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_resume_continues_after_brk() {
+        let emulators = single_emulator("paused");
+        emulators.lock().unwrap().get_mut("paused").unwrap().load_program(0x8000, &[
+            0x00,       // BRK
+            0xA9, 0x33, // LDA #$33
+        ]);
+        
+        step_handler("paused".to_string(), None, emulators.clone()).await.unwrap();
+        let json = reply_json(step_handler("paused".to_string(), None, emulators.clone()).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["halted"], true);
+        assert_eq!(json["data"]["cpu"]["a"], 0);
+        
+        let json = reply_json(resume_handler("paused".to_string(), ResumeQuery { pc: None }, emulators.clone()).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["halted"], false);
+        let json = reply_json(step_handler("paused".to_string(), None, emulators.clone()).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["a"], 0x33);
+        assert_eq!(json["data"]["cpu"]["pc"], 0x8003);
+        
+        let query = ResumeQuery { pc: Some(0x8001) };
+        let json = reply_json(resume_handler("paused".to_string(), query, emulators).await.unwrap()).await;
+        assert_eq!(json["data"]["cpu"]["pc"], 0x8001);
+    }
+    
+    #[test]
+    fn test_opcodes_used_breaks_ties_by_opcode() {
+        let mut emulator = Emulator::new();
+        // NOP, INX, NOP, INX, CLC: NOP ($EA) and INX ($E8) tie on 2
+        emulator.load_program(0x0200, &[0xEA, 0xE8, 0xEA, 0xE8, 0x18]);
+        
+        let usage = emulator.opcodes_used(0x0200, 5).unwrap();
+        let order: Vec<(u8, u32)> = usage.iter().map(|u| (u.opcode, u.count)).collect();
+        assert_eq!(order, vec![(0xE8, 2), (0xEA, 2), (0x18, 1)]);
+    }
+}
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use crate::cpu::CPU;
+use crate::cpu::{CpuVariant, CPU};
 use crate::memory::Memory;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,13 +14,15 @@ pub struct EmulatorSnapshot {
     pub owner_id: String,
     pub cpu_state: CpuSnapshot,
     pub memory_dump: Vec<u8>,
+    pub content_hash: String, // SHA-256 of the uncompressed memory
+    pub memory_ref: Option<String>, // Snapshot holding the memory blob when deduplicated
     pub metadata: SnapshotMetadata,
     pub created_at: DateTime<Utc>,
     pub size_bytes: u64,
     pub tags: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CpuSnapshot {
     pub a: u8,
     pub x: u8,
@@ -30,11 +32,15 @@ pub struct CpuSnapshot {
     pub status: u8,
     pub cycles: u64,
     pub halted: bool,
+    #[serde(default)]
+    pub variant: CpuVariant,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnapshotMetadata {
     pub emulator_type: String,
+    #[serde(default)]
+    pub cpu_variant: CpuVariant, // Copy of cpu_state.variant for listings; restores use cpu_state
     pub template_id: Option<String>,
     pub checkpoint_reason: CheckpointReason,
     pub instruction_count: u64,
@@ -58,12 +64,36 @@ pub struct CreateSnapshotRequest {
     pub description: Option<String>,
     pub tags: Option<Vec<String>>,
     pub compress: Option<bool>,
+    pub dedup: Option<bool>, // Share the memory blob of an identical existing snapshot
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RestoreSnapshotRequest {
     pub snapshot_id: String,
     pub force: Option<bool>, // Restore even if it would overwrite running state
+    pub target_emulator_id: Option<String>, // Defaults to the emulator the snapshot was taken from
+    #[serde(default)]
+    pub restore_mode: RestoreMode,
+}
+
+// Which parts of a snapshot to restore. Leaving one out keeps the
+// emulator's current registers or memory.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RestoreMode {
+    #[serde(default = "restore_part_default")]
+    pub registers: bool,
+    #[serde(default = "restore_part_default")]
+    pub memory: bool,
+}
+
+fn restore_part_default() -> bool {
+    true
+}
+
+impl Default for RestoreMode {
+    fn default() -> Self {
+        Self { registers: true, memory: true }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,11 +112,17 @@ pub struct SnapshotSummary {
     pub size_bytes: u64,
     pub checkpoint_reason: CheckpointReason,
     pub tags: Vec<String>,
+    pub content_hash: String,
 }
 
 pub type SnapshotStore = std::sync::Arc<std::sync::Mutex<HashMap<String, EmulatorSnapshot>>>;
 
+// Owner of snapshots taken without an authenticated user (e.g. local use).
+// Nobody matches it, so only admins can reach them.
+pub const SYSTEM_OWNER: &str = "system";
+
 impl EmulatorSnapshot {
+    #[allow(clippy::too_many_arguments)]
     pub fn create_from_emulator(
         name: String,
         description: String,
@@ -101,22 +137,10 @@ impl EmulatorSnapshot {
         execution_time_ms: u64,
         tags: Vec<String>,
     ) -> Self {
-        let cpu_state = CpuSnapshot {
-            a: cpu.get_register_a(),
-            x: cpu.get_register_x(),
-            y: cpu.get_register_y(),
-            pc: cpu.get_pc(),
-            sp: cpu.get_sp(),
-            status: cpu.get_status(),
-            cycles: 0, // We'd need to expose this from CPU
-            halted: cpu.is_halted(),
-        };
+        let cpu_state = CpuSnapshot::capture(cpu);
+        let memory_dump = dump_memory(memory);
         
-        // Create memory dump
-        let mut memory_dump = Vec::with_capacity(65536);
-        for addr in 0..65536 {
-            memory_dump.push(memory.read(addr as u16));
-        }
+        let content_hash = hash_memory(&memory_dump);
         
         // Compress memory if mostly zeros (common case)
         let original_size = memory_dump.len();
@@ -125,6 +149,7 @@ impl EmulatorSnapshot {
         
         let metadata = SnapshotMetadata {
             emulator_type,
+            cpu_variant: cpu.variant,
             template_id,
             checkpoint_reason: reason,
             instruction_count,
@@ -132,6 +157,9 @@ impl EmulatorSnapshot {
             compression_ratio,
         };
         
+        // Every snapshot has an owner, or access checks would match nobody
+        let owner_id = if owner_id.is_empty() { SYSTEM_OWNER.to_string() } else { owner_id };
+        
         Self {
             id: Uuid::new_v4().to_string(),
             name,
@@ -140,6 +168,8 @@ impl EmulatorSnapshot {
             owner_id,
             cpu_state,
             memory_dump: compressed_dump.clone(),
+            content_hash,
+            memory_ref: None,
             metadata,
             created_at: Utc::now(),
             size_bytes: compressed_dump.len() as u64,
@@ -147,27 +177,68 @@ impl EmulatorSnapshot {
         }
     }
     
-    pub fn restore_to_emulator(&self, cpu: &mut CPU, memory: &mut Memory) -> Result<(), String> {
-        // Restore CPU state
-        cpu.set_register_a(self.cpu_state.a);
-        cpu.set_register_x(self.cpu_state.x);
-        cpu.set_register_y(self.cpu_state.y);
-        cpu.set_pc(self.cpu_state.pc);
-        cpu.set_sp(self.cpu_state.sp);
-        cpu.set_status(self.cpu_state.status);
+    // If another snapshot by the same owner has identical CPU state and memory,
+    // drop our copy of the memory and point at theirs instead. Returns whether
+    // a match was found.
+    pub fn deduplicate(&mut self, store: &HashMap<String, EmulatorSnapshot>) -> bool {
+        let original = store.values().find(|s| {
+            s.memory_ref.is_none()
+                && s.owner_id == self.owner_id
+                && s.content_hash == self.content_hash
+                && s.cpu_state == self.cpu_state
+        });
         
-        if self.cpu_state.halted {
-            cpu.halt();
+        match original {
+            Some(original) => {
+                self.memory_ref = Some(original.id.clone());
+                self.memory_dump = Vec::new();
+                self.size_bytes = 0;
+                true
+            }
+            None => false,
         }
-        
-        // Restore memory
-        let decompressed_memory = decompress_memory(&self.memory_dump)?;
+    }
+    
+    // Restore, following the memory reference of a deduplicated snapshot
+    pub fn restore_from_store(
+        &self,
+        store: &HashMap<String, EmulatorSnapshot>,
+        cpu: &mut CPU,
+        memory: &mut Memory,
+        mode: RestoreMode,
+    ) -> Result<(), String> {
+        match &self.memory_ref {
+            Some(id) => {
+                let original = store.get(id)
+                    .ok_or_else(|| format!("Referenced snapshot {} not found", id))?;
+                self.restore_with_dump(&original.memory_dump, cpu, memory, mode)
+            }
+            None => self.restore_to_emulator(cpu, memory, mode),
+        }
+    }
+    
+    pub fn restore_to_emulator(&self, cpu: &mut CPU, memory: &mut Memory, mode: RestoreMode) -> Result<(), String> {
+        if self.memory_ref.is_some() {
+            return Err("Snapshot memory is stored in another snapshot".to_string());
+        }
+        self.restore_with_dump(&self.memory_dump, cpu, memory, mode)
+    }
+    
+    fn restore_with_dump(&self, memory_dump: &[u8], cpu: &mut CPU, memory: &mut Memory, mode: RestoreMode) -> Result<(), String> {
+        // Validate the dump even when only registers are wanted, so a
+        // corrupt snapshot is reported rather than half-restored
+        let decompressed_memory = decompress_memory(memory_dump)?;
         if decompressed_memory.len() != 65536 {
             return Err("Invalid memory dump size".to_string());
         }
         
-        for (addr, &value) in decompressed_memory.iter().enumerate() {
-            memory.write(addr as u16, value);
+        if mode.registers {
+            self.cpu_state.apply(cpu);
+        }
+        
+        // Bypass read-only and I/O regions so ROM contents are restored too
+        if mode.memory {
+            memory.load_rom(&decompressed_memory, 0);
         }
         
         Ok(())
@@ -186,10 +257,187 @@ impl EmulatorSnapshot {
             size_bytes: self.size_bytes,
             checkpoint_reason: self.metadata.checkpoint_reason.clone(),
             tags: self.tags.clone(),
+            content_hash: self.content_hash.clone(),
+        }
+    }
+}
+
+impl CpuSnapshot {
+    pub fn capture(cpu: &CPU) -> Self {
+        Self {
+            a: cpu.get_register_a(),
+            x: cpu.get_register_x(),
+            y: cpu.get_register_y(),
+            pc: cpu.get_pc(),
+            sp: cpu.get_sp(),
+            status: cpu.get_status(),
+            cycles: cpu.cycles,
+            halted: cpu.is_halted(),
+            variant: cpu.variant,
+        }
+    }
+    
+    pub fn apply(&self, cpu: &mut CPU) {
+        cpu.set_register_a(self.a);
+        cpu.set_register_x(self.x);
+        cpu.set_register_y(self.y);
+        cpu.set_pc(self.pc);
+        cpu.set_sp(self.sp);
+        cpu.set_status(self.status);
+        cpu.variant = self.variant;
+        
+        cpu.cycles = self.cycles;
+        
+        if self.halted {
+            cpu.halt();
+        } else {
+            cpu.resume();
+        }
+    }
+}
+
+const STATE_BLOB_MAGIC: &[u8; 4] = b"EC65";
+const STATE_BLOB_VERSION: u8 = 2;
+const STATE_BLOB_HEADER_LEN: usize = 22;
+const STATE_BLOB_V1_HEADER_LEN: usize = 21; // No variant byte; always NMOS
+const STATE_BLOB_HASH_LEN: usize = 32;
+
+// Portable binary form of an emulator's CPU and memory. The layout is
+//
+//   "EC65" version a x y pc(le16) sp status cycles(le64) halted variant memory(RLE) sha256
+//
+// where the trailing SHA-256 covers every byte before it, so a damaged or
+// truncated blob is rejected rather than loaded. The variant is 0 for the
+// NMOS 6502 and 1 for the 65C02; version 1 blobs have no variant byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateBlob {
+    pub cpu_state: CpuSnapshot,
+    pub memory: Vec<u8>,
+}
+
+impl StateBlob {
+    pub fn capture(cpu: &CPU, memory: &Memory) -> Self {
+        Self {
+            cpu_state: CpuSnapshot::capture(cpu),
+            memory: dump_memory(memory),
+        }
+    }
+    
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let state = &self.cpu_state;
+        let mut blob = Vec::new();
+        blob.extend_from_slice(STATE_BLOB_MAGIC);
+        blob.push(STATE_BLOB_VERSION);
+        blob.extend_from_slice(&[state.a, state.x, state.y]);
+        blob.extend_from_slice(&state.pc.to_le_bytes());
+        blob.extend_from_slice(&[state.sp, state.status]);
+        blob.extend_from_slice(&state.cycles.to_le_bytes());
+        blob.push(state.halted as u8);
+        blob.push(match state.variant {
+            CpuVariant::Nmos6502 => 0,
+            CpuVariant::Cmos65C02 => 1,
+        });
+        blob.extend_from_slice(&compress_memory(&self.memory));
+        
+        let hash = sha256(&blob);
+        blob.extend_from_slice(&hash);
+        blob
+    }
+    
+    pub fn from_bytes(blob: &[u8]) -> Result<Self, String> {
+        if blob.len() < STATE_BLOB_V1_HEADER_LEN + STATE_BLOB_HASH_LEN {
+            return Err("State blob is truncated".to_string());
+        }
+        
+        let (payload, hash) = blob.split_at(blob.len() - STATE_BLOB_HASH_LEN);
+        if sha256(payload).as_slice() != hash {
+            return Err("State blob failed integrity check (corrupt or truncated)".to_string());
+        }
+        if &payload[0..4] != STATE_BLOB_MAGIC {
+            return Err("Not an EC65 state blob".to_string());
+        }
+        let (variant, header_len) = match payload[4] {
+            1 => (CpuVariant::Nmos6502, STATE_BLOB_V1_HEADER_LEN),
+            STATE_BLOB_VERSION if payload.len() >= STATE_BLOB_HEADER_LEN => match payload[21] {
+                0 => (CpuVariant::Nmos6502, STATE_BLOB_HEADER_LEN),
+                1 => (CpuVariant::Cmos65C02, STATE_BLOB_HEADER_LEN),
+                other => return Err(format!("Unknown CPU variant {} in state blob", other)),
+            },
+            STATE_BLOB_VERSION => return Err("State blob is truncated".to_string()),
+            version => return Err(format!("Unsupported state blob version {}", version)),
+        };
+        
+        let cpu_state = CpuSnapshot {
+            a: payload[5],
+            x: payload[6],
+            y: payload[7],
+            pc: u16::from_le_bytes([payload[8], payload[9]]),
+            sp: payload[10],
+            status: payload[11],
+            cycles: u64::from_le_bytes(payload[12..20].try_into().unwrap()),
+            halted: payload[20] != 0,
+            variant,
+        };
+        let memory = decompress_memory(&payload[header_len..])?;
+        // Same check and message as restore_with_dump
+        if memory.len() != 65536 {
+            return Err("Invalid memory dump size".to_string());
         }
+        
+        Ok(Self { cpu_state, memory })
+    }
+    
+    pub fn restore(&self, cpu: &mut CPU, memory: &mut Memory) {
+        self.cpu_state.apply(cpu);
+        memory.load_rom(&self.memory, 0);
     }
 }
 
+// Copy of the whole address space, read without counting bus accesses
+fn dump_memory(memory: &Memory) -> Vec<u8> {
+    (0..=0xFFFF).map(|addr| memory.peek(addr)).collect()
+}
+
+// Remove a snapshot from the store. If other snapshots share its memory, the
+// blob moves to one of them and the rest are repointed there.
+pub fn remove_snapshot(store: &mut HashMap<String, EmulatorSnapshot>, id: &str) -> Option<EmulatorSnapshot> {
+    let removed = store.remove(id)?;
+    
+    if removed.memory_ref.is_none() {
+        let mut referrers: Vec<String> = store
+            .values()
+            .filter(|s| s.memory_ref.as_deref() == Some(id))
+            .map(|s| s.id.clone())
+            .collect();
+        referrers.sort();
+        
+        if let Some((new_owner, others)) = referrers.split_first() {
+            if let Some(snapshot) = store.get_mut(new_owner) {
+                snapshot.memory_ref = None;
+                snapshot.memory_dump = removed.memory_dump.clone();
+                snapshot.size_bytes = removed.size_bytes;
+            }
+            for other in others {
+                if let Some(snapshot) = store.get_mut(other) {
+                    snapshot.memory_ref = Some(new_owner.clone());
+                }
+            }
+        }
+    }
+    
+    Some(removed)
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    use sha2::{Sha256, Digest};
+    Sha256::digest(data).into()
+}
+
+// Lowercase hex SHA-256
+pub fn hash_memory(memory: &[u8]) -> String {
+    sha256(memory).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 // Simple run-length encoding for memory compression
 fn compress_memory(memory: &[u8]) -> Vec<u8> {
     let mut compressed = Vec::new();
@@ -267,7 +515,7 @@ fn decompress_memory(compressed: &[u8]) -> Result<Vec<u8>, String> {
     }
     
     if decompressed.len() != 65536 {
-        return Err(format!("Decompressed size {} != 65536", decompressed.len()));
+        return Err(format!("Invalid memory dump size ({} bytes)", decompressed.len()));
     }
     
     Ok(decompressed)
@@ -347,4 +595,194 @@ mod tests {
         
         assert_eq!(memory, decompressed);
     }
+    
+    #[test]
+    fn test_compression_is_deterministic() {
+        let mut memory = vec![0u8; 65536];
+        for (i, byte) in memory.iter_mut().enumerate().skip(0x8000).take(0x100) {
+            *byte = (i * 7) as u8;
+        }
+        
+        assert_eq!(compress_memory(&memory), compress_memory(&memory.clone()));
+    }
+    
+    fn snapshot_of(cpu: &CPU, memory: &Memory, name: &str) -> EmulatorSnapshot {
+        EmulatorSnapshot::create_from_emulator(
+            name.to_string(),
+            String::new(),
+            "emulator".to_string(),
+            "owner".to_string(),
+            cpu,
+            memory,
+            "6502".to_string(),
+            None,
+            CheckpointReason::Manual,
+            0,
+            0,
+            Vec::new(),
+        )
+    }
+    
+    #[test]
+    fn test_identical_state_shares_content_hash() {
+        let cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(0x0200, 0x42);
+        memory.write(0xFFFC, 0x00);
+        memory.write(0xFFFD, 0x80);
+        
+        let first = snapshot_of(&cpu, &memory, "first");
+        let second = snapshot_of(&cpu, &memory, "second");
+        
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.memory_dump, second.memory_dump);
+        assert_eq!(first.content_hash, second.content_hash);
+        assert_eq!(first.get_summary().content_hash, first.content_hash);
+        
+        memory.write(0x0200, 0x43);
+        let changed = snapshot_of(&cpu, &memory, "changed");
+        assert_ne!(changed.content_hash, first.content_hash);
+    }
+    
+    #[test]
+    fn test_dedup_stores_one_memory_blob() {
+        let cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(0x0200, 0x42);
+        
+        let mut store = HashMap::new();
+        let first = snapshot_of(&cpu, &memory, "first");
+        assert!(!first.clone().deduplicate(&store));
+        store.insert(first.id.clone(), first.clone());
+        
+        let mut second = snapshot_of(&cpu, &memory, "second");
+        assert!(second.deduplicate(&store));
+        store.insert(second.id.clone(), second.clone());
+        
+        let blobs = store.values().filter(|s| !s.memory_dump.is_empty()).count();
+        assert_eq!(blobs, 1);
+        assert_eq!(second.memory_ref.as_deref(), Some(first.id.as_str()));
+        assert_eq!(store.values().map(|s| s.size_bytes).sum::<u64>(), first.size_bytes);
+        
+        // Restore follows the reference
+        let mut restored_cpu = CPU::new();
+        let mut restored_memory = Memory::new();
+        second.restore_from_store(&store, &mut restored_cpu, &mut restored_memory, RestoreMode::default()).unwrap();
+        assert_eq!(restored_memory.read(0x0200), 0x42);
+        
+        // Deleting the original hands its blob to the duplicate
+        remove_snapshot(&mut store, &first.id);
+        let second = &store[&second.id];
+        assert!(second.memory_ref.is_none());
+        let mut restored_memory = Memory::new();
+        second.restore_to_emulator(&mut restored_cpu, &mut restored_memory, RestoreMode::default()).unwrap();
+        assert_eq!(restored_memory.read(0x0200), 0x42);
+    }
+    
+    #[test]
+    fn test_dedup_requires_matching_cpu_state() {
+        let mut cpu = CPU::new();
+        let memory = Memory::new();
+        
+        let mut store = HashMap::new();
+        let first = snapshot_of(&cpu, &memory, "first");
+        store.insert(first.id.clone(), first);
+        
+        cpu.set_register_a(0x01);
+        let mut second = snapshot_of(&cpu, &memory, "second");
+        assert!(!second.deduplicate(&store));
+        assert!(second.memory_ref.is_none());
+    }
+    
+    fn sample_state() -> (CPU, Memory) {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.write(0x0200, 0x42);
+        memory.write(0x8000, 0xEA);
+        cpu.set_register_a(0x12);
+        cpu.set_pc(0x8000);
+        cpu.cycles = 1234;
+        (cpu, memory)
+    }
+    
+    #[test]
+    fn test_restore_memory_only_keeps_registers() {
+        let (cpu, memory) = sample_state();
+        let snapshot = snapshot_of(&cpu, &memory, "memory-only");
+        
+        let mut target_cpu = CPU::new();
+        let mut target_memory = Memory::new();
+        target_cpu.set_register_a(0x99);
+        target_cpu.set_pc(0x4000);
+        target_memory.write(0x0200, 0x01);
+        let registers_before = CpuSnapshot::capture(&target_cpu);
+        
+        let mode = RestoreMode { registers: false, memory: true };
+        snapshot.restore_to_emulator(&mut target_cpu, &mut target_memory, mode).unwrap();
+        assert_eq!(CpuSnapshot::capture(&target_cpu), registers_before);
+        assert!((0..=0xFFFFu16).all(|a| target_memory.peek(a) == memory.peek(a)));
+        
+        // An omitted part defaults to being restored
+        let mode: RestoreMode = serde_json::from_str(r#"{"memory": false}"#).unwrap();
+        assert_eq!(mode, RestoreMode { registers: true, memory: false });
+    }
+    
+    #[test]
+    fn test_state_blob_round_trip() {
+        let (cpu, memory) = sample_state();
+        let blob = StateBlob::capture(&cpu, &memory).to_bytes();
+        
+        let imported = StateBlob::from_bytes(&blob).unwrap();
+        let mut restored_cpu = CPU::new();
+        let mut restored_memory = Memory::new();
+        imported.restore(&mut restored_cpu, &mut restored_memory);
+        
+        assert_eq!(CpuSnapshot::capture(&restored_cpu), CpuSnapshot::capture(&cpu));
+        assert_eq!(restored_memory.read(0x0200), 0x42);
+        assert_eq!(restored_memory.read(0x8000), 0xEA);
+    }
+    
+    #[test]
+    fn test_cmos_snapshot_restores_cmos_variant() {
+        let mut cpu = CPU::with_variant(CpuVariant::Cmos65C02);
+        let mut memory = Memory::new();
+        // JMP ($10FF): NMOS fetches the high byte from $1000, CMOS from $1100
+        memory.write(0x8000, 0x6C);
+        memory.write(0x8001, 0xFF);
+        memory.write(0x8002, 0x10);
+        memory.write(0x10FF, 0x34);
+        memory.write(0x1000, 0x56);
+        memory.write(0x1100, 0x12);
+        cpu.set_pc(0x8000);
+        
+        let snapshot = snapshot_of(&cpu, &memory, "cmos");
+        assert_eq!(snapshot.metadata.cpu_variant, CpuVariant::Cmos65C02);
+        
+        let mut restored_cpu = CPU::new();
+        let mut restored_memory = Memory::new();
+        snapshot.restore_to_emulator(&mut restored_cpu, &mut restored_memory, RestoreMode::default()).unwrap();
+        assert_eq!(restored_cpu.variant, CpuVariant::Cmos65C02);
+        restored_cpu.step(&mut restored_memory);
+        assert_eq!(restored_cpu.get_pc(), 0x1234);
+        
+        // The blob format carries the variant too
+        let blob = StateBlob::capture(&cpu, &memory).to_bytes();
+        assert_eq!(StateBlob::from_bytes(&blob).unwrap().cpu_state.variant, CpuVariant::Cmos65C02);
+    }
+    
+    #[test]
+    fn test_state_blob_rejects_corruption() {
+        let (cpu, memory) = sample_state();
+        let blob = StateBlob::capture(&cpu, &memory).to_bytes();
+        
+        let mut flipped = blob.clone();
+        flipped[6] ^= 0x01;
+        assert!(StateBlob::from_bytes(&flipped).unwrap_err().contains("integrity"));
+        
+        let truncated = &blob[..blob.len() - 1];
+        assert!(StateBlob::from_bytes(truncated).is_err());
+        
+        let short = StateBlob { cpu_state: CpuSnapshot::capture(&cpu), memory: vec![0; 0x100] }.to_bytes();
+        assert!(StateBlob::from_bytes(&short).unwrap_err().starts_with("Invalid memory dump size"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use crate::memory::IoHandler;
+
+/// Registers the timer occupies, from its base address
+pub const TIMER_REGISTERS: u16 = 4;
+
+// Register offsets
+const COUNTER_LOW: u16 = 0;
+const COUNTER_HIGH: u16 = 1;
+const CONTROL: u16 = 2;
+const STATUS: u16 = 3;
+
+/// Control register bits
+pub const TIMER_ENABLE: u8 = 0x01;
+pub const TIMER_IRQ_ENABLE: u8 = 0x02;
+
+/// Status register bit, set each time the counter reaches zero
+pub const TIMER_EXPIRED: u8 = 0x80;
+
+/// A decrementing interval timer, ticked by the CPU's clock cycles.
+///
+/// - base+0, base+1: reload value, low then high. Writing the high byte
+///   also loads the counter; reads return the current count.
+/// - base+2: control, `TIMER_ENABLE` | `TIMER_IRQ_ENABLE`
+/// - base+3: status; `TIMER_EXPIRED` is set when the counter reaches zero,
+///   and any write clears it
+///
+/// When the counter reaches zero it reloads and, if enabled, raises an IRQ.
+pub struct TimerDevice {
+    base: u16,
+    reload: u16,
+    counter: u16,
+    control: u8,
+    status: u8,
+}
+
+impl TimerDevice {
+    pub fn new(base: u16) -> Self {
+        Self {
+            base,
+            reload: 0,
+            counter: 0,
+            control: 0,
+            status: 0,
+        }
+    }
+}
+
+impl IoHandler for TimerDevice {
+    fn read(&self, address: u16) -> u8 {
+        match address.wrapping_sub(self.base) {
+            COUNTER_LOW => self.counter as u8,
+            COUNTER_HIGH => (self.counter >> 8) as u8,
+            CONTROL => self.control,
+            STATUS => self.status,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address.wrapping_sub(self.base) {
+            COUNTER_LOW => self.reload = (self.reload & 0xFF00) | value as u16,
+            COUNTER_HIGH => {
+                self.reload = (self.reload & 0x00FF) | (value as u16) << 8;
+                self.counter = self.reload;
+            }
+            CONTROL => self.control = value,
+            STATUS => self.status = 0,
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) -> bool {
+        // A zero reload would expire on every cycle; treat it as stopped
+        if self.control & TIMER_ENABLE == 0 || self.reload == 0 {
+            return false;
+        }
+        if self.counter == 0 {
+            self.counter = self.reload;
+        }
+
+        let mut remaining = cycles;
+        let mut expired = false;
+        while remaining >= self.counter as u64 {
+            remaining -= self.counter as u64;
+            self.counter = self.reload;
+            expired = true;
+        }
+        self.counter -= remaining as u16;
+
+        if expired {
+            self.status |= TIMER_EXPIRED;
+        }
+        expired && self.control & TIMER_IRQ_ENABLE != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{CPU, IRQ_VECTOR, RESET_VECTOR};
+    use crate::memory::Memory;
+
+    #[test]
+    fn test_timer_irq_fires_after_configured_cycles() {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        memory.add_io_handler("timer", 0xD000, 0xD000 + TIMER_REGISTERS - 1, Box::new(TimerDevice::new(0xD000)));
+        memory.load_rom(&[
+            0x58,             // CLI
+            0xEA,             // loop: NOP
+            0x4C, 0x01, 0x80, // JMP loop
+        ], 0x8000);
+        memory.load_rom(&[
+            0xE6, 0x10,       // INC $10
+            0x8D, 0x03, 0xD0, // STA $D003, acknowledging the timer
+            0x40,             // RTI
+        ], 0x9000);
+        memory.write_u16(RESET_VECTOR, 0x8000);
+        memory.write_u16(IRQ_VECTOR, 0x9000);
+        cpu.reset(&mut memory);
+        cpu.step(&mut memory); // CLI
+
+        // Every 100 cycles
+        memory.write(0xD000, 100);
+        memory.write(0xD001, 0);
+        memory.write(0xD002, TIMER_ENABLE | TIMER_IRQ_ENABLE);
+        let start = cpu.cycles;
+
+        while cpu.get_pc() != 0x9000 {
+            cpu.step(&mut memory);
+            assert!(cpu.cycles - start < 200, "no IRQ after {} cycles", cpu.cycles - start);
+        }
+        // NOP and JMP take 2 and 3 cycles, so the counter runs out during
+        // the instruction ending at cycle 100, and dispatch adds 7
+        assert_eq!(cpu.cycles - start, 100 + 7);
+        assert_eq!(memory.read(0xD003), TIMER_EXPIRED);
+
+        // The handler runs, acknowledges and returns; the timer keeps going
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!(memory.read(0x0010), 1);
+        assert_eq!(memory.read(0xD003), 0);
+        while memory.read(0x0010) < 2 {
+            cpu.step(&mut memory);
+            assert!(cpu.cycles - start < 400);
+        }
+    }
+}
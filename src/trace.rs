@@ -0,0 +1,187 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{
+    CARRY_FLAG, ZERO_FLAG, INTERRUPT_DISABLE, DECIMAL_MODE, BREAK_COMMAND, UNUSED_FLAG,
+    OVERFLOW_FLAG, NEGATIVE_FLAG,
+};
+
+pub const DEFAULT_TRACE_CAPACITY: usize = 1000;
+
+/// One executed instruction. Registers are the values after execution.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceEntry {
+    pub step: u64,
+    pub pc: u16,
+    pub opcode: u8,
+    pub instruction: &'static str,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub status_before: u8,
+    pub status_after: u8,
+    pub flags_changed: Vec<&'static str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TraceConfig {
+    pub enabled: bool,
+    pub capacity: Option<usize>,
+}
+
+/// Ring buffer of the most recent instructions. Tracing is opt-in per
+/// emulator; when it's off nothing is recorded.
+#[derive(Debug, Clone)]
+pub struct TraceBuffer {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl TraceBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Names of the status flags set in `mask`, most significant first
+pub fn flag_names(mask: u8) -> Vec<&'static str> {
+    [
+        (NEGATIVE_FLAG, "N"),
+        (OVERFLOW_FLAG, "V"),
+        (UNUSED_FLAG, "-"),
+        (BREAK_COMMAND, "B"),
+        (DECIMAL_MODE, "D"),
+        (INTERRUPT_DISABLE, "I"),
+        (ZERO_FLAG, "Z"),
+        (CARRY_FLAG, "C"),
+    ]
+    .iter()
+    .filter(|(flag, _)| mask & flag != 0)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+/// Stops a run just before a matching instruction executes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Breakpoint {
+    Address { address: u16 },
+    /// Any instruction with this opcode, wherever it is
+    Opcode { opcode: u8 },
+    /// Any instruction with this mnemonic (case-insensitive), in any
+    /// addressing mode
+    Mnemonic { mnemonic: String },
+}
+
+impl Breakpoint {
+    /// Whether the instruction about to execute at `pc` hits this
+    /// breakpoint. `mnemonic` is None for bytes that aren't an instruction.
+    pub fn matches(&self, pc: u16, opcode: u8, mnemonic: Option<&str>) -> bool {
+        match self {
+            Breakpoint::Address { address } => *address == pc,
+            Breakpoint::Opcode { opcode: wanted } => *wanted == opcode,
+            Breakpoint::Mnemonic { mnemonic: wanted } => mnemonic.is_some_and(|m| m.eq_ignore_ascii_case(wanted)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BreakpointsRequest {
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+pub const DEFAULT_PC_HISTOGRAM_CAPACITY: usize = 4096;
+
+#[derive(Debug, Deserialize)]
+pub struct PcHistogramConfig {
+    pub enabled: bool,
+    pub capacity: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PcCount {
+    pub pc: u16,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PcHistogramReport {
+    pub enabled: bool,
+    pub total: u64,
+    // Instructions at addresses first seen after the map was full
+    pub untracked: u64,
+    // Hottest first; ties in address order
+    pub entries: Vec<PcCount>,
+}
+
+/// How often each address has been executed. Like tracing this is opt-in,
+/// and at most `capacity` distinct addresses are tracked.
+#[derive(Debug, Clone)]
+pub struct PcHistogram {
+    capacity: usize,
+    counts: HashMap<u16, u64>,
+    total: u64,
+    untracked: u64,
+}
+
+impl PcHistogram {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            counts: HashMap::new(),
+            total: 0,
+            untracked: 0,
+        }
+    }
+
+    pub fn record(&mut self, pc: u16) {
+        self.total += 1;
+        if let Some(count) = self.counts.get_mut(&pc) {
+            *count += 1;
+        } else if self.counts.len() < self.capacity {
+            self.counts.insert(pc, 1);
+        } else {
+            self.untracked += 1;
+        }
+    }
+
+    pub fn report(&self) -> PcHistogramReport {
+        let mut entries: Vec<PcCount> = self.counts
+            .iter()
+            .map(|(&pc, &count)| PcCount { pc, count })
+            .collect();
+        entries.sort_by(|a, b| b.count.cmp(&a.count).then(a.pc.cmp(&b.pc)));
+        PcHistogramReport {
+            enabled: true,
+            total: self.total,
+            untracked: self.untracked,
+            entries,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.total = 0;
+        self.untracked = 0;
+    }
+}